@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    RotateClockwise,
+    RotateAntiClockwise,
+    Rotate180,
+    MoveLeft,
+    MoveDown,
+    MoveRight,
+    Drop,
+    Switch,
+    Hold,
+    ExportFumen,
+    Restart,
+    /// Shows or hides the controls over the game
+    Help,
+    Quit,
+}
@@ -0,0 +1,249 @@
+use tui::{backend::Backend, layout::Rect, text::Spans, widgets::Paragraph, Frame};
+
+use crate::{
+    positions::Position,
+    tetromino::TetrominoType,
+    theme::Marks,
+    ui::{collapsing_cell_shown, preview_size, stats_lines, Preview, Renderer, UIState},
+};
+
+/// Draws the board in plain text, `[]` for each block, for terminals without
+/// colour or connections too slow for a screen of coloured blocks. With
+/// `marks`, blocks of a known piece show its mark instead.
+pub struct AsciiRenderer {
+    marks: Marks,
+}
+
+impl<B: Backend> Renderer<B> for AsciiRenderer {
+    /// Enough for everything but the stats, which are cut off on the right.
+    fn min_size(&self, state: &UIState) -> (u16, u16) {
+        let (preview_width, _) = preview_size(state);
+        let width = state.board.first().map_or(0, Vec::len) as u16;
+        (
+            (preview_width * 2 + 1) * 2 + 1 + width * 2 + 4,
+            state.board.len() as u16 + 3,
+        )
+    }
+
+    fn draw_game(&self, frame: &mut Frame<B>, state: &UIState, title: &str, area: Rect) {
+        let lines = self
+            .lines(state, title)
+            .into_iter()
+            .map(Spans::from)
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    /// Right of the hold panel, the garbage meter and the left wall, under the
+    /// title.
+    fn board_area(&self, state: &UIState, area: Rect) -> Option<(Rect, u16)> {
+        let (preview_width, _) = preview_size(state);
+        let width = state.board.first().map_or(0, Vec::len) as u16;
+        let board = Rect::new(
+            area.x + preview_width * 2 + 4,
+            area.y + 1,
+            width * 2,
+            state.board.len() as u16,
+        );
+        Some((board.intersection(area), 2))
+    }
+}
+
+impl AsciiRenderer {
+    pub fn new(marks: Marks) -> Self {
+        Self { marks }
+    }
+
+    /// The two characters of a block of `piece`, if it's known.
+    fn block(&self, piece: Option<TetrominoType>) -> String {
+        piece
+            .and_then(|piece| self.marks.text(piece))
+            .map_or("[]".to_string(), |mark| mark.iter().collect())
+    }
+
+    /// The game as lines of text: the title, then the board with hold on its
+    /// left and the next queue and the stats on its right. Down the left side
+    /// of the board, the garbage meter shows `#` for lines ready to rise and
+    /// `+` for lines on their way.
+    fn lines(&self, state: &UIState, title: &str) -> Vec<String> {
+        let width = state.board.first().map_or(0, Vec::len);
+        let height = state.board.len();
+        let ready = state.garbage_ready as usize;
+        let garbage = ready + state.garbage_waiting as usize;
+        let size = preview_size(state);
+        let hold = self.panel("Hold", state.hold.as_slice(), size);
+        let next = self.panel("Next", &state.next, size);
+        let blank = " ".repeat(size.0 as usize * 2);
+        let mut stats = stats_lines(&state.stats, &state.pieces).into_iter();
+
+        let mut lines = vec![title.to_string()];
+        for (y, row) in state.board.iter().enumerate() {
+            let mut line = format!("{} ", hold.get(y).unwrap_or(&blank));
+            let from_bottom = height - y - 1;
+            line.push_str(if from_bottom < ready {
+                "#"
+            } else if from_bottom < garbage {
+                "+"
+            } else {
+                " "
+            });
+            line.push_str("<!");
+            for (x, colour) in row.iter().enumerate() {
+                let cell = Position {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                let clearing = state
+                    .clearing
+                    .as_ref()
+                    .filter(|(rows, _)| rows.contains(&(y as u32)));
+                line.push_str(&if let Some((_, progress)) = clearing {
+                    if collapsing_cell_shown(x, width, *progress) {
+                        "==".to_string()
+                    } else {
+                        "  ".to_string()
+                    }
+                } else if colour.is_some() {
+                    let piece = state.board_pieces.get(y).and_then(|row| row.get(x));
+                    self.block(piece.copied().flatten())
+                } else if state
+                    .tetromino_blocks
+                    .as_ref()
+                    .is_some_and(|blocks| blocks.contains(&cell))
+                {
+                    self.block(state.tetromino_type)
+                } else if state
+                    .target_blocks
+                    .as_ref()
+                    .is_some_and(|target| target.contains(&cell))
+                {
+                    "::".to_string()
+                } else if state
+                    .ghost_blocks
+                    .as_ref()
+                    .is_some_and(|ghost| ghost.contains(&cell))
+                {
+                    "()".to_string()
+                } else {
+                    " .".to_string()
+                });
+            }
+            line.push_str("!> ");
+            line.push_str(next.get(y).unwrap_or(&blank));
+            if let Some(stat) = stats.next() {
+                line.push(' ');
+                line.push_str(&stat);
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        let indent = " ".repeat(blank.len() + 2);
+        lines.push(format!("{}<!{}!>", indent, "=".repeat(width * 2)));
+        lines.push(format!("{}  {}", indent, "\\/".repeat(width)));
+        lines
+    }
+
+    /// A heading over `pieces`, each `size` cells across and down, with a blank
+    /// line after each.
+    fn panel(&self, heading: &str, pieces: &[Preview], (width, height): (u16, u16)) -> Vec<String> {
+        let width = width as usize * 2;
+        let mut lines = vec![format!("{:<width$}", heading)];
+        for piece in pieces {
+            for y in 0..height {
+                let row = (0..width / 2)
+                    .map(|x| {
+                        let block = Position {
+                            x: x as i32,
+                            y: y as i32,
+                        };
+                        if piece.blocks.contains(&block) {
+                            self.block(Some(piece.piece))
+                        } else {
+                            "  ".to_string()
+                        }
+                    })
+                    .collect();
+                lines.push(row);
+            }
+            lines.push(" ".repeat(width));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::{backend::TestBackend, layout::Rect};
+
+    use super::AsciiRenderer;
+    use crate::{
+        positions::Position,
+        tetromino::{Colour, TetrominoType},
+        theme::Marks,
+        ui::{stats_lines, Preview, Renderer, UIState},
+    };
+
+    fn state() -> UIState {
+        let mut board = vec![vec![None; 3]; 4];
+        board[3] = vec![Some(Colour::Red), None, Some(Colour::Blue)];
+        let mut board_pieces = vec![vec![None; 3]; 4];
+        board_pieces[3] = vec![Some(TetrominoType::Z), None, None];
+        let o = Preview::new(
+            &[(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| Position { x, y }),
+            Colour::Yellow,
+            TetrominoType::O,
+        );
+        UIState {
+            board,
+            board_pieces,
+            tetromino_blocks: Some(vec![Position { x: 1, y: 0 }, Position { x: 1, y: 1 }]),
+            tetromino_colour: Some(Colour::Cyan),
+            tetromino_type: Some(TetrominoType::I),
+            ghost_blocks: Some(vec![Position { x: 0, y: 2 }]),
+            target_blocks: Some(vec![Position { x: 1, y: 2 }, Position { x: 1, y: 3 }]),
+            next: vec![o],
+            garbage_waiting: 1,
+            garbage_ready: 1,
+            ..UIState::empty(3, 4)
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let state = state();
+        let stats = stats_lines(&state.stats, &state.pieces);
+        assert_eq!(
+            AsciiRenderer::new(Marks::None).lines(&state, "TETRIS!"),
+            [
+                "TETRIS!".to_string(),
+                format!("Hold      <! .[] .!> Next     {}", stats[0]),
+                format!("          <! .[] .!> [][]     {}", stats[1]),
+                format!("         +<!():: .!> [][]     {}", stats[2]),
+                format!("         #<![]::[]!>          {}", stats[3]),
+                "          <!======!>".to_string(),
+                "            \\/\\/\\/".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_board_area() {
+        let state = state();
+        let lines = AsciiRenderer::new(Marks::None).lines(&state, "TETRIS!");
+        let left = lines[1].find("<!").unwrap() as u16 + 2;
+        let area = Rect::new(5, 3, 60, 20);
+        assert_eq!(
+            Renderer::<TestBackend>::board_area(&AsciiRenderer::new(Marks::None), &state, area),
+            Some((Rect::new(5 + left, 4, 6, 4), 2))
+        );
+    }
+
+    #[test]
+    fn test_marks() {
+        let state = state();
+        let lines = AsciiRenderer::new(Marks::Letters).lines(&state, "TETRIS!");
+        assert!(lines[1].starts_with("Hold      <! .I  .!> Next"));
+        assert!(lines[2].starts_with("          <! .I  .!> O O "));
+        // Garbage and other blocks whose piece isn't known stay as they were.
+        assert!(lines[4].starts_with("         #<!Z ::[]!>"));
+    }
+}
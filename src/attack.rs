@@ -0,0 +1,110 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::events::TSpin;
+
+/// Lines of garbage sent for a clear, read from a TOML file like:
+///
+/// ```toml
+/// lines = [0, 0, 1, 2, 4]          # by lines cleared, from none up
+/// tspin = [0, 2, 4, 6]             # T-spins by lines cleared
+/// tspin_mini = [0, 0, 1]
+/// b2b = 1                          # extra for a back to back tetris or T-spin
+/// combo = [0, 0, 1, 1, 1, 2, 2, 3] # extra by combo count
+/// ```
+///
+/// Clears past the end of `lines` or the T-spin lists add a line each, so bigger
+/// pieces clearing five lines send one more than a tetris. Long combos use the
+/// last combo entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttackTable {
+    lines: Vec<u32>,
+    tspin: Vec<u32>,
+    tspin_mini: Vec<u32>,
+    b2b: u32,
+    combo: Vec<u32>,
+}
+
+impl Default for AttackTable {
+    /// The guideline table.
+    fn default() -> Self {
+        Self {
+            lines: vec![0, 0, 1, 2, 4],
+            tspin: vec![0, 2, 4, 6],
+            tspin_mini: vec![0, 0, 1],
+            b2b: 1,
+            combo: vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        }
+    }
+}
+
+impl AttackTable {
+    /// Reads an attack table file, for use as a command line value parser.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let table: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        if table.lines.is_empty() || table.tspin.is_empty() || table.tspin_mini.is_empty() {
+            return Err(anyhow!("attack table lists need at least one entry"));
+        }
+        Ok(table)
+    }
+
+    pub fn attack(&self, lines: u32, tspin: TSpin, b2b: bool, combo: u32) -> u32 {
+        if lines == 0 {
+            return 0;
+        }
+        let base = match tspin {
+            TSpin::None => &self.lines,
+            TSpin::Mini => &self.tspin_mini,
+            TSpin::Full => &self.tspin,
+        };
+        let last = base.len() as u32 - 1;
+        let clear = if lines > last {
+            base[last as usize] + (lines - last)
+        } else {
+            base[lines as usize]
+        };
+        let combo = self
+            .combo
+            .get(combo as usize)
+            .or_else(|| self.combo.last())
+            .copied()
+            .unwrap_or(0);
+        clear + combo + if b2b { self.b2b } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AttackTable;
+    use crate::events::TSpin;
+
+    #[test]
+    fn test_default_table() {
+        let table = AttackTable::default();
+        let plain = |lines| table.attack(lines, TSpin::None, false, 0);
+
+        assert_eq!(
+            (0..=5).map(plain).collect::<Vec<_>>(),
+            vec![0, 0, 1, 2, 4, 5]
+        );
+        assert_eq!(table.attack(3, TSpin::Full, true, 0), 7);
+        assert_eq!(table.attack(1, TSpin::Mini, false, 0), 0);
+        assert_eq!(table.attack(1, TSpin::None, false, 4), 1);
+        assert_eq!(table.attack(1, TSpin::None, false, 40), 5);
+        assert_eq!(table.attack(0, TSpin::Full, true, 3), 0);
+    }
+
+    #[test]
+    fn test_table_file() {
+        let table = toml::from_str::<AttackTable>(
+            "lines = [0, 1]\ntspin = [0]\ntspin_mini = [0]\nb2b = 0\ncombo = []",
+        )
+        .unwrap();
+        assert_eq!(table.attack(4, TSpin::None, true, 2), 4);
+        assert_eq!(table.attack(2, TSpin::Full, false, 0), 2);
+        assert!(toml::from_str::<AttackTable>("lines = [0]").is_err());
+    }
+}
@@ -1,17 +1,32 @@
-use crate::{positions::Position, tetramino_rules::Blocks, tetromino::Colour};
+use std::{fmt, str::FromStr};
 
-#[derive(Debug, Clone)]
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    positions::Position,
+    tetromino::{Colour, TetrominoType},
+};
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
 pub struct Cell {
     pub colour: Option<Colour>,
+    /// The piece that filled the cell, if it was one.
+    #[serde(default)]
+    pub piece: Option<TetrominoType>,
 }
 
 impl Cell {
     pub fn new() -> Self {
-        Self { colour: None }
+        Self {
+            colour: None,
+            piece: None,
+        }
     }
 
-    pub fn set_colour(&mut self, colour: Colour) {
+    pub fn fill(&mut self, colour: Colour, piece: TetrominoType) {
         self.colour = Some(colour);
+        self.piece = Some(piece);
     }
 
     pub fn filled(&self) -> bool {
@@ -19,7 +34,7 @@ impl Cell {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
 pub struct Board {
     width: u32,
     height: u32,
@@ -35,19 +50,55 @@ impl Board {
         }
     }
 
-    pub fn valid_position(&self, blocks: &Blocks) -> bool {
-        blocks.iter().all(|block| {
-            self.get_cell(block)
-                .map(|cell| !cell.filled())
-                .unwrap_or(false)
-        })
+    /// Builds a board from rows of cell colours, top row first.
+    pub fn from_colours(rows: Vec<Vec<Option<Colour>>>) -> Self {
+        Self {
+            width: rows.first().map(|row| row.len()).unwrap_or(0) as u32,
+            height: rows.len() as u32,
+            cells: rows
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|colour| Cell {
+                            colour,
+                            piece: None,
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
     }
 
-    pub fn add_blocks(&mut self, blocks: &Blocks, colour: Colour) -> u32 {
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn valid_position(&self, blocks: &[Position]) -> bool {
+        blocks.iter().all(|block| !self.blocked(block))
+    }
+
+    /// Whether `pos` is filled or outside the board.
+    pub fn blocked(&self, pos: &Position) -> bool {
+        self.get_cell(pos).map(|cell| cell.filled()).unwrap_or(true)
+    }
+
+    /// Fills `blocks` with a piece, leaving any rows they complete for
+    /// `remove_full_rows`.
+    pub fn add_blocks(&mut self, blocks: &[Position], colour: Colour, piece: TetrominoType) {
         blocks.iter().for_each(|block| {
-            self.get_cell_mut(block).unwrap().set_colour(colour);
+            self.get_cell_mut(block).unwrap().fill(colour, piece);
         });
-        self.remove_full_rows()
+    }
+
+    /// Indices of the full rows, counting down from the top.
+    pub fn full_rows(&self) -> Vec<u32> {
+        (0..self.height)
+            .filter(|y| self.cells[*y as usize].iter().all(|cell| cell.filled()))
+            .collect()
     }
 
     pub fn draw(&self) -> Vec<Vec<Option<Colour>>> {
@@ -57,13 +108,43 @@ impl Board {
             .collect::<Vec<_>>()
     }
 
-    fn remove_full_rows(&mut self) -> u32 {
-        let mut removed = 0;
+    /// The piece each cell was filled by, where it's known.
+    pub fn pieces(&self) -> Vec<Vec<Option<TetrominoType>>> {
+        self.cells
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.piece).collect())
+            .collect()
+    }
+
+    /// Pushes the stack up by `lines` rows of garbage, each with one gap at
+    /// column `hole`. Returns false if that pushed filled cells off the top.
+    pub fn add_garbage(&mut self, lines: u32, hole: u32) -> bool {
+        let lines = lines.min(self.height) as usize;
+        let overflowed = self.cells[..lines]
+            .iter()
+            .any(|row| row.iter().any(Cell::filled));
+        self.cells.drain(..lines);
+        for _ in 0..lines {
+            let mut row = vec![
+                Cell {
+                    colour: Some(Colour::Grey),
+                    piece: None,
+                };
+                self.width as usize
+            ];
+            row[hole as usize] = Cell::new();
+            self.cells.push(row);
+        }
+        !overflowed
+    }
+
+    /// Removes the full rows, dropping the rows above into their place, and
+    /// returns the indices they had.
+    pub fn remove_full_rows(&mut self) -> Vec<u32> {
+        let removed = self.full_rows();
         let mut new = Vec::with_capacity(self.height as usize);
-        for row in self.cells.iter().rev() {
-            if row.iter().all(|cell| cell.filled()) {
-                removed += 1;
-            } else {
+        for (y, row) in self.cells.iter().enumerate().rev() {
+            if !removed.contains(&(y as u32)) {
                 new.push(row.clone());
             }
         }
@@ -73,6 +154,28 @@ impl Board {
         removed
     }
 
+    /// The board with its rows resting on the floor of a board `height` rows tall.
+    pub fn with_height(&self, height: u32) -> anyhow::Result<Board> {
+        let filled = |row: &Vec<Cell>| row.iter().any(Cell::filled);
+        if self.cells.iter().rev().skip(height as usize).any(filled) {
+            bail!("board is taller than {} rows", height);
+        }
+        let mut cells = self
+            .cells
+            .iter()
+            .rev()
+            .take(height as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+        cells.resize_with(height as usize, || Self::new_row(self.width));
+        cells.reverse();
+        Ok(Self {
+            width: self.width,
+            height,
+            cells,
+        })
+    }
+
     fn get_cell(&self, pos: &Position) -> Option<&Cell> {
         self.cells
             .get(pos.y as usize)
@@ -95,3 +198,142 @@ impl Board {
         new
     }
 }
+
+/// Boards are written one row per line from the top, with `.` for an empty cell,
+/// the letter of the tetromino that filled a cell, or of the one with that
+/// cell's colour when it isn't known, and `G` for garbage. Pieces from a piece
+/// set and chaos mode's polyominoes are written as `G` too, as their letters
+/// can be a tetromino's.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .cells
+            .iter()
+            .map(|row| row.iter().map(cell_letter).collect())
+            .collect::<Vec<String>>();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+impl FromStr for Board {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        let rows = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().map(letter_colour).collect())
+            .collect::<anyhow::Result<Vec<Vec<_>>>>()?;
+        let width = rows.first().map(Vec::len).unwrap_or(0);
+        if rows.iter().any(|row| row.len() != width) {
+            bail!("board rows must all be {} cells wide", width);
+        }
+        Ok(Board::from_colours(rows))
+    }
+}
+
+fn cell_letter(cell: &Cell) -> char {
+    match (cell.piece, cell.colour) {
+        (_, None) => '.',
+        (Some(TetrominoType::Custom(_) | TetrominoType::Polyomino(_)), _) => 'G',
+        (Some(piece), _) => piece.letter(),
+        (None, Some(colour)) => colour
+            .tetromino_type()
+            .map(|type_| type_.letter())
+            .unwrap_or('G'),
+    }
+}
+
+fn letter_colour(letter: char) -> anyhow::Result<Option<Colour>> {
+    match letter {
+        '.' => Ok(None),
+        'G' => Ok(Some(Colour::Grey)),
+        letter => TetrominoType::from_letter(letter)
+            .map(|type_| Some(type_.colour()))
+            .ok_or_else(|| anyhow!("unknown board cell {:?}", letter)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Board;
+    use crate::{
+        positions::Position,
+        tetromino::{Colour, TetrominoType},
+    };
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = "....\n.TT.\nIOZG";
+        let board = text.parse::<Board>().unwrap();
+
+        assert_eq!(board.width(), 4);
+        assert_eq!(board.height(), 3);
+        assert_eq!(board.draw()[1][1], Some(Colour::Magenta));
+        assert_eq!(board.draw()[2][3], Some(Colour::Grey));
+        assert_eq!(board.to_string(), text);
+    }
+
+    #[test]
+    fn test_text_uses_piece_letters() {
+        let mut board = "...\n...".parse::<Board>().unwrap();
+        board.add_blocks(&[Position { x: 2, y: 1 }], Colour::Red, TetrominoType::T);
+
+        assert_eq!(board.to_string(), "...\n..T");
+    }
+
+    #[test]
+    fn test_text_other_pieces_are_garbage() {
+        let mut board = "...\n...".parse::<Board>().unwrap();
+        for (x, piece) in [TetrominoType::Custom('T'), TetrominoType::Polyomino(8)]
+            .into_iter()
+            .enumerate()
+        {
+            let position = Position { x: x as i32, y: 1 };
+            board.add_blocks(&[position], piece.colour(), piece);
+        }
+
+        assert_eq!(board.to_string(), "...\nGG.");
+    }
+
+    #[test]
+    fn test_text_errors() {
+        assert!("...\n....".parse::<Board>().is_err());
+        assert!("..X.".parse::<Board>().is_err());
+    }
+
+    #[test]
+    fn test_remove_full_rows() {
+        let mut board = "T..\nGGG\n.I.\nOOO".parse::<Board>().unwrap();
+
+        assert_eq!(board.full_rows(), vec![1, 3]);
+        assert_eq!(board.remove_full_rows(), vec![1, 3]);
+        assert_eq!(board.to_string(), "...\n...\nT..\n.I.");
+        assert!(board.remove_full_rows().is_empty());
+    }
+
+    #[test]
+    fn test_add_garbage() {
+        let mut board = "...\n...\nT..".parse::<Board>().unwrap();
+
+        assert!(board.add_garbage(1, 1));
+        assert_eq!(board.to_string(), "...\nT..\nG.G");
+        assert!(!board.add_garbage(2, 0));
+        assert_eq!(board.to_string(), "G.G\n.GG\n.GG");
+    }
+
+    #[test]
+    fn test_with_height() {
+        let board = "T..\nGG.".parse::<Board>().unwrap();
+
+        assert_eq!(
+            board.with_height(4).unwrap().to_string(),
+            "...\n...\nT..\nGG."
+        );
+        assert_eq!(
+            board.with_height(1).unwrap_err().to_string(),
+            "board is taller than 1 rows"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    action::Action, board::Board, finesse::Finesse, game::Tetris, tetramino_rules::TetrominoRules,
+    tetromino::Tetromino,
+};
+
+/// How much the bot cares about each feature of the board a placement leaves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Difficulty {
+    /// Loosely weighted and often careless
+    Easy,
+    /// Well weighted, with the odd slip
+    Medium,
+    /// Always takes the best placement it can see
+    Hard,
+}
+
+impl Difficulty {
+    /// Yiyuan Lee's tuned weights for the harder levels.
+    pub fn weights(&self) -> Weights {
+        match self {
+            Difficulty::Easy => Weights {
+                height: -0.3,
+                lines: 0.4,
+                holes: -0.1,
+                bumpiness: -0.1,
+            },
+            Difficulty::Medium | Difficulty::Hard => Weights {
+                height: -0.510066,
+                lines: 0.760666,
+                holes: -0.35663,
+                bumpiness: -0.184483,
+            },
+        }
+    }
+
+    /// The chance of dropping a piece somewhere at random instead.
+    pub fn mistake_rate(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Medium => 0.1,
+            Difficulty::Hard => 0.0,
+        }
+    }
+}
+
+/// A computer player that hard drops each piece where its weights like best,
+/// no faster than its pieces-per-second cap.
+pub struct Bot {
+    difficulty: Difficulty,
+    /// Time to spend on each piece.
+    interval: Duration,
+    waited: Duration,
+    rng: ChaCha12Rng,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty, pps: f64) -> Self {
+        Self {
+            difficulty,
+            interval: Duration::from_secs_f64(1.0 / pps),
+            waited: Duration::ZERO,
+            rng: ChaCha12Rng::from_rng(thread_rng()).unwrap(),
+        }
+    }
+
+    /// Plays `game` for `dt`, placing the falling piece once it has had it for
+    /// the bot's time per piece.
+    pub fn tick(&mut self, dt: Duration, game: &mut Tetris) {
+        if game.current().is_none() || game.game_over {
+            return;
+        }
+        self.waited = (self.waited + dt).min(self.interval);
+        if self.waited < self.interval {
+            return;
+        }
+        if let Some(route) = self.choose(game) {
+            self.waited = Duration::ZERO;
+            for action in route {
+                game.apply(action);
+            }
+        }
+    }
+
+    fn choose(&mut self, game: &Tetris) -> Option<Vec<Action>> {
+        let tet = game.current()?;
+        let mut landings = Finesse::landings(game.board(), game.rules(), tet);
+        if landings.is_empty() {
+            return None;
+        }
+        if self.rng.gen_bool(self.difficulty.mistake_rate()) {
+            let pick = self.rng.gen_range(0..landings.len());
+            return Some(landings.swap_remove(pick).1);
+        }
+        let weights = self.difficulty.weights();
+        landings
+            .into_iter()
+            .map(|(landed, route)| {
+                (
+                    evaluate(game.board(), game.rules(), &landed, &weights),
+                    route,
+                )
+            })
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, route)| route)
+    }
+}
+
+/// Scores the board left by locking `landed` on `board` and clearing any lines.
+pub fn evaluate(
+    board: &Board,
+    rules: &TetrominoRules,
+    landed: &Tetromino,
+    weights: &Weights,
+) -> f64 {
+    let mut board = board.clone();
+    board.add_blocks(&rules.blocks(landed), landed.colour, landed.type_);
+    let lines = board.remove_full_rows().len();
+
+    let rows = board.draw();
+    let height = rows.len();
+    let mut heights = vec![0; board.width() as usize];
+    let mut holes = 0;
+    for (x, column_height) in heights.iter_mut().enumerate() {
+        let top = rows.iter().position(|row| row[x].is_some());
+        if let Some(top) = top {
+            *column_height = height - top;
+            holes += rows[top..].iter().filter(|row| row[x].is_none()).count();
+        }
+    }
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    weights.height * heights.iter().sum::<usize>() as f64
+        + weights.lines * lines as f64
+        + weights.holes * holes as f64
+        + weights.bumpiness * bumpiness as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::{Bot, Difficulty};
+    use crate::{game::Tetris, settings::Settings};
+
+    fn game(field: &str) -> Tetris {
+        let field = field.parse::<crate::field::Field>().unwrap();
+        let mut game = Tetris::new(&Settings::parse_from([
+            "tetris".to_string(),
+            format!("--width={}", field.board.width()),
+            format!("--height={}", field.board.height()),
+        ]));
+        game.load_field(field).unwrap();
+        game.update();
+        game
+    }
+
+    #[test]
+    fn test_fills_the_well() {
+        let mut game = game(
+            "queue: I\n..........\n..........\nGGGGGGGGG.\nGGGGGGGGG.\nGGGGGGGGG.\nGGGGGGGGG.",
+        );
+        let mut bot = Bot::new(Difficulty::Hard, 2.0);
+
+        bot.tick(Duration::from_millis(400), &mut game);
+        assert_eq!(game.stats.lines, 0);
+        bot.tick(Duration::from_millis(100), &mut game);
+        assert_eq!(game.stats.lines, 4);
+    }
+
+    #[test]
+    fn test_avoids_holes() {
+        let mut game = game("queue: O\n....\n....\n....\nG..G");
+        let mut bot = Bot::new(Difficulty::Hard, 1.0);
+
+        bot.tick(Duration::from_secs(1), &mut game);
+        let filled = game
+            .board()
+            .draw()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| if cell.is_some() { '#' } else { '.' })
+                    .collect()
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(filled, vec!["....", "....", "....", ".##."]);
+    }
+}
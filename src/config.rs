@@ -0,0 +1,98 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use toml::{value::Table, Value};
+
+/// Reads the config file at `path`, a TOML table of settings by their flag
+/// names such as:
+///
+/// ```toml
+/// mode = "versus-bot"
+/// width = 12
+/// theme = "deuteranopia"
+/// mouse = true
+/// ```
+///
+/// A missing file has no settings.
+fn read(path: &Path) -> Result<Table> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            toml::from_str(&text).with_context(|| format!("config {:?} is not valid TOML", path))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Table::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read config {:?}", path)),
+    }
+}
+
+/// The settings in the config file at `path` as command line flags, `width =
+/// 12` as `--width=12`. A flag set to false is left out, as it's off anyway.
+pub fn args(path: &Path) -> Result<Vec<String>> {
+    let mut args = vec![];
+    for (key, value) in read(path)? {
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Boolean(true) => args.push(flag),
+            Value::Boolean(false) => {}
+            Value::String(text) => args.push(format!("{}={}", flag, text)),
+            Value::Integer(number) => args.push(format!("{}={}", flag, number)),
+            Value::Float(number) => args.push(format!("{}={}", flag, number)),
+            _ => {
+                return Err(anyhow!(
+                    "{:?} in config {:?} is not a string, number or boolean",
+                    key,
+                    path
+                ))
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Writes `changes` to the config file at `path`, keeping any other settings
+/// already in it.
+pub fn save(path: &Path, changes: impl IntoIterator<Item = (String, Value)>) -> Result<()> {
+    let mut table = read(path)?;
+    table.extend(changes);
+    fs::write(path, toml::to_string(&table)?)
+        .with_context(|| format!("failed to write config {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use toml::Value;
+
+    use super::{args, save};
+
+    #[test]
+    fn test_config_file() {
+        let path = env::temp_dir().join(format!("tetris_config_{}.toml", process::id()));
+        assert!(args(&path).unwrap().is_empty());
+
+        fs::write(&path, "width = 12\nline_clear_delay = 0.5\nmouse = false\n").unwrap();
+        save(
+            &path,
+            [
+                ("theme".to_string(), Value::String("rounded".to_string())),
+                ("mouse".to_string(), Value::Boolean(true)),
+            ],
+        )
+        .unwrap();
+        let written = args(&path);
+        fs::write(&path, "pieces = [\"I\"]").unwrap();
+        let nested = args(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            written.unwrap(),
+            [
+                "--line-clear-delay=0.5",
+                "--mouse",
+                "--theme=rounded",
+                "--width=12"
+            ]
+        );
+        assert!(nested.is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    backtrace::Backtrace,
+    fs::File,
+    io::Write,
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use log::error;
+
+use crate::{
+    spectate::Screen,
+    ui::{restore_terminal, UIState},
+};
+
+/// The games last drawn, for the crash file.
+static LAST_SCREEN: Mutex<Screen> = Mutex::new(Vec::new());
+
+/// Keeps `screen` as the last one drawn.
+pub fn record(screen: &[(UIState, String)]) {
+    if let Ok(mut last) = LAST_SCREEN.try_lock() {
+        *last = screen.to_vec();
+    }
+}
+
+/// On a panic in any thread, puts the terminal back, writes the panic and the
+/// last screen drawn to `path`, and exits. Left to itself, a panic in a tick
+/// thread would leave the game running with nothing drawing it and the
+/// terminal still in raw mode.
+pub fn install(path: PathBuf) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+        error!("{}", info);
+        let screen = LAST_SCREEN
+            .try_lock()
+            .map(|screen| screen.clone())
+            .unwrap_or_default();
+        match write(&path, &info.to_string(), &screen) {
+            Ok(()) => eprintln!("The game crashed, details are in {}", path.display()),
+            Err(err) => eprintln!("could not write crash file {}: {}", path.display(), err),
+        }
+        process::exit(101);
+    }));
+}
+
+/// Writes the crash file: the panic, a backtrace and the screen as JSON, which
+/// is enough to see the game as it was.
+fn write(path: &Path, panic: &str, screen: &[(UIState, String)]) -> Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", panic)?;
+    writeln!(file, "\n{}", Backtrace::force_capture())?;
+    writeln!(file, "Last screen drawn:")?;
+    serde_json::to_writer_pretty(&mut file, screen)?;
+    writeln!(file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::write;
+    use crate::ui::UIState;
+
+    #[test]
+    fn test_crash_file() {
+        let state = UIState {
+            score: 1234,
+            ..UIState::empty(4, 4)
+        };
+        let path = env::temp_dir().join(format!("tetris_crash_{}.log", process::id()));
+        write(
+            &path,
+            "panicked at src/board.rs:1:1:\noops",
+            &[(state, "TETRIS!".to_string())],
+        )
+        .unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(text.starts_with("panicked at src/board.rs:1:1:\noops\n"));
+        assert!(text.contains("\"score\": 1234"));
+        assert!(text.contains("\"TETRIS!\""));
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tetromino::{Tetromino, TetrominoType};
+
+/// Something that happened in a game, sent to every subscriber of `Tetris`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    PieceSpawned {
+        piece: Tetromino,
+    },
+    /// The falling piece was shifted or soft dropped by the player.
+    Moved {
+        piece: Tetromino,
+    },
+    /// The falling piece turned, using the kick at `kick` in its kick table.
+    Rotated {
+        piece: Tetromino,
+        kick: usize,
+    },
+    Locked {
+        piece: Tetromino,
+    },
+    /// `combo` counts the clears in a row before this one, and `b2b` is set
+    /// when this clear and the last one were both tetrises or T-spins.
+    LinesCleared {
+        rows: Vec<u32>,
+        tspin: TSpin,
+        b2b: bool,
+        combo: u32,
+    },
+    /// The falling piece went into the hold slot.
+    Hold {
+        held: TetrominoType,
+    },
+    LevelUp {
+        level: u32,
+    },
+    /// Lines of attack left over after cancelling any garbage on the way.
+    GarbageSent {
+        lines: u32,
+    },
+    /// Queued garbage rose into the stack.
+    GarbageReceived {
+        lines: u32,
+    },
+    GameOver {
+        reason: GameOverReason,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum GameOverReason {
+    /// The next piece overlapped the stack where it spawns.
+    BlockOut,
+    /// Garbage pushed the stack out of the top of the board.
+    TopOut,
+}
+
+/// The senders of everyone listening for events. Subscribers that have hung up
+/// are dropped on the next send.
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    senders: Vec<Sender<Event>>,
+}
+
+impl Subscribers {
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = channel();
+        self.senders.push(sender);
+        receiver
+    }
+
+    pub fn send(&mut self, event: Event) {
+        self.senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+
+use crate::{board::Board, tetromino::TetrominoType};
+
+const QUEUE_PREFIX: &str = "queue:";
+
+/// A starting field read from a text file: a board in its text format, optionally
+/// preceded by a `queue:` line listing the first pieces to spawn. Lines starting
+/// with `#` are comments.
+#[derive(Debug)]
+pub struct Field {
+    pub board: Board,
+    pub queue: Vec<TetrominoType>,
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut queue = vec![];
+        let mut rows = vec![];
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix(QUEUE_PREFIX) {
+                Some(pieces) => {
+                    for letter in pieces.chars().filter(|c| !c.is_whitespace()) {
+                        queue.push(
+                            TetrominoType::from_letter(letter)
+                                .ok_or_else(|| anyhow!("unknown piece {:?} in queue", letter))?,
+                        );
+                    }
+                }
+                None => rows.push(line),
+            }
+        }
+
+        Ok(Self {
+            board: rows.join("\n").parse()?,
+            queue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Field;
+    use crate::tetromino::TetrominoType;
+
+    #[test]
+    fn test_parse() {
+        let field = "
+            # a tetris ready stack
+            queue: I T
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+        "
+        .parse::<Field>()
+        .unwrap();
+
+        assert_eq!(field.queue, vec![TetrominoType::I, TetrominoType::T]);
+        assert_eq!(field.board.height(), 3);
+        assert_eq!(
+            field.board.to_string(),
+            "..........\nGGGGGGGGG.\nGGGGGGGGG."
+        );
+    }
+
+    #[test]
+    fn test_bad_queue() {
+        assert!("queue: IX\n....".parse::<Field>().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::Action, board::Board, positions::Position, tetramino_rules::TetrominoRules,
+    tetromino::Tetromino,
+};
+
+const MOVES: [Action; 5] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateClockwise,
+    Action::RotateAntiClockwise,
+    Action::Rotate180,
+];
+
+/// The cells a piece ends up occupying, shifted so the highest cell is on row 0.
+/// Placements that cover the same cells compare equal regardless of orientation.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Placement {
+    cells: Vec<(i32, i32)>,
+}
+
+impl Placement {
+    pub fn new(blocks: &[Position]) -> Self {
+        let top = blocks.iter().map(|pos| pos.y).min().unwrap_or(0);
+        let mut cells = blocks
+            .iter()
+            .map(|pos| (pos.x, pos.y - top))
+            .collect::<Vec<_>>();
+        cells.sort();
+        Self { cells }
+    }
+
+    /// Positions of this placement resting on the floor of an empty board.
+    pub fn landed(&self, height: u32) -> Vec<Position> {
+        let bottom = self.cells.iter().map(|(_, y)| *y).max().unwrap_or(0);
+        self.cells
+            .iter()
+            .map(|(x, y)| Position {
+                x: *x,
+                y: height as i32 - 1 - bottom + y,
+            })
+            .collect()
+    }
+}
+
+type Routes = HashMap<Placement, Vec<Action>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Finesse {
+    width: u32,
+    height: u32,
+    #[serde(skip)]
+    routes: HashMap<Tetromino, Routes>,
+}
+
+impl Finesse {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Every placement reachable from `spawn` on an empty board, with the shortest
+    /// sequence of inputs (ending in a hard drop) that reaches it.
+    pub fn routes(&mut self, rules: &TetrominoRules, spawn: &Tetromino) -> &Routes {
+        let (width, height) = (self.width, self.height);
+        self.routes
+            .entry(spawn.clone())
+            .or_insert_with(|| Self::search(&Board::new(width, height), rules, spawn))
+    }
+
+    /// The number of inputs used beyond the optimum for `placed`, or `None` when
+    /// the placement can't be reached by shifting and rotating from the spawn.
+    pub fn excess(
+        &mut self,
+        rules: &TetrominoRules,
+        spawn: &Tetromino,
+        inputs: &[Action],
+        placed: &Placement,
+    ) -> Option<usize> {
+        self.routes(rules, spawn)
+            .get(placed)
+            .map(|optimal| inputs.len().saturating_sub(optimal.len()))
+    }
+
+    fn search(board: &Board, rules: &TetrominoRules, spawn: &Tetromino) -> Routes {
+        Self::landings(board, rules, spawn)
+            .into_iter()
+            .map(|(landed, route)| (Placement::new(&rules.blocks(&landed)), route))
+            .collect()
+    }
+
+    /// Every distinct spot `tet` can land on `board` by shifting and rotating and
+    /// then hard dropping, with the shortest inputs that get there.
+    pub fn landings(
+        board: &Board,
+        rules: &TetrominoRules,
+        tet: &Tetromino,
+    ) -> Vec<(Tetromino, Vec<Action>)> {
+        let mut landings = vec![];
+        let mut placements = HashSet::new();
+        let mut seen = HashSet::from([tet.clone()]);
+        let mut queue = VecDeque::from([(tet.clone(), vec![])]);
+
+        while let Some((tet, path)) = queue.pop_front() {
+            let landed = Self::drop(board, rules, &tet);
+            if placements.insert(Placement::new(&rules.blocks(&landed))) {
+                let mut route = path.clone();
+                route.push(Action::Drop);
+                landings.push((landed, route));
+            }
+
+            for action in MOVES {
+                if let Some(next) = Self::step(board, rules, &tet, action) {
+                    if seen.insert(next.clone()) {
+                        let mut next_path = path.clone();
+                        next_path.push(action);
+                        queue.push_back((next, next_path));
+                    }
+                }
+            }
+        }
+        landings
+    }
+
+    fn step(
+        board: &Board,
+        rules: &TetrominoRules,
+        tet: &Tetromino,
+        action: Action,
+    ) -> Option<Tetromino> {
+        let valid = |tet: &Tetromino| board.valid_position(&rules.blocks(tet));
+        match action {
+            Action::MoveLeft => Some(rules.move_left(tet)).filter(valid),
+            Action::MoveRight => Some(rules.move_right(tet)).filter(valid),
+            Action::RotateClockwise => rules
+                .rotate(board, tet, tet.orientation.rotate_clockwise())
+                .map(|(rotated, _)| rotated),
+            Action::RotateAntiClockwise => rules
+                .rotate(board, tet, tet.orientation.rotate_anticlockwise())
+                .map(|(rotated, _)| rotated),
+            Action::Rotate180 => rules
+                .rotate(board, tet, tet.orientation.rotate_180())
+                .map(|(rotated, _)| rotated),
+            _ => None,
+        }
+    }
+
+    fn drop(board: &Board, rules: &TetrominoRules, tet: &Tetromino) -> Tetromino {
+        let mut dropped = tet.clone();
+        loop {
+            let next = rules.move_down(&dropped);
+            if !board.valid_position(&rules.blocks(&next)) {
+                return dropped;
+            }
+            dropped = next;
+        }
+    }
+}
+
+/// Drills one target placement at a time on an empty board.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FinesseTrainer {
+    pub target: Option<Placement>,
+    rng: ChaCha12Rng,
+}
+
+impl FinesseTrainer {
+    pub fn new() -> Self {
+        Self {
+            target: None,
+            rng: ChaCha12Rng::from_rng(thread_rng()).unwrap(),
+        }
+    }
+
+    pub fn next_target(&mut self, routes: &Routes) {
+        let mut placements = routes.keys().collect::<Vec<_>>();
+        placements.sort();
+        self.target = if placements.is_empty() {
+            None
+        } else {
+            Some(placements[self.rng.gen_range(0..placements.len())].clone())
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finesse, Placement};
+    use crate::{
+        action::Action,
+        positions::Position,
+        rotation::RotationSystemKind,
+        tetramino_rules::TetrominoRules,
+        tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+    };
+
+    fn spawn(type_: TetrominoType, orientation: Orientation) -> Tetromino {
+        Tetromino::new(3, Colour::Red, orientation, type_)
+    }
+
+    #[test]
+    fn test_placement_ignores_height() {
+        let low = [
+            Position { x: 0, y: 18 },
+            Position { x: 1, y: 18 },
+            Position { x: 1, y: 19 },
+            Position { x: 2, y: 19 },
+        ];
+        let high = low.map(|pos| Position {
+            y: pos.y - 10,
+            ..pos
+        });
+        assert_eq!(Placement::new(&low), Placement::new(&high));
+        assert_eq!(Placement::new(&low).landed(20), low.to_vec());
+    }
+
+    #[test]
+    fn test_spawn_column_is_a_single_drop() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let mut finesse = Finesse::new(10, 20);
+        let tet = spawn(TetrominoType::T, Orientation::Up);
+        let placement = Placement::new(&rules.blocks(&tet));
+
+        assert_eq!(
+            finesse.routes(&rules, &tet).get(&placement),
+            Some(&vec![Action::Drop])
+        );
+        assert_eq!(
+            finesse.excess(
+                &rules,
+                &tet,
+                &[Action::MoveLeft, Action::MoveRight, Action::Drop],
+                &placement
+            ),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_every_column_is_reachable() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let mut finesse = Finesse::new(10, 20);
+        let routes = finesse.routes(&rules, &spawn(TetrominoType::O, Orientation::Up));
+
+        assert_eq!(routes.len(), 9);
+        assert_eq!(routes.values().map(Vec::len).max(), Some(6));
+    }
+
+    #[test]
+    fn test_symmetric_orientations_share_placements() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let mut finesse = Finesse::new(10, 20);
+        let i_routes = finesse
+            .routes(&rules, &spawn(TetrominoType::I, Orientation::Up))
+            .len();
+
+        assert_eq!(i_routes, 7 + 10);
+    }
+}
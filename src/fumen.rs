@@ -0,0 +1,819 @@
+use anyhow::{anyhow, bail, Result};
+use strum::IntoEnumIterator;
+
+use crate::{
+    board::Board,
+    positions::Position,
+    tetramino_rules::TetrominoRules,
+    tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+};
+
+const PREFIXES: [&str; 3] = ["v115@", "m115@", "d115@"];
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE: u32 = COMMENT_TABLE.len() as u32 + 1;
+const FIELD_TOP: usize = 23;
+const FIELD_WIDTH: usize = 10;
+const FIELD_BLOCKS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+const GREY: u8 = 8;
+
+/// Cell codes row by row from the top, with the garbage row below the floor last.
+type Field = [u8; FIELD_BLOCKS];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Flags {
+    pub lock: bool,
+    pub rise: bool,
+    pub mirror: bool,
+    pub colorize: bool,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Self {
+            lock: true,
+            rise: false,
+            mirror: false,
+            colorize: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub field: Board,
+    /// The row just below the floor that rises into the field when `flags.rise` is set.
+    pub garbage: Vec<Option<Colour>>,
+    pub piece: Option<Tetromino>,
+    pub comment: Option<String>,
+    pub flags: Flags,
+}
+
+impl Page {
+    pub fn new(field: Board, piece: Option<Tetromino>) -> Self {
+        Self {
+            garbage: vec![None; field.width() as usize],
+            field,
+            piece,
+            comment: None,
+            flags: Flags::default(),
+        }
+    }
+}
+
+/// A piece in fumen's own coordinates: `x` and `y` locate the SRS rotation centre
+/// with `y` counting up from the bottom row, and `rotation` is fumen's numbering.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Piece {
+    type_: TetrominoType,
+    rotation: u32,
+    x: i32,
+    y: i32,
+}
+
+impl Piece {
+    fn cells(&self) -> Vec<(i32, i32)> {
+        let mut cells = Self::shape(self.type_)
+            .iter()
+            .map(|(x, y)| match self.rotation {
+                0 => (-x, -y),
+                1 => (*y, -x),
+                2 => (*x, *y),
+                _ => (-y, *x),
+            })
+            .map(|(x, y)| (self.x + x, self.y + y))
+            .collect::<Vec<_>>();
+        cells.sort();
+        cells
+    }
+
+    fn shape(type_: TetrominoType) -> [(i32, i32); 4] {
+        match type_ {
+            TetrominoType::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+            TetrominoType::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+            TetrominoType::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+            TetrominoType::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            TetrominoType::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+            TetrominoType::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+            TetrominoType::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+            TetrominoType::Custom(_) | TetrominoType::Polyomino(_) => {
+                unreachable!("fumen only has tetrominoes")
+            }
+        }
+    }
+
+    /// Fumen stores some pieces by a cell other than their SRS rotation centre.
+    fn legacy_offset(type_: TetrominoType, rotation: u32) -> (i32, i32) {
+        match (type_, rotation) {
+            (TetrominoType::O, 3) => (1, -1),
+            (TetrominoType::O, 0) => (1, 0),
+            (TetrominoType::O, 2) => (0, -1),
+            (TetrominoType::I, 0) => (1, 0),
+            (TetrominoType::I, 3) => (0, -1),
+            (TetrominoType::S, 2) => (0, -1),
+            (TetrominoType::S, 1) => (-1, 0),
+            (TetrominoType::Z, 2) => (0, -1),
+            (TetrominoType::Z, 3) => (1, 0),
+            _ => (0, 0),
+        }
+    }
+
+    fn decode(type_: TetrominoType, rotation: u32, coordinate: u32) -> Self {
+        let (dx, dy) = Self::legacy_offset(type_, rotation);
+        Self {
+            type_,
+            rotation,
+            x: (coordinate as usize % FIELD_WIDTH) as i32 + dx,
+            y: FIELD_TOP as i32 - (coordinate as usize / FIELD_WIDTH) as i32 - 1 + dy,
+        }
+    }
+
+    fn coordinate(&self) -> Result<u32> {
+        let (dx, dy) = Self::legacy_offset(self.type_, self.rotation);
+        let (x, y) = (self.x - dx, self.y - dy);
+        let coordinate = x + (FIELD_TOP as i32 - y - 1) * FIELD_WIDTH as i32;
+        if !(0..FIELD_BLOCKS as i32).contains(&coordinate) {
+            bail!(
+                "piece at ({}, {}) is outside the fumen field",
+                self.x,
+                self.y
+            );
+        }
+        Ok(coordinate as u32)
+    }
+}
+
+/// Decodes every page of a v115 fumen string. Anything before the version prefix,
+/// such as the editor URL, is ignored.
+pub fn decode(data: &str, rules: &TetrominoRules, height: u32) -> Result<Vec<Page>> {
+    let body = PREFIXES
+        .iter()
+        .find_map(|prefix| data.find(prefix).map(|i| &data[i + prefix.len()..]))
+        .ok_or_else(|| anyhow!("not a v115 fumen string: {:?}", data))?;
+    let mut reader = Reader::new(&body.replace('?', ""))?;
+
+    let mut pages = vec![];
+    let mut prev = [0; FIELD_BLOCKS];
+    let mut repeat = 0;
+    while !reader.is_empty() {
+        let mut field = prev;
+        if repeat > 0 {
+            repeat -= 1;
+        } else if !reader.read_field(&mut field)? {
+            repeat = reader.poll(1)?;
+        }
+
+        let mut value = reader.poll(3)?;
+        let type_ = decode_type(value % 8);
+        value /= 8;
+        let rotation = value % 4;
+        value /= 4;
+        let coordinate = value % FIELD_BLOCKS as u32;
+        value /= FIELD_BLOCKS as u32;
+        let mut flag = || {
+            let set = value % 2 == 1;
+            value /= 2;
+            set
+        };
+        let (rise, mirror, colorize, has_comment, locks) =
+            (flag(), flag(), flag(), flag(), !flag());
+        let flags = Flags {
+            lock: locks,
+            rise,
+            mirror,
+            colorize,
+        };
+
+        let comment = if has_comment {
+            Some(reader.read_comment()?)
+        } else {
+            None
+        };
+        let piece = type_.map(|type_| Piece::decode(type_, rotation, coordinate));
+
+        pages.push(Page {
+            field: board_from_field(&field, height)?,
+            garbage: (0..FIELD_WIDTH)
+                .map(|x| cell_colour(field[FIELD_TOP * FIELD_WIDTH + x]))
+                .collect(),
+            piece: piece
+                .map(|piece| tetromino_from_piece(rules, &piece, height))
+                .transpose()?,
+            comment,
+            flags,
+        });
+
+        if flags.lock {
+            apply(&mut field, piece.as_ref(), flags)?;
+        }
+        prev = field;
+    }
+    Ok(pages)
+}
+
+pub fn encode(pages: &[Page], rules: &TetrominoRules) -> Result<String> {
+    let mut values = vec![];
+    let mut prev = [0; FIELD_BLOCKS];
+    let mut repeat_index: Option<usize> = None;
+
+    for page in pages {
+        let mut field = field_from_page(page)?;
+        let (changed, field_values) = encode_field(&prev, &field);
+        match repeat_index {
+            _ if changed => {
+                values.extend(field_values);
+                repeat_index = None;
+            }
+            Some(index) if values[index] < ENCODE_TABLE.len() as u32 - 1 => values[index] += 1,
+            _ => {
+                values.extend(field_values);
+                values.push(0);
+                repeat_index = Some(values.len() - 1);
+            }
+        }
+
+        let height = page.field.height();
+        let piece = page
+            .piece
+            .as_ref()
+            .map(|tet| piece_from_tetromino(rules, tet, height))
+            .transpose()?;
+        let flags = [
+            !page.flags.lock,
+            page.comment.is_some(),
+            page.flags.colorize,
+            page.flags.mirror,
+            page.flags.rise,
+        ];
+        let mut value = flags.iter().fold(0, |value, flag| value * 2 + *flag as u32);
+        value =
+            value * FIELD_BLOCKS as u32 + piece.map(|p| p.coordinate()).transpose()?.unwrap_or(0);
+        value = value * 4 + piece.map(|p| p.rotation).unwrap_or(0);
+        value = value * 8 + piece.map(|p| encode_type(p.type_)).unwrap_or(0);
+        push(&mut values, value, 3);
+
+        if let Some(ref comment) = page.comment {
+            encode_comment(&mut values, comment)?;
+        }
+
+        if page.flags.lock {
+            apply(&mut field, piece.as_ref(), page.flags)?;
+        }
+        prev = field;
+    }
+
+    let data = values
+        .iter()
+        .map(|value| ENCODE_TABLE[*value as usize] as char)
+        .collect::<String>();
+    Ok(format!("{}{}", PREFIXES[0], split(&data)))
+}
+
+/// Fumen breaks long strings with `?` after the first 42 characters and every 47 after that.
+fn split(data: &str) -> String {
+    if data.len() <= 42 {
+        return data.to_string();
+    }
+    let (head, tail) = data.split_at(42);
+    let mut chunks = vec![head];
+    chunks.extend(
+        tail.as_bytes()
+            .chunks(47)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap()),
+    );
+    chunks.join("?")
+}
+
+struct Reader {
+    values: Vec<u32>,
+    index: usize,
+}
+
+impl Reader {
+    fn new(data: &str) -> Result<Self> {
+        let values = data
+            .bytes()
+            .map(|byte| {
+                ENCODE_TABLE
+                    .iter()
+                    .position(|c| *c == byte)
+                    .map(|value| value as u32)
+                    .ok_or_else(|| anyhow!("invalid character {:?} in fumen data", byte as char))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { values, index: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index >= self.values.len()
+    }
+
+    fn poll(&mut self, count: usize) -> Result<u32> {
+        let values = self
+            .values
+            .get(self.index..self.index + count)
+            .ok_or_else(|| anyhow!("fumen data ends unexpectedly"))?;
+        self.index += count;
+        Ok(values.iter().rev().fold(0, |acc, value| acc * 64 + value))
+    }
+
+    /// Applies the next field diff to `field`, returning whether anything changed.
+    fn read_field(&mut self, field: &mut Field) -> Result<bool> {
+        let mut changed = true;
+        let mut index = 0;
+        while index < FIELD_BLOCKS {
+            let value = self.poll(2)?;
+            let diff = (value / FIELD_BLOCKS as u32) as i32 - 8;
+            let count = (value % FIELD_BLOCKS as u32) as usize + 1;
+            if diff == 0 && count == FIELD_BLOCKS {
+                changed = false;
+            }
+            for cell in field.iter_mut().skip(index).take(count) {
+                let code = *cell as i32 + diff;
+                if !(0..=GREY as i32).contains(&code) {
+                    bail!("invalid block in fumen field");
+                }
+                *cell = code as u8;
+            }
+            index += count;
+        }
+        if index > FIELD_BLOCKS {
+            bail!("fumen field overflows the board");
+        }
+        Ok(changed)
+    }
+
+    fn read_comment(&mut self) -> Result<String> {
+        let length = self.poll(2)? as usize;
+        let mut escaped = String::with_capacity(length);
+        while escaped.len() < length {
+            let mut value = self.poll(5)?;
+            for _ in 0..4 {
+                let index = (value % COMMENT_BASE) as usize;
+                value /= COMMENT_BASE;
+                let c = COMMENT_TABLE
+                    .get(index)
+                    .ok_or_else(|| anyhow!("invalid character in fumen comment"))?;
+                escaped.push(*c as char);
+            }
+        }
+        escaped.truncate(length);
+        Ok(unescape(&escaped))
+    }
+}
+
+fn push(values: &mut Vec<u32>, mut value: u32, count: usize) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+fn encode_field(prev: &Field, field: &Field) -> (bool, Vec<u32>) {
+    let mut runs: Vec<(u32, u32)> = vec![];
+    for (old, new) in prev.iter().zip(field.iter()) {
+        let diff = (*new as i32 - *old as i32 + 8) as u32;
+        match runs.last_mut() {
+            Some((last, count)) if *last == diff => *count += 1,
+            _ => runs.push((diff, 1)),
+        }
+    }
+    let changed = !(runs.len() == 1 && runs[0].0 == 8);
+
+    let mut values = vec![];
+    for (diff, count) in runs {
+        push(&mut values, diff * FIELD_BLOCKS as u32 + count - 1, 2);
+    }
+    (changed, values)
+}
+
+fn encode_comment(values: &mut Vec<u32>, comment: &str) -> Result<()> {
+    let escaped = escape(comment);
+    if escaped.len() >= 64 * 64 {
+        bail!("comment is too long for fumen");
+    }
+    push(values, escaped.len() as u32, 2);
+    for chunk in escaped.as_bytes().chunks(4) {
+        let value = chunk.iter().rev().fold(0, |acc, c| {
+            let index = COMMENT_TABLE.iter().position(|t| t == c).unwrap() as u32;
+            acc * COMMENT_BASE + index
+        });
+        push(values, value, 5);
+    }
+    Ok(())
+}
+
+/// Locks `piece` into the field and applies the page's line clear, rise and mirror.
+fn apply(field: &mut Field, piece: Option<&Piece>, flags: Flags) -> Result<()> {
+    if let Some(piece) = piece {
+        for (x, y) in piece.cells() {
+            let row = FIELD_TOP as i32 - 1 - y;
+            if !(0..FIELD_WIDTH as i32).contains(&x) || !(0..FIELD_TOP as i32).contains(&row) {
+                bail!("fumen piece is outside the field");
+            }
+            field[row as usize * FIELD_WIDTH + x as usize] = encode_type(piece.type_) as u8;
+        }
+    }
+
+    let mut rows = field[..FIELD_TOP * FIELD_WIDTH]
+        .chunks(FIELD_WIDTH)
+        .filter(|row| row.contains(&0))
+        .map(|row| row.to_vec())
+        .collect::<Vec<_>>();
+    let garbage = field[FIELD_TOP * FIELD_WIDTH..].to_vec();
+    while rows.len() < FIELD_TOP {
+        rows.insert(0, vec![0; FIELD_WIDTH]);
+    }
+    if flags.rise {
+        rows.remove(0);
+        rows.push(garbage.clone());
+    }
+    if flags.mirror {
+        rows.iter_mut().for_each(|row| row.reverse());
+    }
+    rows.push(if flags.rise {
+        vec![0; FIELD_WIDTH]
+    } else {
+        garbage
+    });
+
+    field.copy_from_slice(&rows.concat());
+    Ok(())
+}
+
+fn decode_type(code: u32) -> Option<TetrominoType> {
+    match code {
+        1 => Some(TetrominoType::I),
+        2 => Some(TetrominoType::L),
+        3 => Some(TetrominoType::O),
+        4 => Some(TetrominoType::Z),
+        5 => Some(TetrominoType::T),
+        6 => Some(TetrominoType::J),
+        7 => Some(TetrominoType::S),
+        _ => None,
+    }
+}
+
+fn encode_type(type_: TetrominoType) -> u32 {
+    match type_ {
+        TetrominoType::I => 1,
+        TetrominoType::L => 2,
+        TetrominoType::O => 3,
+        TetrominoType::Z => 4,
+        TetrominoType::T => 5,
+        TetrominoType::J => 6,
+        TetrominoType::S => 7,
+        TetrominoType::Custom(_) | TetrominoType::Polyomino(_) => {
+            unreachable!("fumen only has tetrominoes")
+        }
+    }
+}
+
+fn cell_colour(code: u8) -> Option<Colour> {
+    match code {
+        0 => None,
+        GREY => Some(Colour::Grey),
+        code => decode_type(code as u32).map(|type_| type_.colour()),
+    }
+}
+
+/// The fumen code of a cell, by its colour unless it was filled by a piece
+/// fumen has no code for, which is written as garbage.
+fn cell_code(colour: Option<Colour>, piece: Option<TetrominoType>) -> u8 {
+    if let Some(TetrominoType::Custom(_) | TetrominoType::Polyomino(_)) = piece {
+        return GREY;
+    }
+    colour
+        .map(|colour| {
+            colour
+                .tetromino_type()
+                .map(|type_| encode_type(type_) as u8)
+                .unwrap_or(GREY)
+        })
+        .unwrap_or(0)
+}
+
+/// The fumen row that holds board row `y` when the board's floor sits on fumen's floor.
+fn field_row(y: u32, height: u32) -> i32 {
+    y as i32 + FIELD_TOP as i32 - height as i32
+}
+
+fn board_from_field(field: &Field, height: u32) -> Result<Board> {
+    if (0..field_row(0, height).max(0))
+        .any(|row| field[row as usize * FIELD_WIDTH..][..FIELD_WIDTH] != [0; FIELD_WIDTH])
+    {
+        bail!("fumen field is taller than a board of height {}", height);
+    }
+    let rows = (0..height)
+        .map(|y| match field_row(y, height) {
+            row if row < 0 => vec![None; FIELD_WIDTH],
+            row => field[row as usize * FIELD_WIDTH..][..FIELD_WIDTH]
+                .iter()
+                .map(|code| cell_colour(*code))
+                .collect(),
+        })
+        .collect();
+    Ok(Board::from_colours(rows))
+}
+
+fn field_from_page(page: &Page) -> Result<Field> {
+    if page.field.width() as usize != FIELD_WIDTH {
+        bail!(
+            "fumen fields are {} columns wide but the board has {}",
+            FIELD_WIDTH,
+            page.field.width()
+        );
+    }
+    let mut field = [0; FIELD_BLOCKS];
+    let pieces = page.field.pieces();
+    for (y, (row, pieces)) in page.field.draw().into_iter().zip(pieces).enumerate() {
+        let row_index = field_row(y as u32, page.field.height());
+        if row_index < 0 {
+            if row.iter().any(Option::is_some) {
+                bail!("board has blocks above the top of a fumen field");
+            }
+            continue;
+        }
+        for (x, (colour, piece)) in row.into_iter().zip(pieces).enumerate() {
+            field[row_index as usize * FIELD_WIDTH + x] = cell_code(colour, piece);
+        }
+    }
+    for (x, colour) in page.garbage.iter().enumerate().take(FIELD_WIDTH) {
+        field[FIELD_TOP * FIELD_WIDTH + x] = cell_code(*colour, None);
+    }
+    Ok(field)
+}
+
+fn orientation_for(rotation: u32) -> Orientation {
+    match rotation {
+        0 => Orientation::Down,
+        1 => Orientation::Right,
+        2 => Orientation::Up,
+        _ => Orientation::Left,
+    }
+}
+
+fn rotation_for(orientation: Orientation) -> u32 {
+    match orientation {
+        Orientation::Down => 0,
+        Orientation::Right => 1,
+        Orientation::Up => 2,
+        Orientation::Left => 3,
+    }
+}
+
+/// Finds the tetromino covering the same cells as `piece`, preferring the orientation
+/// fumen names so that symmetric pieces keep their rotation state.
+fn tetromino_from_piece(rules: &TetrominoRules, piece: &Piece, height: u32) -> Result<Tetromino> {
+    let mut cells = piece
+        .cells()
+        .into_iter()
+        .map(|(x, y)| Position {
+            x,
+            y: height as i32 - 1 - y,
+        })
+        .collect::<Vec<_>>();
+    cells.sort_by_key(|pos| (pos.x, pos.y));
+
+    let preferred = orientation_for(piece.rotation);
+    let orientations = std::iter::once(preferred)
+        .chain(Orientation::iter().filter(|or| *or != preferred))
+        .collect::<Vec<_>>();
+    for orientation in orientations {
+        let probe = Tetromino::new(0, piece.type_.colour(), orientation, piece.type_);
+        let anchor = rules.blocks(&probe)[0];
+        for cell in &cells {
+            let tet = Tetromino {
+                box_corner: Position {
+                    x: cell.x - anchor.x,
+                    y: cell.y - anchor.y,
+                },
+                ..probe.clone()
+            };
+            let mut blocks = rules.blocks(&tet).to_vec();
+            blocks.sort_by_key(|pos| (pos.x, pos.y));
+            if blocks == cells {
+                return Ok(tet);
+            }
+        }
+    }
+    Err(anyhow!(
+        "no {:?} piece covers the fumen piece's cells",
+        piece.type_
+    ))
+}
+
+fn piece_from_tetromino(rules: &TetrominoRules, tet: &Tetromino, height: u32) -> Result<Piece> {
+    if let TetrominoType::Custom(_) | TetrominoType::Polyomino(_) = tet.type_ {
+        return Err(anyhow!("{:?} piece has no fumen equivalent", tet.type_));
+    }
+    let mut cells = rules
+        .blocks(tet)
+        .iter()
+        .map(|pos| (pos.x, height as i32 - 1 - pos.y))
+        .collect::<Vec<_>>();
+    cells.sort();
+
+    let preferred = rotation_for(tet.orientation);
+    let rotations = std::iter::once(preferred).chain((0..4).filter(|r| *r != preferred));
+    for rotation in rotations {
+        for (x, y) in &cells {
+            let piece = Piece {
+                type_: tet.type_,
+                rotation,
+                x: *x,
+                y: *y,
+            };
+            if piece.cells() == cells {
+                return Ok(piece);
+            }
+        }
+    }
+    Err(anyhow!("{:?} piece has no fumen equivalent", tet.type_))
+}
+
+/// Percent-encodes `text` the way JavaScript's `escape` does, which fumen applies to comments.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) {
+            escaped.push(c);
+        } else if (c as u32) < 256 {
+            escaped.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                escaped.push_str(&format!("%u{:04X}", unit));
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut units = vec![];
+    let mut rest = escaped;
+    while let Some(c) = rest.chars().next() {
+        let parsed = match rest.strip_prefix("%u") {
+            Some(hex) => hex.get(..4).map(|hex| (hex, 6)),
+            None => rest
+                .strip_prefix('%')
+                .and_then(|hex| hex.get(..2))
+                .map(|hex| (hex, 3)),
+        }
+        .and_then(|(hex, len)| u16::from_str_radix(hex, 16).ok().map(|unit| (unit, len)));
+        match parsed {
+            Some((unit, len)) => {
+                units.push(unit);
+                rest = &rest[len..];
+            }
+            None => {
+                units.push(c as u16);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, split, Page};
+    use crate::{
+        board::Board,
+        positions::Position,
+        rotation::RotationSystemKind,
+        tetramino_rules::TetrominoRules,
+        tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+    };
+
+    // A page per piece of a bottom-row build, the first one commented "Opening".
+    const OPENING: &str = "v115@vhFRQYHAvItJEJmhCAUGJKJJvMJTNJGBJ";
+
+    #[test]
+    fn test_split() {
+        let data = "A".repeat(43);
+        assert_eq!(split(&data[..41]), data[..41]);
+        assert_eq!(split(&data[..42]), data[..42]);
+        assert_eq!(split(&data), format!("{}?A", &data[..42]));
+    }
+
+    #[test]
+    fn test_empty_field() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let pages = decode("v115@vhAAgH", &rules, 20).unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].piece.is_none());
+        assert!(pages[0].comment.is_none());
+        assert!(pages[0].field.draw().iter().flatten().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_garbage_field() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let pages = decode("https://harddrop.com/fumen/?v115@bhI8KeAgH", &rules, 20).unwrap();
+        let rows = pages[0].field.draw();
+
+        assert!(rows[..19].iter().flatten().all(Option::is_none));
+        assert_eq!(rows[19][..9], [Some(Colour::Grey); 9]);
+        assert_eq!(rows[19][9], None);
+    }
+
+    #[test]
+    fn test_pieces_and_comments() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let pages = decode(OPENING, &rules, 20).unwrap();
+
+        assert_eq!(pages.len(), 6);
+        assert_eq!(pages[0].comment.as_deref(), Some("Opening"));
+        assert!(pages[1].comment.is_none());
+
+        let i_piece = pages[0].piece.as_ref().unwrap();
+        assert_eq!(i_piece.type_, TetrominoType::I);
+        assert_eq!(i_piece.orientation, Orientation::Up);
+        assert_eq!(
+            rules.blocks(i_piece).to_vec(),
+            (3..7).map(|x| Position { x, y: 19 }).collect::<Vec<_>>()
+        );
+
+        let placed = pages[1].field.draw();
+        assert_eq!(
+            placed[19][2..8],
+            [
+                None,
+                Some(Colour::Cyan),
+                Some(Colour::Cyan),
+                Some(Colour::Cyan),
+                Some(Colour::Cyan),
+                None
+            ]
+        );
+        assert_eq!(pages[5].piece.as_ref().unwrap().type_, TetrominoType::J);
+    }
+
+    #[test]
+    fn test_round_trip_known_samples() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        for sample in ["v115@vhAAgH", "v115@bhI8KeAgH", OPENING] {
+            let pages = decode(sample, &rules, 20).unwrap();
+            assert_eq!(encode(&pages, &rules).unwrap(), sample);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_board() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let mut rows = vec![vec![None; 10]; 20];
+        rows[19] = vec![Some(Colour::Grey); 10];
+        rows[19][4] = None;
+        rows[18][0] = Some(Colour::Red);
+        rows[18][1] = Some(Colour::Orange);
+        let board = Board::from_colours(rows);
+        let mut pages = vec![];
+        for (x, orientation) in [
+            (0, Orientation::Up),
+            (4, Orientation::Left),
+            (7, Orientation::Down),
+        ] {
+            let piece = Tetromino::new(x, Colour::Magenta, orientation, TetrominoType::T);
+            let mut page = Page::new(board.clone(), Some(piece));
+            page.flags.lock = false;
+            page.comment = Some(format!("{:?} 100%", orientation));
+            pages.push(page);
+        }
+
+        let encoded = encode(&pages, &rules).unwrap();
+        assert!(encoded.contains('?'));
+        let decoded = decode(&encoded, &rules, 20).unwrap();
+
+        assert_eq!(decoded.len(), pages.len());
+        for (page, expected) in decoded.iter().zip(pages.iter()) {
+            assert_eq!(page.field.draw(), expected.field.draw());
+            assert_eq!(page.piece, expected.piece);
+            assert_eq!(page.comment, expected.comment);
+        }
+    }
+
+    #[test]
+    fn test_polyomino_cells_are_garbage() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let mut board = Board::new(10, 20);
+        let pentomino = TetrominoType::Polyomino(8);
+        board.add_blocks(&[Position { x: 0, y: 19 }], pentomino.colour(), pentomino);
+
+        let encoded = encode(&[Page::new(board, None)], &rules).unwrap();
+        let rows = decode(&encoded, &rules, 20).unwrap()[0].field.draw();
+        assert_eq!(rows[19][0], Some(Colour::Grey));
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        assert!(decode("v110@vhAAgH", &rules, 20).is_err());
+        assert!(decode("v115@vh", &rules, 20).is_err());
+        assert!(decode("v115@bhI8KeAgH", &rules, 0).is_err());
+        assert!(encode(&[Page::new(Board::new(8, 20), None)], &rules).is_err());
+    }
+}
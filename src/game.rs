@@ -1,116 +1,249 @@
-use log::info;
+use std::{collections::VecDeque, sync::mpsc::Receiver, time::Duration};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, trace};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    board::Board, spawner::TetrominoSpawner, tetramino_rules::TetrominoRules, tetromino::Tetromino,
-    ui::UIState,
+    action::Action,
+    attack::AttackTable,
+    board::Board,
+    events::{Event, GameOverReason, Subscribers, TSpin},
+    field::Field,
+    finesse::{Finesse, FinesseTrainer, Placement},
+    fumen::{self, Page},
+    polyominoes::Polyominoes,
+    settings::{Mode, Settings},
+    spawner::TetrominoSpawner,
+    stats::Statistics,
+    tetramino_rules::TetrominoRules,
+    tetromino::{Orientation, Tetromino, TetrominoType},
+    ui::{Preview, UIState},
 };
 
-#[derive(Debug)]
+/// Lines of garbage on their way to the board.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Garbage {
+    lines: u32,
+    /// Time left before the lines can rise.
+    remaining: Duration,
+}
+
+/// A pause in play after a piece locks, as in classic and TGM rulesets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Delay {
+    /// Full rows stay on the board until the line clear delay runs out.
+    LineClear { rows: Vec<u32>, remaining: Duration },
+    /// Entry delay (ARE) before the next piece spawns.
+    Spawn { remaining: Duration },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Tetris {
     board: Board,
     current_tet: Option<Tetromino>,
     spawner: TetrominoSpawner,
     rules: TetrominoRules,
+    gravity: Duration,
+    gravity_timer: Duration,
+    #[serde(default)]
+    line_clear_delay: Duration,
+    #[serde(default)]
+    spawn_delay: Duration,
+    #[serde(default)]
+    delay: Option<Delay>,
+    finesse: Finesse,
+    trainer: Option<FinesseTrainer>,
+    spawned: Option<Tetromino>,
+    inputs: Vec<Action>,
+    #[serde(default)]
+    held: Option<TetrominoType>,
+    #[serde(default)]
+    hold_used: bool,
+    /// The kick of the last rotation, until the piece next moves.
+    #[serde(default)]
+    last_kick: Option<usize>,
+    /// Clears in a row so far, or `None` after a piece that cleared nothing.
+    #[serde(default)]
+    combo: Option<u32>,
+    /// Whether the last clear was a tetris or T-spin.
+    #[serde(default)]
+    b2b: bool,
+    #[serde(default)]
+    attack_table: AttackTable,
+    #[serde(default)]
+    garbage_delay: Duration,
+    #[serde(default)]
+    garbage: VecDeque<Garbage>,
+    #[serde(skip)]
+    subscribers: Subscribers,
+    pub stats: Statistics,
     pub score: u32,
     pub game_over: bool,
 }
 
 impl Tetris {
-    pub fn new(rows: u32, cols: u32) -> Self {
+    pub fn new(settings: &Settings) -> Self {
         Self {
-            board: Board::new(rows, cols),
+            board: Board::new(settings.width, settings.height),
             current_tet: None::<Tetromino>,
             spawner: TetrominoSpawner::new(),
-            rules: TetrominoRules::new(),
+            rules: match (&settings.pieces, settings.polyominoes) {
+                (Some(pieces), _) => TetrominoRules::from_system(Box::new(pieces.clone())),
+                (None, Some(size)) => {
+                    TetrominoRules::from_system(Box::new(Polyominoes::new(size).unwrap()))
+                }
+                (None, None) => TetrominoRules::new(settings.rotation_system),
+            }
+            .with_kicks_180(settings.kicks_180.clone()),
+            gravity: Duration::from_secs_f64(settings.interval),
+            gravity_timer: Duration::ZERO,
+            line_clear_delay: Duration::from_secs_f64(settings.line_clear_delay),
+            spawn_delay: Duration::from_secs_f64(settings.spawn_delay),
+            delay: None,
+            finesse: Finesse::new(settings.width, settings.height),
+            trainer: (settings.mode == Mode::FinesseTrainer).then(FinesseTrainer::new),
+            spawned: None,
+            inputs: vec![],
+            held: None,
+            hold_used: false,
+            last_kick: None,
+            combo: None,
+            b2b: false,
+            attack_table: settings.attack_table.clone().unwrap_or_default(),
+            garbage_delay: Duration::from_secs_f64(settings.garbage_delay),
+            garbage: VecDeque::new(),
+            subscribers: Subscribers::default(),
+            stats: Statistics::new(),
             score: 0,
             game_over: false,
         }
     }
 
-    pub fn update(&mut self) {
-        info!("updating");
+    /// A game whose pieces, switches and garbage are decided by `seed`, so that
+    /// it plays out the same as any other game with the same seed, settings and
+    /// inputs.
+    pub fn seeded(settings: &Settings, seed: u64) -> Self {
+        let game = Self::new(settings);
+        Self {
+            spawner: TetrominoSpawner::seeded(seed),
+            rules: game.rules.seeded(seed),
+            ..game
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn rules(&self) -> &TetrominoRules {
+        &self.rules
+    }
+
+    /// The falling piece, if there is one.
+    pub fn current(&self) -> Option<&Tetromino> {
+        self.current_tet.as_ref()
+    }
+
+    /// Events from now on, until the returned receiver is dropped.
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        self.subscribers.subscribe()
+    }
+
+    /// Advances the game clock by `dt`, applying gravity once per elapsed interval.
+    /// Gravity waits while a line clear or entry delay runs.
+    pub fn tick(&mut self, dt: Duration) {
         if self.game_over {
             return;
         }
 
+        self.stats.elapsed += dt;
+        for garbage in self.garbage.iter_mut() {
+            garbage.remaining = garbage.remaining.saturating_sub(dt);
+        }
+        if self.delay.is_some() {
+            self.wait(dt);
+            return;
+        }
+        self.gravity_timer += dt;
+        while self.gravity_timer >= self.gravity && !self.game_over {
+            self.gravity_timer -= self.gravity;
+            self.update();
+        }
+    }
+
+    pub fn update(&mut self) {
+        debug!("updating");
+        if self.game_over || self.delay.is_some() {
+            return;
+        }
+
         if self.current_tet.is_some() {
-            self.move_tetromino_down();
+            self.fall();
         } else {
-            let new = self.spawner.spawn();
-            info!("new tetromino: {:?}", new);
-            if self.valid(&new) {
-                self.current_tet = Some(new);
-            } else {
-                info!("game over");
-                self.game_over = true;
-            }
+            self.spawn();
+        }
+    }
+
+    /// Applies one of the piece controls. Actions for the whole program, like
+    /// quitting, are left to the caller.
+    pub fn apply(&mut self, action: Action) {
+        match action {
+            Action::RotateClockwise => self.rotate_clockwise(),
+            Action::RotateAntiClockwise => self.rotate_anticlockwise(),
+            Action::Rotate180 => self.rotate_180(),
+            Action::MoveLeft => self.move_tetromino_left(),
+            Action::MoveRight => self.move_tetromino_right(),
+            Action::Drop => self.drop_tetromino(),
+            Action::MoveDown => self.move_tetromino_down(),
+            Action::Switch => self.switch_tetromino(),
+            Action::Hold => self.hold(),
+            Action::ExportFumen | Action::Restart | Action::Help | Action::Quit => {}
         }
     }
 
     pub fn move_tetromino_down(&mut self) {
         info!("moving tetromino down");
-        if let Some(ref tet) = self.current_tet {
-            if !self.game_over {
-                let new = self.rules.move_down(tet);
-
-                if !self.valid(&new) {
-                    self.score += Self::score_for_lines(
-                        self.board.add_blocks(&self.rules.blocks(tet), tet.colour),
-                    );
-                    self.current_tet = None;
-                } else {
-                    self.current_tet = Some(new);
-                }
-            }
+        self.record_key(Action::MoveDown);
+        if self.fall() {
+            self.moved();
         }
     }
 
     pub fn rotate_clockwise(&mut self) {
         info!("rotating tetromino clockwise");
+        self.record_key(Action::RotateClockwise);
         if let Some(ref tet) = self.current_tet {
-            if !self.game_over {
-                let new_orientation = tet.orientation.rotate_clockwise();
-
-                let new = self
-                    .rules
-                    .rotate(tet, new_orientation)
-                    .into_iter()
-                    .find(|tet| self.board.valid_position(&self.rules.blocks(tet)));
-
-                if new.is_some() {
-                    self.current_tet = new;
-                }
-            }
+            self.rotate(tet.orientation.rotate_clockwise());
         }
     }
 
     pub fn rotate_anticlockwise(&mut self) {
         info!("rotating tetromino anti clockwise");
+        self.record_key(Action::RotateAntiClockwise);
         if let Some(ref tet) = self.current_tet {
-            if !self.game_over {
-                let new_orientation = tet.orientation.rotate_anticlockwise();
-
-                let new = self
-                    .rules
-                    .rotate(tet, new_orientation)
-                    .into_iter()
-                    .find(|tet| self.valid(tet));
+            self.rotate(tet.orientation.rotate_anticlockwise());
+        }
+    }
 
-                if new.is_some() {
-                    self.current_tet = new;
-                }
-            }
+    pub fn rotate_180(&mut self) {
+        info!("rotating tetromino 180");
+        self.record_key(Action::Rotate180);
+        if let Some(ref tet) = self.current_tet {
+            self.rotate(tet.orientation.rotate_180());
         }
     }
 
     pub fn move_tetromino_left(&mut self) {
         info!("moving tetromino left");
+        self.record_key(Action::MoveLeft);
         if let Some(ref tet) = self.current_tet {
             if !self.game_over {
                 let new = self.rules.move_left(tet);
 
                 if self.valid(&new) {
                     self.current_tet = Some(new);
+                    self.moved();
                 }
             }
         }
@@ -118,12 +251,14 @@ impl Tetris {
 
     pub fn move_tetromino_right(&mut self) {
         info!("moving tetromino right");
+        self.record_key(Action::MoveRight);
         if let Some(ref tet) = self.current_tet {
             if !self.game_over {
                 let new = self.rules.move_right(tet);
 
                 if self.valid(&new) {
                     self.current_tet = Some(new);
+                    self.moved();
                 }
             }
         }
@@ -131,13 +266,15 @@ impl Tetris {
 
     pub fn drop_tetromino(&mut self) {
         info!("dropping tetromino");
+        self.record_key(Action::Drop);
         while self.current_tet.is_some() && !self.game_over {
-            self.move_tetromino_down();
+            self.fall();
         }
     }
 
     pub fn switch_tetromino(&mut self) {
         info!("switching tetromino");
+        self.record_key(Action::Switch);
         if let Some(ref tet) = self.current_tet {
             if !self.game_over {
                 let new = self.rules.switch(tet);
@@ -149,30 +286,885 @@ impl Tetris {
         }
     }
 
+    /// Queues `lines` of garbage from an opponent. It rises after the garbage delay,
+    /// when a piece locks without clearing anything.
+    pub fn receive_garbage(&mut self, lines: u32) {
+        self.garbage.push_back(Garbage {
+            lines,
+            remaining: self.garbage_delay,
+        });
+    }
+
+    /// Swaps the falling piece with the held one, or with the next piece when
+    /// nothing is held yet. Each piece can only be held once.
+    pub fn hold(&mut self) {
+        info!("holding tetromino");
+        self.record_key(Action::Hold);
+        if self.hold_used || self.game_over {
+            return;
+        }
+        if let Some(tet) = self.current_tet.take() {
+            self.hold_used = true;
+            let held = self.held.replace(tet.type_);
+            self.emit(Event::Hold { held: tet.type_ });
+            match held {
+                Some(type_) => {
+                    self.enter(Tetromino::new(0, type_.colour(), Orientation::Up, type_));
+                }
+                None => self.spawn(),
+            }
+        }
+    }
+
+    /// Replaces the board with the field of the first page of a fumen string.
+    pub fn load_fumen(&mut self, data: &str) -> Result<()> {
+        let page = fumen::decode(data, &self.rules, self.board.height())?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("fumen has no pages"))?;
+        self.set_board(page.field)
+    }
+
+    /// Starts from a text field, spawning its queued pieces first.
+    pub fn load_field(&mut self, field: Field) -> Result<()> {
+        self.set_board(field.board)?;
+        self.spawner.enqueue(field.queue);
+        Ok(())
+    }
+
+    /// Encodes the board and the falling piece as a single fumen page.
+    pub fn to_fumen(&self) -> Result<String> {
+        let page = Page::new(self.board.clone(), self.current_tet.clone());
+        fumen::encode(&[page], &self.rules)
+    }
+
     pub fn ui_state(&self) -> UIState {
         let state = UIState {
             board: self.board.draw(),
+            board_pieces: self.board.pieces(),
             tetromino_blocks: self.current_tet.as_ref().map(|t| self.rules.blocks(t)),
             game_over: self.game_over,
             tetromino_colour: self.current_tet.as_ref().map(|t| t.colour),
+            tetromino_type: self.current_tet.as_ref().map(|t| t.type_),
+            ghost_blocks: self
+                .current_tet
+                .as_ref()
+                .map(|t| self.rules.blocks(&self.landing(t))),
+            clearing: match self.delay {
+                Some(Delay::LineClear {
+                    ref rows,
+                    remaining,
+                }) => Some((
+                    rows.clone(),
+                    1.0 - remaining.as_secs_f64() / self.line_clear_delay.as_secs_f64(),
+                )),
+                _ => None,
+            },
+            target_blocks: self
+                .trainer
+                .as_ref()
+                .and_then(|trainer| trainer.target.as_ref())
+                .map(|target| target.landed(self.board.height())),
             score: self.score,
+            stats: self.stats.clone(),
+            pieces: self.rules.pieces(),
+            hold: self.held.map(|type_| {
+                self.preview(&Tetromino::new(0, type_.colour(), Orientation::Up, type_))
+            }),
+            next: self.spawner.next().map(|tet| self.preview(tet)).collect(),
+            garbage_waiting: self.garbage_lines(false),
+            garbage_ready: self.garbage_lines(true),
         };
-        info!("ui state: {:?}", state);
+        trace!("ui state: {:?}", state);
         state
     }
 
+    /// Where `tet` would land if it were dropped.
+    fn landing(&self, tet: &Tetromino) -> Tetromino {
+        let mut landing = tet.clone();
+        loop {
+            let lower = self.rules.move_down(&landing);
+            if !self.valid(&lower) {
+                return landing;
+            }
+            landing = lower;
+        }
+    }
+
+    /// `tet` as it looks when it spawns.
+    fn preview(&self, tet: &Tetromino) -> Preview {
+        let spawned = self.rules.spawn(tet, self.board.width());
+        Preview::new(&self.rules.blocks(&spawned), tet.colour, tet.type_)
+    }
+
+    fn garbage_lines(&self, ready: bool) -> u32 {
+        self.garbage
+            .iter()
+            .filter(|garbage| garbage.remaining.is_zero() == ready)
+            .map(|garbage| garbage.lines)
+            .sum()
+    }
+
+    fn set_board(&mut self, board: Board) -> Result<()> {
+        if board.width() != self.board.width() {
+            return Err(anyhow!(
+                "field is {} columns wide but the board has {}",
+                board.width(),
+                self.board.width()
+            ));
+        }
+        self.board = board.with_height(self.board.height())?;
+        Ok(())
+    }
+
+    fn spawn(&mut self) {
+        let new = self.spawner.spawn(&self.rules.pieces());
+        let type_ = new.type_;
+        if self.enter(new) {
+            self.stats.record_spawn(type_);
+        }
+    }
+
+    /// Puts `tet` at its spawn position as the falling piece, ending the game if
+    /// it doesn't fit.
+    fn enter(&mut self, tet: Tetromino) -> bool {
+        let new = self.rules.spawn(&tet, self.board.width());
+        info!("new tetromino: {:?}", new);
+        if !self.valid(&new) {
+            info!("game over");
+            self.game_over = true;
+            self.emit(Event::GameOver {
+                reason: GameOverReason::BlockOut,
+            });
+            return false;
+        }
+        if let Some(trainer) = self.trainer.as_mut() {
+            trainer.next_target(self.finesse.routes(&self.rules, &new));
+        }
+        self.inputs.clear();
+        self.last_kick = None;
+        self.spawned = Some(new.clone());
+        self.current_tet = Some(new.clone());
+        self.emit(Event::PieceSpawned { piece: new });
+        true
+    }
+
+    /// Runs down the current delay, removing the cleared rows or spawning the next
+    /// piece once it is over.
+    fn wait(&mut self, dt: Duration) {
+        let remaining = match self.delay {
+            Some(Delay::LineClear {
+                ref mut remaining, ..
+            })
+            | Some(Delay::Spawn { ref mut remaining }) => {
+                *remaining = remaining.saturating_sub(dt);
+                *remaining
+            }
+            None => return,
+        };
+        if !remaining.is_zero() {
+            return;
+        }
+
+        let finished = self.delay.take();
+        self.gravity_timer = Duration::ZERO;
+        match finished {
+            Some(Delay::LineClear { .. }) => {
+                self.board.remove_full_rows();
+                self.start_spawn_delay();
+                if self.delay.is_none() {
+                    self.spawn();
+                }
+            }
+            Some(Delay::Spawn { .. }) => self.spawn(),
+            None => {}
+        }
+    }
+
+    fn start_spawn_delay(&mut self) {
+        if !self.spawn_delay.is_zero() {
+            self.delay = Some(Delay::Spawn {
+                remaining: self.spawn_delay,
+            });
+        }
+    }
+
+    fn rotate(&mut self, new_orientation: Orientation) {
+        if let Some(ref tet) = self.current_tet {
+            if !self.game_over {
+                if let Some((new, kick)) = self.rules.rotate(&self.board, tet, new_orientation) {
+                    info!("rotated with kick {}", kick);
+                    self.last_kick = Some(kick);
+                    self.current_tet = Some(new.clone());
+                    self.emit(Event::Rotated { piece: new, kick });
+                }
+            }
+        }
+    }
+
+    /// Moves the falling piece down a row, or locks it if it can't. Returns whether
+    /// it moved.
+    fn fall(&mut self) -> bool {
+        if let Some(ref tet) = self.current_tet {
+            if !self.game_over {
+                let new = self.rules.move_down(tet);
+
+                if !self.valid(&new) {
+                    self.lock(tet.clone());
+                } else {
+                    self.current_tet = Some(new);
+                    self.last_kick = None;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Notes a shift or soft drop of the falling piece.
+    fn moved(&mut self) {
+        self.last_kick = None;
+        if let Some(piece) = self.current_tet.clone() {
+            self.emit(Event::Moved { piece });
+        }
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.subscribers.send(event);
+    }
+
+    fn lock(&mut self, tet: Tetromino) {
+        let fault = self.finesse_fault(&tet);
+
+        if let Some(ref trainer) = self.trainer {
+            let placement = Placement::new(&self.rules.blocks(&tet));
+            if fault == Some(false) && trainer.target.as_ref() == Some(&placement) {
+                self.stats.record_lock(0, 0);
+                self.current_tet = None;
+                self.hold_used = false;
+                self.emit(Event::Locked { piece: tet });
+            } else {
+                info!("finesse fault, restarting piece");
+                self.stats.record_finesse_fault();
+                self.inputs.clear();
+                self.current_tet = self.spawned.clone();
+            }
+            return;
+        }
+
+        if fault == Some(true) {
+            self.stats.record_finesse_fault();
+        }
+        let tspin = match self.last_kick {
+            Some(kick) => self.rules.t_spin(&self.board, &tet, kick),
+            None => TSpin::None,
+        };
+        self.board
+            .add_blocks(&self.rules.blocks(&tet), tet.colour, tet.type_);
+        let rows = self.board.full_rows();
+        let lines = rows.len() as u32;
+        let level = self.stats.level();
+        self.score += Self::score_for_lines(lines);
+        self.current_tet = None;
+        self.hold_used = false;
+
+        self.emit(Event::Locked { piece: tet });
+        let mut attack = 0;
+        if lines > 0 {
+            let difficult = lines >= 4 || tspin != TSpin::None;
+            let b2b = difficult && self.b2b;
+            self.b2b = difficult;
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            self.combo = Some(combo);
+            attack = self.attack_table.attack(lines, tspin, b2b, combo);
+            self.emit(Event::LinesCleared {
+                rows: rows.clone(),
+                tspin,
+                b2b,
+                combo,
+            });
+            let sent = self.cancel_garbage(attack);
+            if sent > 0 {
+                self.emit(Event::GarbageSent { lines: sent });
+            }
+        } else {
+            self.combo = None;
+        }
+        self.stats.record_lock(lines, attack);
+        if self.stats.level() > level {
+            self.emit(Event::LevelUp {
+                level: self.stats.level(),
+            });
+        }
+
+        if !rows.is_empty() && !self.line_clear_delay.is_zero() {
+            self.delay = Some(Delay::LineClear {
+                rows,
+                remaining: self.line_clear_delay,
+            });
+        } else {
+            self.board.remove_full_rows();
+            if lines == 0 {
+                self.raise_garbage();
+            }
+            self.start_spawn_delay();
+        }
+    }
+
+    /// Uses `attack` to cancel queued garbage, oldest first, returning what is left
+    /// to send on.
+    fn cancel_garbage(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            match self.garbage.front_mut() {
+                Some(garbage) if garbage.lines > attack => {
+                    garbage.lines -= attack;
+                    attack = 0;
+                }
+                Some(garbage) => {
+                    attack -= garbage.lines;
+                    self.garbage.pop_front();
+                }
+                None => break,
+            }
+        }
+        attack
+    }
+
+    /// Raises the queued garbage that has waited out the garbage delay, each batch
+    /// with its own gap.
+    fn raise_garbage(&mut self) {
+        let mut raised = 0;
+        let mut fits = true;
+        while self
+            .garbage
+            .front()
+            .is_some_and(|garbage| garbage.remaining.is_zero())
+        {
+            let garbage = self.garbage.pop_front().unwrap();
+            let hole = self.spawner.garbage_hole(self.board.width());
+            fits &= self.board.add_garbage(garbage.lines, hole);
+            raised += garbage.lines;
+        }
+        if raised > 0 {
+            self.emit(Event::GarbageReceived { lines: raised });
+        }
+        if !fits {
+            info!("game over");
+            self.game_over = true;
+            self.emit(Event::GameOver {
+                reason: GameOverReason::TopOut,
+            });
+        }
+    }
+
+    /// Whether `placed` took more inputs than necessary. Only hard dropped pieces that
+    /// were never soft dropped or switched are judged, since tucks and spins can't be
+    /// reached by shifting and rotating alone.
+    fn finesse_fault(&mut self, placed: &Tetromino) -> Option<bool> {
+        let spawned = self.spawned.as_ref()?;
+        let judged = self.inputs.last() == Some(&Action::Drop)
+            && !self
+                .inputs
+                .iter()
+                .any(|action| matches!(action, Action::MoveDown | Action::Switch));
+        if !judged {
+            return None;
+        }
+
+        let placement = Placement::new(&self.rules.blocks(placed));
+        self.finesse
+            .excess(&self.rules, spawned, &self.inputs, &placement)
+            .map(|excess| excess > 0)
+    }
+
+    fn record_key(&mut self, action: Action) {
+        if self.current_tet.is_some() && !self.game_over {
+            self.inputs.push(action);
+            self.stats.record_key();
+        }
+    }
+
     fn valid(&self, tetromino: &Tetromino) -> bool {
         self.board.valid_position(&self.rules.blocks(tetromino))
     }
 
+    /// Bigger pieces can clear more than four lines; each line past a tetris
+    /// doubles the score.
     fn score_for_lines(lines: u32) -> u32 {
         match lines {
             0 => 0,
             1 => 40,
             2 => 100,
             3 => 300,
-            4 => 1200,
-            _ => panic!("invalid number of lines"),
+            _ => 1200 << (lines - 4).min(16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::Tetris;
+    use crate::{
+        board::Board,
+        events::{Event, TSpin},
+        field::Field,
+        fumen,
+        positions::Position,
+        settings::Settings,
+        spawner::NEXT_PIECES,
+        tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+        ui::Preview,
+    };
+
+    fn game(text: &str) -> Tetris {
+        game_with(text, &[])
+    }
+
+    fn game_with(text: &str, args: &[&str]) -> Tetris {
+        let field = text.parse::<Field>().unwrap();
+        let mut settings_args = vec![
+            "tetris".to_string(),
+            format!("--width={}", field.board.width()),
+            format!("--height={}", field.board.height()),
+        ];
+        settings_args.extend(args.iter().map(|arg| arg.to_string()));
+        let settings = Settings::parse_from(settings_args);
+        let mut game = Tetris::new(&settings);
+        game.load_field(field).unwrap();
+        game
+    }
+
+    /// The rows, T-spin, back to back and combo of each clear so far.
+    fn clears(events: &[Event]) -> Vec<(Vec<u32>, TSpin, bool, u32)> {
+        events
+            .iter()
+            .cloned()
+            .filter_map(|event| match event {
+                Event::LinesCleared {
+                    rows,
+                    tspin,
+                    b2b,
+                    combo,
+                } => Some((rows, tspin, b2b, combo)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_board(game: &Tetris, expected: &str) {
+        assert_eq!(
+            game.board.to_string(),
+            expected.parse::<Board>().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_hard_drop_clears_lines() {
+        let mut game = game(
+            "
+            ..........
+            ..........
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+            ",
+        );
+        game.current_tet = Some(Tetromino::new(
+            7,
+            Colour::Cyan,
+            Orientation::Right,
+            TetrominoType::I,
+        ));
+        game.drop_tetromino();
+
+        assert_board(
+            &game,
+            "
+            ..........
+            ..........
+            ..........
+            .........I
+            .........I
+            ",
+        );
+        assert_eq!(game.score, 100);
+        assert_eq!(game.stats.lines, 2);
+    }
+
+    #[test]
+    fn test_line_clear_and_spawn_delays() {
+        let mut game = game_with(
+            "
+            ..........
+            ..........
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+            ",
+            &["--line-clear-delay=0.5", "--spawn-delay=0.2"],
+        );
+        game.current_tet = Some(Tetromino::new(
+            7,
+            Colour::Cyan,
+            Orientation::Right,
+            TetrominoType::I,
+        ));
+        game.drop_tetromino();
+
+        // The full rows stay put until the line clear delay is over.
+        assert_eq!(game.stats.lines, 2);
+        assert_eq!(game.board.full_rows(), vec![3, 4]);
+        game.tick(Duration::from_secs_f64(0.25));
+        assert_eq!(game.ui_state().clearing, Some((vec![3, 4], 0.5)));
+        game.update();
+        assert!(game.current_tet.is_none());
+
+        game.tick(Duration::from_secs_f64(0.25));
+        assert_board(
+            &game,
+            "
+            ..........
+            ..........
+            ..........
+            .........I
+            .........I
+            ",
+        );
+        assert!(game.ui_state().clearing.is_none());
+        assert!(game.current_tet.is_none());
+
+        game.tick(Duration::from_secs_f64(0.2));
+        assert!(game.current_tet.is_some());
+    }
+
+    #[test]
+    fn test_t_spin_triple() {
+        let mut game = game(
+            "
+            ..........
+            ..........
+            .......GGG
+            ........GG
+            GGGGGGG.GG
+            GGGGGG..GG
+            GGGGGGG.GG
+            ",
+        );
+        game.current_tet = Some(Tetromino {
+            box_corner: Position { x: 5, y: 2 },
+            ..Tetromino::new(0, Colour::Magenta, Orientation::Up, TetrominoType::T)
+        });
+        let events = game.subscribe();
+        game.rotate_anticlockwise();
+        game.drop_tetromino();
+
+        assert_board(
+            &game,
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            .......GGG
+            ........GG
+            ",
+        );
+        assert_eq!(game.stats.lines, 3);
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            clears(&events),
+            vec![(vec![4, 5, 6], TSpin::Full, false, 0)]
+        );
+    }
+
+    #[test]
+    fn test_back_to_back_tetrises_and_level_up() {
+        let mut game = game(
+            "
+            ..........
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            ",
+        );
+        game.stats.lines = 6;
+        let events = game.subscribe();
+        for _ in 0..2 {
+            game.current_tet = Some(Tetromino::new(
+                7,
+                Colour::Cyan,
+                Orientation::Right,
+                TetrominoType::I,
+            ));
+            game.drop_tetromino();
         }
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert!(events.contains(&Event::LevelUp { level: 2 }));
+        assert_eq!(
+            clears(&events),
+            vec![
+                (vec![6, 7, 8, 9], TSpin::None, false, 0),
+                (vec![6, 7, 8, 9], TSpin::None, true, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_garbage_cancels_then_rises() {
+        let mut game = game_with(
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            ",
+            &["--garbage-delay=0.5"],
+        );
+        let events = game.subscribe();
+        game.receive_garbage(1);
+        game.current_tet = Some(Tetromino::new(
+            7,
+            Colour::Cyan,
+            Orientation::Right,
+            TetrominoType::I,
+        ));
+        game.drop_tetromino();
+
+        // The tetris cancels the queued line and sends the other three.
+        assert!(events
+            .try_iter()
+            .any(|event| event == Event::GarbageSent { lines: 3 }));
+        assert_eq!(game.ui_state().garbage_waiting, 0);
+
+        game.receive_garbage(1);
+        assert_eq!(game.ui_state().garbage_waiting, 1);
+
+        game.tick(Duration::from_secs_f64(0.5));
+        assert_eq!(game.ui_state().garbage_ready, 1);
+        game.current_tet = Some(Tetromino::new(
+            0,
+            Colour::Yellow,
+            Orientation::Up,
+            TetrominoType::O,
+        ));
+        game.drop_tetromino();
+
+        assert_eq!(game.ui_state().garbage_ready, 0);
+        let rows = game.board.draw();
+        assert_eq!(rows[5][1], Some(Colour::Yellow));
+        assert_eq!(rows[7].iter().filter(|cell| cell.is_none()).count(), 1);
+    }
+
+    #[test]
+    fn test_hold() {
+        let mut game = game(
+            "
+            queue: T O I
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        game.update();
+        let events = game.subscribe();
+
+        game.hold();
+        assert_eq!(game.held, Some(TetrominoType::T));
+        assert_eq!(game.current_tet.as_ref().unwrap().type_, TetrominoType::O);
+        // Only once per piece.
+        game.hold();
+        assert_eq!(game.current_tet.as_ref().unwrap().type_, TetrominoType::O);
+
+        game.drop_tetromino();
+        game.update();
+        game.hold();
+        assert_eq!(game.held, Some(TetrominoType::I));
+        let tet = game.current_tet.as_ref().unwrap();
+        assert_eq!(tet.type_, TetrominoType::T);
+        assert_eq!(tet.colour, TetrominoType::T.colour());
+        assert_eq!(tet.box_corner, Position { x: 3, y: 0 });
+
+        let holds = events
+            .try_iter()
+            .filter(|event| matches!(event, Event::Hold { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            holds,
+            vec![
+                Event::Hold {
+                    held: TetrominoType::T
+                },
+                Event::Hold {
+                    held: TetrominoType::I
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_and_hold_previews() {
+        let mut game = game(
+            "
+            queue: T O I
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        game.update();
+        let sizes = |previews: &[Preview]| previews.iter().map(Preview::size).collect::<Vec<_>>();
+        let state = game.ui_state();
+        assert_eq!(state.next.len(), NEXT_PIECES);
+        assert_eq!(sizes(&state.next[..2]), [(2, 2), (4, 1)]);
+        assert_eq!(state.hold, None);
+
+        game.hold();
+        let state = game.ui_state();
+        assert_eq!(sizes(&state.next[..1]), [(4, 1)]);
+        let hold = state.hold.unwrap();
+        assert_eq!((hold.size(), hold.colour), ((3, 2), Colour::Magenta));
+    }
+
+    #[test]
+    fn test_pentomino_clears_five_lines() {
+        let mut game = game_with(
+            "
+            ..........
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            GGGGGGGGG.
+            ",
+            &["--pieces=pieces/pentominoes.toml"],
+        );
+        game.current_tet = Some(Tetromino::new(
+            7,
+            Colour::Cyan,
+            Orientation::Right,
+            TetrominoType::Custom('I'),
+        ));
+        game.drop_tetromino();
+
+        assert_board(
+            &game,
+            "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        assert_eq!(game.score, 2400);
+        assert_eq!(game.stats.lines, 5);
+        assert_eq!(game.stats.attack, 5);
+    }
+
+    #[test]
+    fn test_spawns_flat_in_the_middle() {
+        let mut game = game(
+            "
+            queue: T
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        game.update();
+
+        let tet = game.current_tet.as_ref().unwrap();
+        assert_eq!(tet.orientation, Orientation::Up);
+        assert_eq!(tet.box_corner, Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn test_queue_spawns_first() {
+        let mut game = game(
+            "
+            queue: ZO
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ",
+        );
+        game.update();
+        assert_eq!(game.current_tet.as_ref().unwrap().type_, TetrominoType::Z);
+        game.drop_tetromino();
+        game.update();
+        assert_eq!(game.current_tet.as_ref().unwrap().type_, TetrominoType::O);
+    }
+
+    #[test]
+    fn test_blocked_spawn_ends_game() {
+        let mut game = game(
+            "
+            GGGGG.GGGG
+            GGGGG.GGGG
+            GGGGG.GGGG
+            GGGGG.GGGG
+            ",
+        );
+        game.update();
+
+        assert!(game.game_over);
+        assert!(game.current_tet.is_none());
+    }
+
+    #[test]
+    fn test_fumen_export_of_played_board() {
+        let mut game = Tetris::new(&Settings::parse_from(["tetris"]));
+        for shift in 0..6 {
+            game.update();
+            for _ in 0..shift {
+                game.move_tetromino_left();
+            }
+            game.drop_tetromino();
+        }
+        game.update();
+
+        let pages = fumen::decode(&game.to_fumen().unwrap(), &game.rules, 20).unwrap();
+        let expected = game
+            .board
+            .pieces()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|piece| piece.map(|type_| type_.colour()))
+            })
+            .map(Iterator::collect)
+            .collect::<Vec<Vec<_>>>();
+        assert_eq!(pages[0].field.draw(), expected);
+        assert_eq!(
+            pages[0].piece.as_ref().map(|piece| piece.type_),
+            game.current_tet.as_ref().map(|tet| tet.type_)
+        );
     }
 }
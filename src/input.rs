@@ -1,28 +1,87 @@
-use crossterm::event::{self, Event, KeyCode};
-
-pub enum Action {
-    RotateClockwise,
-    RotateAntiClockwise,
-    MoveLeft,
-    MoveDown,
-    MoveRight,
-    Drop,
-    Switch,
-    Restart,
-    Quit,
+use std::{collections::VecDeque, time::Duration};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use log::error;
+use signal_hook::{
+    consts::{SIGINT, SIGTSTP},
+    low_level::raise,
+};
+
+use crate::{
+    action::Action,
+    mouse,
+    settings::{Mode, Settings},
+    signals,
+};
+
+/// How often the input loops check whether a signal has asked them to quit.
+const QUIT_POLL: Duration = Duration::from_millis(100);
+
+/// What the keys read by `map_event` do, for the controls.
+const KEYS: &[(&str, &str)] = &[
+    ("← → / j l", "Move"),
+    ("↓ / k", "Soft drop"),
+    ("space", "Hard drop"),
+    ("↑ / a", "Rotate anticlockwise"),
+    ("d", "Rotate clockwise"),
+    ("w", "Rotate 180"),
+    ("c", "Hold"),
+    ("s", "Switch piece"),
+    ("f", "Export fumen"),
+    ("r", "Restart"),
+    ("? / F1", "Show or hide controls"),
+    ("q", "Quit"),
+];
+
+/// What the keys read by `map_versus_event` do, player one's keys first.
+const VERSUS_KEYS: &[(&str, &str)] = &[
+    ("a d | ← →", "Move"),
+    ("s | ↓", "Soft drop"),
+    ("space | enter", "Hard drop"),
+    ("w | ↑", "Rotate clockwise"),
+    ("z | .", "Rotate anticlockwise"),
+    ("x | /", "Rotate 180"),
+    ("c | m", "Hold"),
+    ("r", "Restart"),
+    ("? / F1", "Show or hide controls"),
+    ("q / esc", "Quit"),
+];
+
+/// What the mouse does with `--mouse`.
+const MOUSE: &[(&str, &str)] = &[
+    ("hover", "Move to the column"),
+    ("scroll", "Rotate"),
+    ("left click", "Hard drop"),
+    ("right click", "Hold"),
+];
+
+/// The controls for a game with `settings`, as the keys and what they do.
+pub fn controls(settings: &Settings) -> Vec<(&'static str, &'static str)> {
+    let mut controls = match settings.mode {
+        Mode::Versus => VERSUS_KEYS.to_vec(),
+        _ => KEYS.to_vec(),
+    };
+    if settings.mouse {
+        controls.extend(MOUSE);
+    }
+    controls
 }
 
 fn map_event(event: Event) -> Option<Action> {
     match event {
         Event::Key(key) => match key.code {
             KeyCode::Char('q') => Some(Action::Quit),
+            KeyCode::Char('?') | KeyCode::F(1) => Some(Action::Help),
             KeyCode::Char('a') => Some(Action::RotateAntiClockwise),
             KeyCode::Char('d') => Some(Action::RotateClockwise),
+            KeyCode::Char('w') => Some(Action::Rotate180),
             KeyCode::Char('j') => Some(Action::MoveLeft),
             KeyCode::Char('k') => Some(Action::MoveDown),
             KeyCode::Char('l') => Some(Action::MoveRight),
             KeyCode::Char('s') => Some(Action::Switch),
+            KeyCode::Char('c') => Some(Action::Hold),
             KeyCode::Char('r') => Some(Action::Restart),
+            KeyCode::Char('f') => Some(Action::ExportFumen),
             KeyCode::Char(' ') => Some(Action::Drop),
             KeyCode::Right => Some(Action::MoveRight),
             KeyCode::Left => Some(Action::MoveLeft),
@@ -34,10 +93,101 @@ fn map_event(event: Event) -> Option<Action> {
     }
 }
 
+/// Keys for two players on one keyboard, as `(player, action)`. Player one
+/// moves with a, s and d, turns with w, z and x, drops with space and holds
+/// with c. Player two uses the arrow keys, turns with up, '.' and '/', drops
+/// with enter and holds with m. Either can quit with q or esc or restart with r.
+fn map_versus_event(event: Event) -> Option<(usize, Action)> {
+    match event {
+        Event::Key(key) => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some((0, Action::Quit)),
+            KeyCode::Char('r') => Some((0, Action::Restart)),
+            KeyCode::Char('?') | KeyCode::F(1) => Some((0, Action::Help)),
+            KeyCode::Char('a') => Some((0, Action::MoveLeft)),
+            KeyCode::Char('d') => Some((0, Action::MoveRight)),
+            KeyCode::Char('s') => Some((0, Action::MoveDown)),
+            KeyCode::Char('w') => Some((0, Action::RotateClockwise)),
+            KeyCode::Char('z') => Some((0, Action::RotateAntiClockwise)),
+            KeyCode::Char('x') => Some((0, Action::Rotate180)),
+            KeyCode::Char(' ') => Some((0, Action::Drop)),
+            KeyCode::Char('c') => Some((0, Action::Hold)),
+            KeyCode::Left => Some((1, Action::MoveLeft)),
+            KeyCode::Right => Some((1, Action::MoveRight)),
+            KeyCode::Down => Some((1, Action::MoveDown)),
+            KeyCode::Up => Some((1, Action::RotateClockwise)),
+            KeyCode::Char('.') => Some((1, Action::RotateAntiClockwise)),
+            KeyCode::Char('/') => Some((1, Action::Rotate180)),
+            KeyCode::Enter => Some((1, Action::Drop)),
+            KeyCode::Char('m') => Some((1, Action::Hold)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Raw mode turns off the keys that send signals, so Ctrl-C and Ctrl-Z send
+/// them here instead. Returns whether `key` was one of them.
+pub fn raise_signal(key: &KeyEvent) -> bool {
+    let signal = match key.code {
+        _ if !key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Char('c') => SIGINT,
+        KeyCode::Char('z') => SIGTSTP,
+        _ => return false,
+    };
+    if let Err(err) = raise(signal) {
+        error!("could not raise signal {}: {}", signal, err);
+    }
+    true
+}
+
+/// Inputs for the first player. The mouse only plays on their board.
 pub fn input_loop() -> impl Iterator<Item = Action> {
-    std::iter::from_fn(|| loop {
-        if let Some(action) = map_event(event::read().unwrap()) {
-            return Some(action);
+    read_loop(
+        map_event,
+        |player, action| (player == 0).then_some(action),
+        Action::Quit,
+    )
+}
+
+pub fn versus_input_loop() -> impl Iterator<Item = (usize, Action)> {
+    read_loop(
+        map_versus_event,
+        |player, action| Some((player, action)),
+        (0, Action::Quit),
+    )
+}
+
+/// Maps each key read to an input, and each mouse event to the inputs for the
+/// player whose board it's over with `map_mouse`. Gives `quit` once a signal
+/// asks the game to quit.
+fn read_loop<T: Copy>(
+    map: fn(Event) -> Option<T>,
+    map_mouse: fn(usize, Action) -> Option<T>,
+    quit: T,
+) -> impl Iterator<Item = T> {
+    let mut pending = VecDeque::new();
+    std::iter::from_fn(move || loop {
+        if signals::quit_requested() {
+            return Some(quit);
+        }
+        if let Some(input) = pending.pop_front() {
+            return Some(input);
+        }
+        if !event::poll(QUIT_POLL).unwrap() {
+            continue;
+        }
+        match event::read().unwrap() {
+            Event::Key(key) if raise_signal(&key) => {}
+            Event::Mouse(event) => pending.extend(
+                mouse::actions(event)
+                    .into_iter()
+                    .filter_map(|(player, action)| map_mouse(player, action)),
+            ),
+            event => {
+                if let Some(input) = map(event) {
+                    return Some(input);
+                }
+            }
         }
     })
 }
@@ -0,0 +1,137 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use log::{info, LevelFilter, Record};
+use log4rs::{
+    append::rolling_file::{
+        policy::compound::{
+            roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+        },
+        RollingFileAppender,
+    },
+    config::{Appender, Logger, Root},
+    encode::{pattern::PatternEncoder, Encode, Write},
+    Config,
+};
+use serde_json::{json, Value};
+
+use crate::{events::Event, settings::Settings};
+
+/// Log target for game events, which are logged as JSON whatever the level.
+const EVENTS: &str = "events";
+
+/// Rolled over logs kept next to the current one, as `<log file>.1` and so on.
+const BACKUPS: u32 = 3;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LogFormat {
+    /// Timestamped lines of text
+    Text,
+    /// One JSON object per line, with game events as nested objects
+    Json,
+}
+
+/// Logs to the settings' log file, rolling it over once it reaches the size
+/// limit. Game events are always kept, so `--log-level=off` leaves a log of just
+/// the events.
+pub fn init(settings: &Settings) -> Result<()> {
+    let encoder: Box<dyn Encode> = match settings.log_format {
+        LogFormat::Text => Box::new(PatternEncoder::new("{d} {l} {t} - {m}{n}")),
+        LogFormat::Json => Box::new(JsonLines),
+    };
+    let roller = FixedWindowRoller::builder()
+        .base(1)
+        .build(&format!("{}.{{}}", settings.log_file.display()), BACKUPS)?;
+    let policy = CompoundPolicy::new(
+        Box::new(SizeTrigger::new(settings.log_max_size * 1024)),
+        Box::new(roller),
+    );
+    let logfile = RollingFileAppender::builder()
+        .encoder(encoder)
+        .build(&settings.log_file, Box::new(policy))?;
+
+    let config = Config::builder()
+        .appender(Appender::builder().build("logfile", Box::new(logfile)))
+        .logger(Logger::builder().build(EVENTS, LevelFilter::Info))
+        .build(
+            Root::builder()
+                .appender("logfile")
+                .build(settings.log_level),
+        )?;
+    log4rs::init_config(config)?;
+    Ok(())
+}
+
+pub fn log_event(event: &Event) {
+    match serde_json::to_string(event) {
+        Ok(json) => info!(target: EVENTS, "{}", json),
+        Err(err) => info!(target: EVENTS, "unencodable event {:?}: {}", event, err),
+    }
+}
+
+/// Writes each record as a JSON object on its own line. Game events go in an
+/// `event` field as they are rather than as a string.
+#[derive(Debug)]
+struct JsonLines;
+
+impl Encode for JsonLines {
+    fn encode(&self, w: &mut dyn Write, record: &Record) -> anyhow::Result<()> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let message = record.args().to_string();
+        let mut line = json!({
+            "time_ms": time,
+            "level": record.level().as_str(),
+            "target": record.target(),
+        });
+        match serde_json::from_str::<Value>(&message) {
+            Ok(event) if record.target() == EVENTS => line["event"] = event,
+            _ => line["message"] = Value::String(message),
+        }
+        serde_json::to_writer(&mut *w, &line)?;
+        w.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Record};
+    use log4rs::encode::{writer::simple::SimpleWriter, Encode};
+    use serde_json::Value;
+
+    use super::{JsonLines, EVENTS};
+    use crate::events::{Event, GameOverReason};
+
+    fn encode(target: &str, message: &str) -> Value {
+        let mut out = SimpleWriter(vec![]);
+        JsonLines
+            .encode(
+                &mut out,
+                &Record::builder()
+                    .level(Level::Info)
+                    .target(target)
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+            .unwrap();
+        let text = String::from_utf8(out.0).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let event = Event::GameOver {
+            reason: GameOverReason::BlockOut,
+        };
+        let line = encode(EVENTS, &serde_json::to_string(&event).unwrap());
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["event"]["GameOver"]["reason"], "BlockOut");
+        assert!(line.get("message").is_none());
+
+        let line = encode("tetris::game", "new tetromino");
+        assert_eq!(line["message"], "new tetromino");
+        assert!(line.get("event").is_none());
+    }
+}
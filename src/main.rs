@@ -1,85 +1,368 @@
 use std::{
-    sync::{Arc, Mutex},
-    thread::{self, sleep},
-    time::Duration,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use log::{info, LevelFilter};
-use log4rs::{
-    append::file::FileAppender,
-    config::{Appender, Root},
-    Config,
-};
+use log::{error, info};
 use ui::UI;
 
-use anyhow::Result;
-use clap::Parser;
-use settings::Settings;
+use anyhow::{anyhow, Result};
+use settings::{Command, Mode, Settings};
 
 use crate::{
+    action::Action,
     game::Tetris,
-    input::{input_loop, Action},
+    input::{input_loop, versus_input_loop},
+    menu::Menu,
+    online::Online,
+    scores::HighScore,
+    spectate::{Screen, Spectators},
+    versus::{Match, Versus},
 };
 
+mod action;
+mod ascii_render;
+mod attack;
 mod board;
+mod bot;
+mod config;
+mod crash;
+mod events;
+mod field;
+mod finesse;
+mod fumen;
 mod game;
 mod input;
+mod logging;
+mod menu;
+mod mouse;
+mod netcode;
+mod online;
+mod pieces;
+mod polyominoes;
 mod positions;
+mod protocol;
+mod rotation;
+mod save;
+mod scores;
 mod settings;
+mod signals;
 mod spawner;
+mod spectate;
+mod stats;
 mod tetramino_rules;
 mod tetromino;
+mod theme;
+mod tui_render;
 mod ui;
+mod versus;
 
-fn main() -> Result<()> {
-    let logfile = FileAppender::builder().build("log.txt")?;
+const FRAME: Duration = Duration::from_millis(100);
 
-    let config = Config::builder()
-        .appender(Appender::builder().build("logfile", Box::new(logfile)))
-        .build(Root::builder().appender("logfile").build(LevelFilter::Info))?;
+/// Calls a function with the time since its last call, every frame on a thread
+/// of its own, until stopped.
+struct Ticker {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Ticker {
+    fn spawn(mut tick: impl FnMut(Duration) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            let mut last = Instant::now();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    sleep(FRAME);
+                    let now = Instant::now();
+                    tick(now - last);
+                    last = now;
+                }
+            }
+        });
+        Self { stop, thread }
+    }
 
-    log4rs::init_config(config)?;
+    /// Stops ticking, waiting for a tick under way to finish.
+    fn stop(self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread
+            .join()
+            .map_err(|_| anyhow!("the game clock thread panicked"))
+    }
+}
 
-    let settings = Settings::parse();
+fn main() -> Result<()> {
+    let args = env::args_os().collect::<Vec<_>>();
+    let settings = Settings::load(args.clone())?;
+    logging::init(&settings)?;
     info!("{:?}", settings);
+    crash::install(settings.crash_file.clone());
+    signals::handle()?;
+    if let Some(Command::Watch { ref addr }) = settings.command {
+        return watch(&settings, addr);
+    }
+    let spectators = match settings.broadcast {
+        Some(ref addr) => {
+            let spectators = Spectators::listen(addr)?;
+            info!("broadcasting to spectators on {}", spectators.addr());
+            Some(Arc::new(spectators))
+        }
+        None => None,
+    };
+    if settings.no_menu || settings.resume {
+        return play(&settings, spectators);
+    }
+    let mut menu = Menu::new(settings, args);
+    while let Some(settings) = menu.run()? {
+        play(&settings, spectators.clone())?;
+    }
+    Ok(())
+}
+
+/// Plays a game of the mode in `settings` until the player quits.
+fn play(settings: &Settings, spectators: Option<Arc<Spectators>>) -> Result<()> {
+    match settings.mode {
+        Mode::Versus => {
+            return run_versus(
+                settings,
+                Versus::new(settings),
+                versus_input_loop(),
+                spectators,
+            )
+        }
+        Mode::VersusBot => {
+            return run_versus(
+                settings,
+                Versus::new(settings),
+                input_loop().map(|action| (0, action)),
+                spectators,
+            )
+        }
+        Mode::Online => {
+            return run_versus(
+                settings,
+                Online::connect(settings)?,
+                input_loop().map(|action| (0, action)),
+                spectators,
+            )
+        }
+        Mode::Marathon | Mode::FinesseTrainer => {}
+    }
 
-    let mut ui = UI::new()?;
-    let game = Tetris::new(settings.width, settings.height);
+    let mut ui = UI::new(settings)?;
+    let saved = if settings.resume {
+        save::load(&settings.save_file)?
+    } else {
+        None
+    };
+    let mut game = match saved {
+        Some(game) => game,
+        None => new_game(settings)?,
+    };
+    log_events(&mut game);
     ui.start_ui()?;
     ui.draw(&game.ui_state()).unwrap();
 
     let game = Arc::new(Mutex::new(game));
     let ui = Arc::new(Mutex::new(ui));
+    signals::show(ui.clone());
 
-    thread::spawn({
+    let ticker = Ticker::spawn({
         let game = game.clone();
         let ui = ui.clone();
-        move || loop {
-            sleep(Duration::from_millis((settings.interval * 1000.0) as u64));
+        move |dt| {
             let mut game = game.lock().unwrap();
-            game.update();
+            game.tick(dt);
+            let state = game.ui_state();
+            if let Some(ref spectators) = spectators {
+                let title = state.title();
+                spectators.publish(&[(state.clone(), title)]);
+            }
             let mut ui = ui.lock().unwrap();
-            ui.draw(&game.ui_state()).unwrap();
+            ui.draw(&state).unwrap();
         }
     });
 
+    let mut exported = vec![];
     for action in input_loop() {
         let mut game = game.lock().unwrap();
         match action {
             Action::Quit => break,
-            Action::RotateClockwise => game.rotate_clockwise(),
-            Action::RotateAntiClockwise => game.rotate_anticlockwise(),
-            Action::MoveLeft => game.move_tetromino_left(),
-            Action::MoveRight => game.move_tetromino_right(),
-            Action::Drop => game.drop_tetromino(),
-            Action::MoveDown => game.move_tetromino_down(),
-            Action::Switch => game.switch_tetromino(),
-            Action::Restart => *game = Tetris::new(settings.width, settings.height),
+            Action::ExportFumen => match game.to_fumen() {
+                Ok(fumen) => export_fumen(settings, fumen, &mut exported)?,
+                Err(err) => error!("could not export fumen: {}", err),
+            },
+            Action::Restart => {
+                record_score(settings, &game)?;
+                *game = new_game(settings)?;
+                log_events(&mut game);
+            }
+            Action::Help => ui.lock().unwrap().toggle_help(),
+            action => game.apply(action),
         }
         let mut ui = ui.lock().unwrap();
         ui.draw(&game.ui_state())?;
     }
 
+    ticker.stop()?;
+    ui.lock().unwrap().stop_ui()?;
+    for fumen in exported {
+        println!("{}", fumen);
+    }
+
+    let game = game.lock().unwrap();
+    if game.game_over {
+        save::discard(&settings.save_file)?;
+        record_score(settings, &game)?;
+    } else {
+        save::save(&game, &settings.save_file)?;
+        info!("saved game to {:?}", settings.save_file);
+    }
+    Ok(())
+}
+
+/// Keeps the score of a marathon that's over if it's one of the best.
+fn record_score(settings: &Settings, game: &Tetris) -> Result<()> {
+    if settings.mode != Mode::Marathon || !game.game_over || game.score == 0 {
+        return Ok(());
+    }
+    let score = HighScore {
+        name: settings.name.clone(),
+        score: game.score,
+        lines: game.stats.lines,
+        seconds: game.stats.elapsed.as_secs_f64(),
+    };
+    if let Some(place) = scores::record(&settings.scores_file, score)? {
+        info!("high score, number {}", place + 1);
+    }
+    Ok(())
+}
+
+/// Plays a versus match until a player quits, taking `(player, action)` inputs.
+/// Matches aren't saved.
+fn run_versus(
+    settings: &Settings,
+    versus: impl Match + 'static,
+    inputs: impl Iterator<Item = (usize, Action)>,
+    spectators: Option<Arc<Spectators>>,
+) -> Result<()> {
+    let mut ui = UI::new(settings)?;
+    ui.start_ui()?;
+    let versus = Arc::new(Mutex::new(versus));
+    let ui = Arc::new(Mutex::new(ui));
+    signals::show(ui.clone());
+
+    let ticker = Ticker::spawn({
+        let versus = versus.clone();
+        let ui = ui.clone();
+        move |dt| {
+            let mut versus = versus.lock().unwrap();
+            versus.tick(dt);
+            let screen = versus.ui_states();
+            if let Some(ref spectators) = spectators {
+                spectators.publish(&screen);
+            }
+            let mut ui = ui.lock().unwrap();
+            ui.draw_versus(&screen).unwrap();
+        }
+    });
+
+    for (player, action) in inputs {
+        let mut versus = versus.lock().unwrap();
+        match action {
+            Action::Quit => break,
+            Action::Help => ui.lock().unwrap().toggle_help(),
+            action => versus.apply(player, action),
+        }
+        let mut ui = ui.lock().unwrap();
+        ui.draw_versus(&versus.ui_states())?;
+    }
+
+    ticker.stop()?;
     ui.lock().unwrap().stop_ui()?;
     Ok(())
 }
+
+/// Shows the game broadcast on `addr` until it ends or the spectator quits.
+fn watch(settings: &Settings, addr: &str) -> Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    let mut ui = UI::new(settings)?;
+    ui.start_ui()?;
+    let ui = Arc::new(Mutex::new(ui));
+    signals::show(ui.clone());
+
+    thread::spawn({
+        let ui = ui.clone();
+        move || {
+            let mut last: Screen = vec![];
+            for screen in protocol::messages::<Screen>(stream) {
+                match screen {
+                    Ok(screen) => last = screen,
+                    Err(err) => {
+                        error!("lost the broadcast: {}", err);
+                        break;
+                    }
+                }
+                ui.lock().unwrap().draw_versus(&last).unwrap();
+            }
+            if !last.is_empty() {
+                for (_, title) in last.iter_mut() {
+                    title.push_str(" (ended)");
+                }
+                ui.lock().unwrap().draw_versus(&last).unwrap();
+            }
+        }
+    });
+
+    for action in input_loop() {
+        if action == Action::Quit {
+            break;
+        }
+    }
+    ui.lock().unwrap().stop_ui()?;
+    Ok(())
+}
+
+fn new_game(settings: &Settings) -> Result<Tetris> {
+    let mut game = Tetris::new(settings);
+    if let Some(ref fumen) = settings.board {
+        game.load_fumen(fumen)?;
+    }
+    if let Some(ref path) = settings.field {
+        game.load_field(fs::read_to_string(path)?.parse()?)?;
+    }
+    Ok(game)
+}
+
+/// Logs the game's events until it is replaced.
+fn log_events(game: &mut Tetris) {
+    let events = game.subscribe();
+    thread::spawn(move || {
+        for event in events {
+            logging::log_event(&event);
+        }
+    });
+}
+
+/// Appends `fumen` to the configured file, or holds it to print once the UI has closed.
+fn export_fumen(settings: &Settings, fumen: String, exported: &mut Vec<String>) -> Result<()> {
+    info!("exported fumen: {}", fumen);
+    match settings.fumen_file {
+        Some(ref path) => {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", fumen)?;
+        }
+        None => exported.push(fumen),
+    }
+    Ok(())
+}
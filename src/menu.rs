@@ -0,0 +1,747 @@
+use std::{
+    ffi::OsString,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use toml::Value;
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::{
+    bot::Difficulty,
+    config, input,
+    rotation::RotationSystemKind,
+    scores::{self, HighScore},
+    settings::{Mode, Settings},
+    signals,
+    theme::{Marks, Theme},
+    ui::UI,
+};
+
+/// How often the menu is drawn while no key is pressed, so it comes back after
+/// a suspend.
+const REDRAW: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Page {
+    Main,
+    Modes,
+    Settings,
+    Controls,
+    HighScores,
+}
+
+/// The main menu's entries.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Entry {
+    /// Only there when there's a saved game
+    Continue,
+    Play,
+    Modes,
+    Settings,
+    Controls,
+    HighScores,
+    Quit,
+}
+
+impl Entry {
+    fn label(&self) -> &'static str {
+        match self {
+            Entry::Continue => "Continue",
+            Entry::Play => "Play",
+            Entry::Modes => "Mode select",
+            Entry::Settings => "Settings",
+            Entry::Controls => "Controls",
+            Entry::HighScores => "High scores",
+            Entry::Quit => "Quit",
+        }
+    }
+}
+
+/// What the player picked from the menu.
+#[derive(Debug)]
+enum Outcome {
+    Play(Box<Settings>),
+    Quit,
+}
+
+/// What a setting's value can be.
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Choice(Vec<String>),
+    Integer { min: i64, max: i64 },
+    Number { step: f64, min: f64, max: f64 },
+    Toggle,
+    Text,
+}
+
+/// A setting in the settings editor, by its flag name, with its value as text.
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    key: &'static str,
+    label: &'static str,
+    kind: Kind,
+    value: String,
+    /// Only changed settings are written, so the config file doesn't pin down
+    /// settings the player never touched.
+    changed: bool,
+}
+
+impl Field {
+    fn new(key: &'static str, label: &'static str, kind: Kind, value: impl ToString) -> Self {
+        Self {
+            key,
+            label,
+            kind,
+            value: value.to_string(),
+            changed: false,
+        }
+    }
+
+    /// A choice between the values of `T`, by the names they're given on the
+    /// command line.
+    fn choice<T: ValueEnum>(key: &'static str, label: &'static str, value: T) -> Self {
+        let name = |value: &T| {
+            value
+                .to_possible_value()
+                .map_or(String::new(), |possible| possible.get_name().to_string())
+        };
+        let choices = T::value_variants().iter().map(name).collect();
+        Self::new(key, label, Kind::Choice(choices), name(&value))
+    }
+
+    /// Steps the value up, or down when not `forward`, wrapping around choices
+    /// and stopping at the ends of numbers.
+    fn step(&mut self, forward: bool) {
+        let sign = if forward { 1 } else { -1 };
+        self.value = match self.kind {
+            Kind::Choice(ref choices) => {
+                let next = match choices.iter().position(|choice| *choice == self.value) {
+                    Some(at) => (at as i64 + sign).rem_euclid(choices.len() as i64) as usize,
+                    None => 0,
+                };
+                choices[next].clone()
+            }
+            Kind::Integer { min, max } => (self.value.parse().unwrap_or(min) + sign)
+                .clamp(min, max)
+                .to_string(),
+            Kind::Number { step, min, max } => {
+                let value = self.value.parse().unwrap_or(min) + sign as f64 * step;
+                // Rounded so steps of 0.05 don't show as 0.15000000000000002.
+                ((value.clamp(min, max) * 100.0).round() / 100.0).to_string()
+            }
+            Kind::Toggle => if self.value == "on" { "off" } else { "on" }.to_string(),
+            Kind::Text => return,
+        };
+        self.changed = true;
+    }
+
+    /// Types `key` into a text setting.
+    fn edit(&mut self, key: KeyCode) {
+        if self.kind != Kind::Text {
+            return;
+        }
+        match key {
+            KeyCode::Char(c) => self.value.push(c),
+            KeyCode::Backspace => {
+                self.value.pop();
+            }
+            _ => return,
+        }
+        self.changed = true;
+    }
+
+    /// The value as it's written to the config file.
+    fn toml(&self) -> Value {
+        match self.kind {
+            Kind::Integer { min, .. } => Value::Integer(self.value.parse().unwrap_or(min)),
+            Kind::Number { min, .. } => Value::Float(self.value.parse().unwrap_or(min)),
+            Kind::Toggle => Value::Boolean(self.value == "on"),
+            Kind::Choice(_) | Kind::Text => Value::String(self.value.clone()),
+        }
+    }
+}
+
+/// The settings the editor changes, as they are in `settings`.
+fn fields(settings: &Settings) -> Vec<Field> {
+    let toggle = |on| if on { "on" } else { "off" };
+    let theme = Theme::names()
+        .find(|name| Theme::from_arg(name).is_ok_and(|theme| theme == settings.theme))
+        .unwrap_or("custom");
+    let themes = Theme::names().map(str::to_string).collect();
+    vec![
+        Field::choice("mode", "Mode", settings.mode),
+        Field::new(
+            "interval",
+            "Gravity (seconds a row)",
+            Kind::Number {
+                step: 0.05,
+                min: 0.05,
+                max: 5.0,
+            },
+            settings.interval,
+        ),
+        Field::new(
+            "width",
+            "Board width",
+            Kind::Integer { min: 4, max: 40 },
+            settings.width,
+        ),
+        Field::new(
+            "height",
+            "Board height",
+            Kind::Integer { min: 4, max: 40 },
+            settings.height,
+        ),
+        Field::choice::<RotationSystemKind>(
+            "rotation-system",
+            "Rotation system",
+            settings.rotation_system,
+        ),
+        Field::new(
+            "line-clear-delay",
+            "Line clear delay",
+            Kind::Number {
+                step: 0.05,
+                min: 0.0,
+                max: 2.0,
+            },
+            settings.line_clear_delay,
+        ),
+        Field::new(
+            "spawn-delay",
+            "Spawn delay (ARE)",
+            Kind::Number {
+                step: 0.05,
+                min: 0.0,
+                max: 2.0,
+            },
+            settings.spawn_delay,
+        ),
+        Field::new("theme", "Theme", Kind::Choice(themes), theme),
+        Field::choice::<Marks>(
+            "marks",
+            "Piece marks",
+            settings.marks.unwrap_or(settings.theme.marks),
+        ),
+        Field::new(
+            "mouse",
+            "Mouse controls",
+            Kind::Toggle,
+            toggle(settings.mouse),
+        ),
+        Field::choice::<Difficulty>("bot-difficulty", "Bot difficulty", settings.bot_difficulty),
+        Field::new(
+            "bot-pps",
+            "Bot pieces per second",
+            Kind::Number {
+                step: 0.1,
+                min: 0.1,
+                max: 10.0,
+            },
+            settings.bot_pps,
+        ),
+        Field::new(
+            "first-to",
+            "Rounds to win versus",
+            Kind::Integer { min: 1, max: 9 },
+            settings.first_to,
+        ),
+        Field::new("name", "Name", Kind::Text, &settings.name),
+        Field::new("server", "Online server", Kind::Text, &settings.server),
+    ]
+}
+
+fn describe(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Marathon => "Clear lines as the pieces fall faster",
+        Mode::FinesseTrainer => "Place each piece in as few keys as it takes",
+        Mode::Versus => "Two players on one keyboard",
+        Mode::VersusBot => "Play against the computer",
+        Mode::Online => "Play someone through tetrs-server",
+    }
+}
+
+/// The main menu and the pages it leads to, kept apart from any game.
+pub struct Menu {
+    settings: Settings,
+    /// The command line, parsed again over the config file once it's saved
+    args: Vec<OsString>,
+    page: Page,
+    selected: usize,
+    fields: Vec<Field>,
+    scores: Vec<HighScore>,
+    /// Shown under the page until the next key
+    message: Option<String>,
+}
+
+impl Menu {
+    pub fn new(settings: Settings, args: Vec<OsString>) -> Self {
+        let fields = fields(&settings);
+        Self {
+            settings,
+            args,
+            page: Page::Main,
+            selected: 0,
+            fields,
+            scores: vec![],
+            message: None,
+        }
+    }
+
+    /// Shows the menu until the player picks a game, returning the settings to
+    /// play it with, or quits.
+    pub fn run(&mut self) -> Result<Option<Settings>> {
+        let mut ui = UI::new(&self.settings)?;
+        ui.start_ui()?;
+        let ui = Arc::new(Mutex::new(ui));
+        signals::show(ui.clone());
+        self.page = Page::Main;
+        self.selected = 0;
+
+        let outcome = loop {
+            ui.lock().unwrap().draw_screen(|frame| self.draw(frame))?;
+            if signals::quit_requested() {
+                break Outcome::Quit;
+            }
+            if !event::poll(REDRAW)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if input::raise_signal(&key) {
+                    continue;
+                }
+                if let Some(outcome) = self.key(key) {
+                    break outcome;
+                }
+            }
+        };
+        ui.lock().unwrap().stop_ui()?;
+        Ok(match outcome {
+            Outcome::Play(settings) => Some(*settings),
+            Outcome::Quit => None,
+        })
+    }
+
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = vec![];
+        if self.settings.save_file.exists() {
+            entries.push(Entry::Continue);
+        }
+        entries.extend([
+            Entry::Play,
+            Entry::Modes,
+            Entry::Settings,
+            Entry::Controls,
+            Entry::HighScores,
+            Entry::Quit,
+        ]);
+        entries
+    }
+
+    /// How many rows of the page can be selected. The settings page ends with
+    /// Save and Back.
+    fn rows(&self) -> usize {
+        match self.page {
+            Page::Main => self.entries().len(),
+            Page::Modes => Mode::value_variants().len(),
+            Page::Settings => self.fields.len() + 2,
+            Page::Controls | Page::HighScores => 0,
+        }
+    }
+
+    fn open(&mut self, page: Page) {
+        match page {
+            Page::Settings => self.fields = fields(&self.settings),
+            Page::HighScores => match scores::load(&self.settings.scores_file) {
+                Ok(scores) => self.scores = scores,
+                Err(err) => self.message = Some(format!("{:#}", err)),
+            },
+            Page::Main | Page::Modes | Page::Controls => {}
+        }
+        self.page = page;
+        self.selected = 0;
+    }
+
+    fn key(&mut self, key: KeyEvent) -> Option<Outcome> {
+        self.message = None;
+        let rows = self.rows();
+        match key.code {
+            KeyCode::Up if rows > 0 => {
+                self.selected = (self.selected + rows - 1) % rows;
+                return None;
+            }
+            KeyCode::Down if rows > 0 => {
+                self.selected = (self.selected + 1) % rows;
+                return None;
+            }
+            KeyCode::Esc if self.page == Page::Main => return Some(Outcome::Quit),
+            KeyCode::Esc => {
+                self.open(Page::Main);
+                return None;
+            }
+            _ => {}
+        }
+        match self.page {
+            Page::Main => self.main_key(key.code),
+            Page::Modes => {
+                let mode = Mode::value_variants()[self.selected];
+                (key.code == KeyCode::Enter).then(|| {
+                    let mut settings = self.settings.clone();
+                    settings.mode = mode;
+                    Outcome::Play(Box::new(settings))
+                })
+            }
+            Page::Settings => {
+                self.settings_key(key.code);
+                None
+            }
+            Page::Controls | Page::HighScores => {
+                if matches!(key.code, KeyCode::Enter | KeyCode::Char('q')) {
+                    self.open(Page::Main);
+                }
+                None
+            }
+        }
+    }
+
+    fn main_key(&mut self, key: KeyCode) -> Option<Outcome> {
+        match key {
+            KeyCode::Char('q') => return Some(Outcome::Quit),
+            KeyCode::Enter => {}
+            _ => return None,
+        }
+        let mut settings = self.settings.clone();
+        match self.entries()[self.selected] {
+            Entry::Continue => {
+                // Only single player games are saved.
+                if !matches!(settings.mode, Mode::Marathon | Mode::FinesseTrainer) {
+                    settings.mode = Mode::Marathon;
+                }
+                settings.resume = true;
+                Some(Outcome::Play(Box::new(settings)))
+            }
+            Entry::Play => Some(Outcome::Play(Box::new(settings))),
+            Entry::Modes => {
+                self.open(Page::Modes);
+                self.selected = Mode::value_variants()
+                    .iter()
+                    .position(|mode| *mode == self.settings.mode)
+                    .unwrap_or(0);
+                None
+            }
+            Entry::Settings => {
+                self.open(Page::Settings);
+                None
+            }
+            Entry::Controls => {
+                self.open(Page::Controls);
+                None
+            }
+            Entry::HighScores => {
+                self.open(Page::HighScores);
+                None
+            }
+            Entry::Quit => Some(Outcome::Quit),
+        }
+    }
+
+    fn settings_key(&mut self, key: KeyCode) {
+        let save = self.fields.len();
+        match (self.selected, key) {
+            (row, KeyCode::Enter) if row == save => self.save(),
+            (row, KeyCode::Enter) if row == save + 1 => self.open(Page::Main),
+            (row, key) if row < save => {
+                let field = &mut self.fields[row];
+                match key {
+                    KeyCode::Left => field.step(false),
+                    KeyCode::Right | KeyCode::Enter => field.step(true),
+                    key => field.edit(key),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the changed settings to the config file and plays with them from
+    /// now on, still under any flags given on the command line.
+    fn save(&mut self) {
+        let changes = self
+            .fields
+            .iter()
+            .filter(|field| field.changed)
+            .map(|field| (field.key.to_string(), field.toml()))
+            .collect::<Vec<_>>();
+        let saved = config::save(&self.settings.config, changes)
+            .and_then(|()| Settings::load(self.args.clone()));
+        match saved {
+            Ok(settings) => {
+                self.message = Some(format!("Saved to {}", settings.config.display()));
+                self.settings = settings;
+                self.open(Page::Main);
+            }
+            Err(err) => self.message = Some(format!("{:#}", err)),
+        }
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+        let (title, lines, selected, hint) = match self.page {
+            Page::Main => (
+                "TETRIS",
+                self.entries()
+                    .iter()
+                    .map(|entry| entry.label().to_string())
+                    .collect(),
+                Some(self.selected),
+                "↑↓ select  enter choose  q quit",
+            ),
+            Page::Modes => (
+                "Mode select",
+                Mode::value_variants()
+                    .iter()
+                    .map(|mode| {
+                        let name = mode
+                            .to_possible_value()
+                            .map_or("", |value| value.get_name());
+                        format!("{:<16}{}", name, describe(*mode))
+                    })
+                    .collect(),
+                Some(self.selected),
+                "enter play  esc back",
+            ),
+            Page::Settings => (
+                "Settings",
+                self.fields
+                    .iter()
+                    .map(|field| format!("{:<24}{}", field.label, field.value))
+                    .chain(["Save".to_string(), "Back".to_string()])
+                    .collect(),
+                Some(self.selected),
+                "←→ change  type to edit text  esc back",
+            ),
+            Page::Controls => (
+                "Controls",
+                control_lines(&input::controls(&self.settings)),
+                None,
+                "esc back",
+            ),
+            Page::HighScores => ("High scores", self.score_lines(), None, "esc back"),
+        };
+        let mut footer = vec![hint.to_string()];
+        footer.extend(self.message.clone());
+        draw_box(frame, frame.size(), title, &lines, selected, &footer);
+    }
+
+    fn score_lines(&self) -> Vec<String> {
+        if self.scores.is_empty() {
+            return vec!["No high scores yet: play a marathon".to_string()];
+        }
+        let mut lines = vec![format!(
+            "{:>2}  {:<12}{:>8}{:>7}{:>8}",
+            "", "Name", "Score", "Lines", "Time"
+        )];
+        lines.extend(self.scores.iter().enumerate().map(|(place, score)| {
+            let time = format!(
+                "{}:{:02}",
+                score.seconds as u32 / 60,
+                score.seconds as u32 % 60
+            );
+            format!(
+                "{:>2}  {:<12}{:>8}{:>7}{:>8}",
+                place + 1,
+                score.name,
+                score.score,
+                score.lines,
+                time
+            )
+        }));
+        lines
+    }
+}
+
+fn control_lines(controls: &[(&str, &str)]) -> Vec<String> {
+    controls
+        .iter()
+        .map(|(keys, action)| format!("{:<16}{}", keys, action))
+        .collect()
+}
+
+/// The controls in a box over the middle of `area`.
+pub fn draw_controls<B: Backend>(frame: &mut Frame<B>, area: Rect, controls: &[(&str, &str)]) {
+    let footer = ["? or F1 to hide".to_string()];
+    draw_box(
+        frame,
+        area,
+        "Controls",
+        &control_lines(controls),
+        None,
+        &footer,
+    );
+}
+
+/// A box in the middle of `area` with `lines` under `title`, the `selected`
+/// one highlighted, and `footer` at the bottom. It scrolls to keep the
+/// selection in view when it doesn't all fit.
+fn draw_box<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    title: &str,
+    lines: &[String],
+    selected: Option<usize>,
+    footer: &[String],
+) {
+    let inner_width = lines
+        .iter()
+        .chain(footer)
+        .map(|line| line.chars().count() + 2)
+        .chain([title.chars().count()])
+        .max()
+        .unwrap_or(0);
+    let width = (inner_width as u16 + 2).min(area.width);
+    let height = (lines.len() + footer.len() + 3).min(area.height as usize) as u16;
+    let rect = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let mut text = lines
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let style = if selected == Some(row) {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Spans::from(Span::styled(
+                format!(" {:<width$} ", line, width = inner_width - 2),
+                style,
+            ))
+        })
+        .collect::<Vec<_>>();
+    text.push(Spans::default());
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    text.extend(
+        footer
+            .iter()
+            .map(|line| Spans::from(Span::styled(format!(" {}", line), dim))),
+    );
+    let shown = height.saturating_sub(footer.len() as u16 + 3) as usize;
+    let scroll = selected.map_or(0, |row| (row + 1).saturating_sub(shown)) as u16;
+
+    frame.render_widget(Clear, rect);
+    frame.render_widget(
+        Paragraph::new(text).scroll((scroll, 0)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title.to_string())
+                .title_alignment(Alignment::Center),
+        ),
+        rect,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, ffi::OsString, fs, process};
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Menu, Outcome, Page};
+    use crate::settings::{Mode, Settings};
+
+    fn menu(name: &str) -> (Menu, std::path::PathBuf) {
+        let config = env::temp_dir().join(format!("tetris_menu_{}_{}.toml", name, process::id()));
+        let args = [
+            "tetris".to_string(),
+            format!("--config={}", config.display()),
+            "--save-file=no_such_save.json".to_string(),
+        ]
+        .map(OsString::from)
+        .to_vec();
+        let settings = Settings::load(args.clone()).unwrap();
+        (Menu::new(settings, args), config)
+    }
+
+    fn press(menu: &mut Menu, keys: &[KeyCode]) -> Option<Outcome> {
+        let mut outcome = None;
+        for key in keys {
+            outcome = menu.key(KeyEvent::new(*key, KeyModifiers::NONE));
+        }
+        outcome
+    }
+
+    #[test]
+    fn test_play_and_quit() {
+        let (mut menu, _) = menu("play");
+        match press(&mut menu, &[KeyCode::Enter]) {
+            Some(Outcome::Play(settings)) => assert_eq!(settings.mode, Mode::Marathon),
+            outcome => panic!("{:?}", outcome),
+        }
+        // To the bottom by going up from the top, to quit.
+        assert!(matches!(
+            press(&mut menu, &[KeyCode::Up, KeyCode::Enter]),
+            Some(Outcome::Quit)
+        ));
+        assert!(matches!(
+            press(&mut menu, &[KeyCode::Esc]),
+            Some(Outcome::Quit)
+        ));
+    }
+
+    #[test]
+    fn test_mode_select() {
+        let (mut menu, _) = menu("modes");
+        press(&mut menu, &[KeyCode::Down, KeyCode::Enter]);
+        assert_eq!(menu.page, Page::Modes);
+        match press(&mut menu, &[KeyCode::Down, KeyCode::Down, KeyCode::Enter]) {
+            Some(Outcome::Play(settings)) => assert_eq!(settings.mode, Mode::Versus),
+            outcome => panic!("{:?}", outcome),
+        }
+        // Only for that game.
+        assert_eq!(menu.settings.mode, Mode::Marathon);
+        press(&mut menu, &[KeyCode::Esc]);
+        assert_eq!(menu.page, Page::Main);
+    }
+
+    #[test]
+    fn test_settings_editor() {
+        let (mut menu, config) = menu("settings");
+        press(&mut menu, &[KeyCode::Down, KeyCode::Down, KeyCode::Enter]);
+        assert_eq!(menu.page, Page::Settings);
+
+        // Width up one, and the name typed over.
+        press(&mut menu, &[KeyCode::Down, KeyCode::Down, KeyCode::Right]);
+        let name = menu.fields.iter().position(|field| field.key == "name");
+        menu.selected = name.unwrap();
+        press(&mut menu, &[KeyCode::Backspace; 6]);
+        press(&mut menu, &[KeyCode::Char('a'), KeyCode::Char('l')]);
+        // Save is the first row after the settings, past the last one.
+        menu.selected = menu.fields.len();
+        press(&mut menu, &[KeyCode::Enter]);
+
+        let written = fs::read_to_string(&config);
+        fs::remove_file(&config).unwrap();
+        assert_eq!(menu.page, Page::Main);
+        assert_eq!(
+            (menu.settings.width, menu.settings.name.as_str()),
+            (11, "al")
+        );
+        let written = written.unwrap();
+        assert!(written.contains("width = 11"));
+        assert!(written.contains("name = \"al\""));
+        assert!(!written.contains("height"));
+    }
+}
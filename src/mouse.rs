@@ -0,0 +1,151 @@
+use std::{iter, sync::Mutex};
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tui::layout::Rect;
+
+use crate::{action::Action, ui::UIState};
+
+/// The games last drawn, by player, for working out what the mouse is over.
+static BOARDS: Mutex<Vec<BoardArea>> = Mutex::new(Vec::new());
+
+/// Where a game was drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardArea {
+    /// The game's part of the terminal
+    area: Rect,
+    /// The board's cells and how many characters across each is, if the game
+    /// was drawn
+    board: Option<(Rect, u16)>,
+    /// The leftmost and rightmost columns of the falling piece
+    piece: Option<(i32, i32)>,
+}
+
+impl BoardArea {
+    pub fn new(state: &UIState, area: Rect, board: Option<(Rect, u16)>) -> Self {
+        let piece = state.tetromino_blocks.as_ref().and_then(|blocks| {
+            let columns = blocks.iter().map(|block| block.x);
+            Some((columns.clone().min()?, columns.max()?))
+        });
+        Self { area, board, piece }
+    }
+
+    /// The board column under the terminal column `x`.
+    fn column(&self, x: u16) -> Option<i32> {
+        let (board, cell_width) = self.board?;
+        (board.x..board.right())
+            .contains(&x)
+            .then(|| ((x - board.x) / cell_width.max(1)) as i32)
+    }
+
+    /// Moves that bring the middle of the falling piece over the terminal
+    /// column `x`. Moves past a wall or another block just don't happen.
+    fn moves_to(&self, x: u16) -> Vec<Action> {
+        let (column, (left, right)) = match (self.column(x), self.piece) {
+            (Some(column), Some(piece)) => (column, piece),
+            _ => return vec![],
+        };
+        let shift = column - (left + right) / 2;
+        let action = if shift < 0 {
+            Action::MoveLeft
+        } else {
+            Action::MoveRight
+        };
+        iter::repeat_n(action, shift.unsigned_abs() as usize).collect()
+    }
+}
+
+/// Keeps where the games were drawn, by player.
+pub fn record(boards: Vec<BoardArea>) {
+    *BOARDS.lock().unwrap() = boards;
+}
+
+/// The actions for a mouse event, with the player whose game it's over.
+pub fn actions(event: MouseEvent) -> Vec<(usize, Action)> {
+    actions_on(&BOARDS.lock().unwrap(), event)
+}
+
+/// Hovering over a column moves the piece there, scrolling rotates it, a left
+/// click drops it and a right click holds it.
+fn actions_on(boards: &[BoardArea], event: MouseEvent) -> Vec<(usize, Action)> {
+    let (player, board) = match boards.iter().enumerate().find(|(_, board)| {
+        (board.area.x..board.area.right()).contains(&event.column)
+            && (board.area.y..board.area.bottom()).contains(&event.row)
+    }) {
+        Some(found) => found,
+        None => return vec![],
+    };
+    let actions = match event.kind {
+        MouseEventKind::Moved | MouseEventKind::Drag(_) => board.moves_to(event.column),
+        MouseEventKind::ScrollUp => vec![Action::RotateClockwise],
+        MouseEventKind::ScrollDown => vec![Action::RotateAntiClockwise],
+        MouseEventKind::Down(MouseButton::Left) => vec![Action::Drop],
+        MouseEventKind::Down(MouseButton::Right) => vec![Action::Hold],
+        _ => vec![],
+    };
+    actions.into_iter().map(|action| (player, action)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+    use tui::layout::Rect;
+
+    use super::{actions_on, BoardArea};
+    use crate::action::Action;
+
+    fn event(kind: MouseEventKind, column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn test_mouse_actions() {
+        // Two games side by side, each with a 10 column board of 2 character
+        // cells, and a T piece over columns 3 to 5 of the first.
+        let boards = [
+            BoardArea {
+                area: Rect::new(0, 0, 40, 24),
+                board: Some((Rect::new(10, 1, 20, 20), 2)),
+                piece: Some((3, 5)),
+            },
+            BoardArea {
+                area: Rect::new(40, 0, 40, 24),
+                board: Some((Rect::new(50, 1, 20, 20), 2)),
+                piece: None,
+            },
+        ];
+        let moved = |column| actions_on(&boards, event(MouseEventKind::Moved, column, 5));
+
+        // Over column 6, two right of the middle of the piece.
+        assert_eq!(moved(23), [(0, Action::MoveRight), (0, Action::MoveRight)]);
+        assert_eq!(moved(10), [(0, Action::MoveLeft); 4]);
+        assert_eq!(moved(18), []);
+        // Beside the board, and over a board with nothing falling.
+        assert_eq!(moved(5), []);
+        assert_eq!(moved(55), []);
+
+        let at = |kind| actions_on(&boards, event(kind, 60, 10));
+        assert_eq!(at(MouseEventKind::ScrollUp), [(1, Action::RotateClockwise)]);
+        assert_eq!(
+            at(MouseEventKind::ScrollDown),
+            [(1, Action::RotateAntiClockwise)]
+        );
+        assert_eq!(
+            at(MouseEventKind::Down(MouseButton::Left)),
+            [(1, Action::Drop)]
+        );
+        assert_eq!(
+            at(MouseEventKind::Down(MouseButton::Right)),
+            [(1, Action::Hold)]
+        );
+        assert_eq!(at(MouseEventKind::Up(MouseButton::Left)), []);
+        assert_eq!(
+            actions_on(&boards, event(MouseEventKind::ScrollUp, 90, 10)),
+            []
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use std::{mem, sync::mpsc::Receiver, time::Duration};
+
+use anyhow::{anyhow, Result};
+use log::debug;
+
+use crate::{
+    action::Action, board::Board, events::Event, game::Tetris, settings::Settings, versus::PLAYERS,
+};
+
+/// Time simulated in each frame. Games only ever advance a whole frame at a
+/// time, so they play out the same on every client.
+pub const FRAME: Duration = Duration::from_millis(20);
+
+/// The 64-bit FNV-1a offset basis and prime.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Frames we run ahead of the last one with every player's inputs before
+/// waiting for the opponent to catch up.
+const MAX_PREDICTION: usize = 30;
+
+/// Both players' games, stepped a frame at a time from the players' inputs and
+/// the shared seed alone.
+struct Sim {
+    games: Vec<Tetris>,
+    events: Vec<Receiver<Event>>,
+}
+
+impl Sim {
+    fn new(games: Vec<Tetris>) -> Self {
+        let mut sim = Self {
+            games,
+            events: vec![],
+        };
+        sim.events = sim.games.iter_mut().map(Tetris::subscribe).collect();
+        sim
+    }
+
+    /// A copy of the games, the same way they are saved to disk.
+    fn save(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.games)?)
+    }
+
+    fn restore(saved: &str) -> Result<Self> {
+        Ok(Self::new(serde_json::from_str(saved)?))
+    }
+
+    /// Plays `frame`, with no actions from players whose inputs for it are
+    /// missing, then passes on the garbage each game sent.
+    fn step(&mut self, inputs: &[Vec<Vec<Action>>], frame: usize) {
+        for (player, game) in self.games.iter_mut().enumerate() {
+            for action in inputs[player].get(frame).into_iter().flatten() {
+                game.apply(*action);
+            }
+            game.tick(FRAME);
+        }
+        for player in 0..PLAYERS {
+            let sent = self.events[player]
+                .try_iter()
+                .map(|event| match event {
+                    Event::GarbageSent { lines } => lines,
+                    _ => 0,
+                })
+                .sum::<u32>();
+            if sent > 0 {
+                self.games[(player + 1) % PLAYERS].receive_garbage(sent);
+            }
+        }
+    }
+
+    /// FNV-1a over both boards' bytes, which unlike the standard library's
+    /// hashers comes out the same from every build of the game.
+    fn checksum(&self) -> u64 {
+        self.games.iter().fold(FNV_OFFSET, |hash, game| {
+            fnv1a(hash, &board_bytes(game.board()))
+        })
+    }
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The board's width and height as little endian `u32`s, then a byte for each
+/// cell from the top left: 0 when empty, otherwise one more than its colour's
+/// place in `Colour`.
+fn board_bytes(board: &Board) -> Vec<u8> {
+    let mut bytes = [board.width(), board.height()]
+        .iter()
+        .flat_map(|size| size.to_le_bytes())
+        .collect::<Vec<_>>();
+    bytes.extend(
+        board
+            .draw()
+            .into_iter()
+            .flatten()
+            .map(|colour| colour.map_or(0, |colour| colour as u8 + 1)),
+    );
+    bytes
+}
+
+/// Rollback netcode for one round. Both games are simulated here from the
+/// local player's inputs and the opponent's as they arrive. Frames the
+/// opponent's inputs haven't reached yet are played as if they did nothing, and
+/// when their inputs turn out otherwise the games are rolled back to the last
+/// frame everyone's inputs were known for and played forward again.
+pub struct Rollback {
+    seat: usize,
+    /// Each player's actions for each frame, as far as we know them.
+    inputs: Vec<Vec<Vec<Action>>>,
+    sim: Sim,
+    /// Frames simulated so far.
+    frame: usize,
+    /// Frames with every player's inputs, and the games after the last of them.
+    confirmed: usize,
+    checkpoint: String,
+    /// Whether each game had topped out as of the last confirmed frame.
+    confirmed_over: Vec<bool>,
+    /// Time towards the next frame.
+    elapsed: Duration,
+    /// Local actions to play in the next frame.
+    pending: Vec<Action>,
+}
+
+impl Rollback {
+    /// Starts a round in which the local player is in seat `seat`.
+    pub fn new(settings: &Settings, seed: u64, seat: usize) -> Result<Self> {
+        let sim = Sim::new(
+            (0..PLAYERS)
+                .map(|_| Tetris::seeded(settings, seed))
+                .collect(),
+        );
+        let checkpoint = sim.save()?;
+        Ok(Self {
+            seat,
+            inputs: vec![vec![]; PLAYERS],
+            sim,
+            frame: 0,
+            confirmed: 0,
+            checkpoint,
+            confirmed_over: vec![false; PLAYERS],
+            elapsed: Duration::ZERO,
+            pending: vec![],
+        })
+    }
+
+    pub fn games(&self) -> &[Tetris] {
+        &self.sim.games
+    }
+
+    /// Queues a local action for the next frame.
+    pub fn apply(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    /// Plays the frames due in `dt`, returning the first of them and the local
+    /// actions for each to send to the opponent. Stops short if that would take
+    /// us too far past the opponent's inputs.
+    pub fn advance(&mut self, dt: Duration) -> (usize, Vec<Vec<Action>>) {
+        let first = self.frame;
+        let mut frames = vec![];
+        self.elapsed = (self.elapsed + dt).min(FRAME * MAX_PREDICTION as u32);
+        while self.elapsed >= FRAME && self.frame < self.confirmed + MAX_PREDICTION {
+            self.elapsed -= FRAME;
+            let actions = mem::take(&mut self.pending);
+            self.inputs[self.seat].push(actions.clone());
+            frames.push(actions);
+            self.sim.step(&self.inputs, self.frame);
+            self.frame += 1;
+        }
+        (first, frames)
+    }
+
+    /// Takes the opponent's actions for the frames from `frame` on, rolling back
+    /// if they did anything in frames already played. Returns the first newly
+    /// confirmed frame and the checksum of the boards after each one.
+    pub fn receive(
+        &mut self,
+        frame: usize,
+        actions: Vec<Vec<Action>>,
+    ) -> Result<(usize, Vec<u64>)> {
+        let opponent = (self.seat + 1) % PLAYERS;
+        if frame != self.inputs[opponent].len() {
+            return Err(anyhow!(
+                "expected inputs from frame {}, got frame {}",
+                self.inputs[opponent].len(),
+                frame
+            ));
+        }
+        let mispredicted = actions
+            .iter()
+            .enumerate()
+            .any(|(i, actions)| frame + i < self.frame && !actions.is_empty());
+        self.inputs[opponent].extend(actions);
+
+        let first = self.confirmed;
+        let confirmable = self.inputs.iter().map(Vec::len).min().unwrap_or(0);
+        let mut checksums = vec![];
+        if confirmable > self.confirmed {
+            let mut sim = Sim::restore(&self.checkpoint)?;
+            for frame in self.confirmed..confirmable {
+                sim.step(&self.inputs, frame);
+                checksums.push(sim.checksum());
+            }
+            self.checkpoint = sim.save()?;
+            self.confirmed_over = sim.games.iter().map(|game| game.game_over).collect();
+            self.confirmed = confirmable;
+            if mispredicted {
+                debug!(
+                    "rolling back {} frames to frame {}",
+                    self.frame - first,
+                    first
+                );
+                for frame in confirmable..self.frame {
+                    sim.step(&self.inputs, frame);
+                }
+                self.sim = sim;
+            }
+        }
+        Ok((first, checksums))
+    }
+
+    /// Whether the local player had topped out as of the last frame with
+    /// everyone's inputs, so it can't be undone by a rollback.
+    pub fn topped_out(&self) -> bool {
+        self.confirmed_over[self.seat]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{board_bytes, fnv1a, Rollback, FNV_OFFSET, FRAME, MAX_PREDICTION};
+    use crate::{action::Action, board::Board, settings::Settings};
+
+    fn rollback(seat: usize) -> Rollback {
+        let settings = Settings::parse_from(["tetris", "--mode=online", "--netcode=rollback"]);
+        Rollback::new(&settings, 7, seat).unwrap()
+    }
+
+    fn boards(rollback: &Rollback) -> Vec<String> {
+        rollback
+            .games()
+            .iter()
+            .map(|game| game.board().to_string())
+            .collect()
+    }
+
+    /// Has both clients play `actions` each second, a second of frames at a
+    /// time before hearing from the other, checking they agree throughout.
+    fn play_apart(actions: &[Action]) -> (Rollback, Rollback) {
+        let mut alice = rollback(0);
+        let mut bob = rollback(1);
+
+        let mut sent = [vec![], vec![]];
+        for second in 0..4 {
+            for (client, sent) in [&mut alice, &mut bob].into_iter().zip(sent.iter_mut()) {
+                for action in actions {
+                    client.apply(*action);
+                }
+                let (frame, frames) = client.advance(FRAME * 25);
+                assert_eq!(frame, second * 25);
+                sent.push((frame, frames));
+            }
+            let [to_bob, to_alice] = &mut sent;
+            let mut checksums = vec![];
+            for (frame, frames) in to_alice.drain(..) {
+                checksums.extend(alice.receive(frame, frames).unwrap().1);
+            }
+            let mut bob_checksums = vec![];
+            for (frame, frames) in to_bob.drain(..) {
+                bob_checksums.extend(bob.receive(frame, frames).unwrap().1);
+            }
+            assert_eq!(checksums, bob_checksums);
+            assert_eq!(checksums.len(), 25);
+        }
+        assert_eq!(boards(&alice), boards(&bob));
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_late_inputs_roll_back() {
+        let (alice, _) = play_apart(&[Action::MoveLeft, Action::Drop]);
+        assert!(alice.games()[0].stats.pieces > 0);
+        assert_eq!(
+            alice.games()[0].board().to_string(),
+            alice.games()[1].board().to_string()
+        );
+    }
+
+    #[test]
+    fn test_switch_rolls_back() {
+        let (alice, _) = play_apart(&[Action::Switch, Action::Drop]);
+        assert_eq!(
+            alice.games()[0].board().to_string(),
+            alice.games()[1].board().to_string()
+        );
+    }
+
+    #[test]
+    fn test_checksum_bytes() {
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let board = ".T\nGI".parse::<Board>().unwrap();
+        assert_eq!(board_bytes(&board), [2, 0, 0, 0, 2, 0, 0, 0, 0, 6, 8, 5]);
+    }
+
+    #[test]
+    fn test_waits_for_the_opponent() {
+        let mut alice = rollback(0);
+        let (_, frames) = alice.advance(FRAME * 100);
+        assert_eq!(frames.len(), MAX_PREDICTION);
+        let (frame, frames) = alice.advance(FRAME);
+        assert_eq!((frame, frames.len()), (MAX_PREDICTION, 0));
+
+        let (first, checksums) = alice.receive(0, vec![vec![]; 10]).unwrap();
+        assert_eq!((first, checksums.len()), (0, 10));
+        assert!(alice.receive(5, vec![vec![]]).is_err());
+        // The frame it waited on is played as well as this one.
+        let (_, frames) = alice.advance(FRAME);
+        assert_eq!(frames.len(), 2);
+    }
+}
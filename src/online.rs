@@ -0,0 +1,512 @@
+use std::{
+    net::TcpStream,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::{
+    action::Action,
+    board::Board,
+    events::Event,
+    game::Tetris,
+    netcode::Rollback,
+    protocol::{self, ClientMessage, Netcode, ServerMessage, Snapshot},
+    settings::Settings,
+    stats::Statistics,
+    ui::UIState,
+    versus::{Match, PLAYERS, ROUND_BREAK},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Status {
+    /// Waiting for an opponent, or for them to be ready for a rematch.
+    Waiting,
+    Playing,
+    RoundOver {
+        won: bool,
+    },
+    MatchOver {
+        won: bool,
+    },
+    /// The clients' games differed at `frame`, so the round was abandoned.
+    Desynced {
+        frame: usize,
+    },
+    /// The server turned us away or the connection was lost.
+    Failed(String),
+}
+
+/// The games of the round being played, kept as the room's netcode says.
+enum Play {
+    /// Our game is played here and the opponent's is drawn from their snapshots.
+    Relay {
+        game: Box<Tetris>,
+        events: Receiver<Event>,
+        opponent: Option<Snapshot>,
+        /// The last snapshot sent, so an unchanged game isn't sent again.
+        sent: Option<Snapshot>,
+    },
+    /// Both games are simulated here from the players' inputs.
+    Rollback {
+        rollback: Rollback,
+        /// Whether the server has been told we topped out.
+        reported: bool,
+    },
+}
+
+impl Play {
+    fn new(settings: &Settings, seed: u64, seat: usize) -> Result<Self> {
+        Ok(match settings.netcode {
+            Netcode::Relay => {
+                let mut game = Box::new(Tetris::seeded(settings, seed));
+                let events = game.subscribe();
+                Play::Relay {
+                    game,
+                    events,
+                    opponent: None,
+                    sent: None,
+                }
+            }
+            Netcode::Rollback => Play::Rollback {
+                rollback: Rollback::new(settings, seed, seat)?,
+                reported: false,
+            },
+        })
+    }
+}
+
+/// A versus match against a player on another machine through `tetrs-server`.
+/// With relay netcode our game is played here and the server passes on the
+/// garbage we send and our board; with rollback netcode only inputs are sent and
+/// both games are simulated by both clients. Either way the server decides who
+/// won each round.
+pub struct Online {
+    settings: Settings,
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    play: Play,
+    /// Our place in the room, and so our game's index under rollback netcode.
+    seat: usize,
+    status: Status,
+    round: u32,
+    first_to: u32,
+    wins: u32,
+    opponent_wins: u32,
+    opponent_name: Option<String>,
+    /// Time left before telling the server we're ready for the next round.
+    round_break: Option<Duration>,
+}
+
+impl Online {
+    /// Connects to the server and joins the room named in the settings.
+    pub fn connect(settings: &Settings) -> Result<Self> {
+        let mut stream = TcpStream::connect((settings.server.as_str(), settings.port))?;
+        info!("connected to {}:{}", settings.server, settings.port);
+        protocol::send(
+            &mut stream,
+            &ClientMessage::Join {
+                room: settings.room.clone(),
+                name: settings.name.clone(),
+                netcode: settings.netcode,
+            },
+        )?;
+
+        let (sender, messages) = channel();
+        let reader = stream.try_clone()?;
+        thread::spawn(move || {
+            for message in protocol::messages(reader) {
+                match message {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        warn!("bad message from server: {}", err);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            settings: settings.clone(),
+            stream,
+            messages,
+            play: Play::new(settings, 0, 0)?,
+            seat: 0,
+            status: Status::Waiting,
+            round: 0,
+            first_to: settings.first_to,
+            wins: 0,
+            opponent_wins: 0,
+            opponent_name: None,
+            round_break: None,
+        })
+    }
+
+    /// Our own game.
+    fn game(&self) -> &Tetris {
+        match self.play {
+            Play::Relay { ref game, .. } => game,
+            Play::Rollback { ref rollback, .. } => &rollback.games()[self.seat],
+        }
+    }
+
+    fn receive(&mut self, message: ServerMessage) -> Result<()> {
+        match message {
+            ServerMessage::Opponent { .. } | ServerMessage::Inputs { .. } => {}
+            _ => info!("from server: {:?}", message),
+        }
+        match message {
+            ServerMessage::Waiting => self.status = Status::Waiting,
+            ServerMessage::Start {
+                round,
+                first_to,
+                opponent,
+                seat,
+                seed,
+            } => {
+                if round == 1 {
+                    self.wins = 0;
+                    self.opponent_wins = 0;
+                }
+                self.round = round;
+                self.first_to = first_to;
+                self.opponent_name = Some(opponent);
+                self.play = Play::new(&self.settings, seed, seat)?;
+                self.seat = seat;
+                self.round_break = None;
+                self.status = Status::Playing;
+            }
+            ServerMessage::Attack { lines } => {
+                if let Play::Relay { ref mut game, .. } = self.play {
+                    game.receive_garbage(lines);
+                }
+            }
+            ServerMessage::Opponent { snapshot } => {
+                if let Play::Relay {
+                    ref mut opponent, ..
+                } = self.play
+                {
+                    *opponent = Some(snapshot);
+                }
+            }
+            ServerMessage::Inputs { frame, actions } => {
+                if let (Status::Playing, Play::Rollback { rollback, .. }) =
+                    (&self.status, &mut self.play)
+                {
+                    let (frame, hashes) = rollback.receive(frame, actions)?;
+                    if !hashes.is_empty() {
+                        self.send(ClientMessage::Checksums { frame, hashes });
+                    }
+                }
+            }
+            ServerMessage::RoundOver {
+                won,
+                wins,
+                opponent_wins,
+                match_over,
+            } => {
+                self.wins = wins;
+                self.opponent_wins = opponent_wins;
+                if match_over {
+                    self.status = Status::MatchOver { won };
+                } else {
+                    self.status = Status::RoundOver { won };
+                    self.round_break = Some(ROUND_BREAK);
+                }
+            }
+            ServerMessage::Desync { frame } => {
+                self.status = Status::Desynced { frame };
+                self.round_break = Some(ROUND_BREAK);
+            }
+            ServerMessage::OpponentLeft => {
+                self.status = Status::Waiting;
+                self.opponent_name = None;
+                self.wins = 0;
+                self.opponent_wins = 0;
+            }
+            ServerMessage::Error { message } => self.status = Status::Failed(message),
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        if let Err(err) = protocol::send(&mut self.stream, &message) {
+            warn!("could not send {:?}: {}", message, err);
+            self.status = Status::Failed("connection lost".to_string());
+        }
+    }
+
+    /// Plays the round on by `dt` and tells the server what it needs to know.
+    fn play(&mut self, dt: Duration) {
+        let mut messages = vec![];
+        match self.play {
+            Play::Relay {
+                ref mut game,
+                ref events,
+                ref mut sent,
+                ..
+            } => {
+                game.tick(dt);
+                for event in events.try_iter() {
+                    match event {
+                        Event::GarbageSent { lines } => {
+                            messages.push(ClientMessage::Attack { lines })
+                        }
+                        Event::GameOver { .. } => messages.push(ClientMessage::ToppedOut),
+                        _ => {}
+                    }
+                }
+                let snapshot = Self::snapshot(game);
+                if sent.as_ref() != Some(&snapshot) {
+                    messages.push(ClientMessage::Snapshot {
+                        snapshot: snapshot.clone(),
+                    });
+                    *sent = Some(snapshot);
+                }
+            }
+            Play::Rollback {
+                ref mut rollback,
+                ref mut reported,
+            } => {
+                let (frame, actions) = rollback.advance(dt);
+                if !actions.is_empty() {
+                    messages.push(ClientMessage::Inputs { frame, actions });
+                }
+                if rollback.topped_out() && !*reported {
+                    messages.push(ClientMessage::ToppedOut);
+                    *reported = true;
+                }
+            }
+        }
+        for message in messages {
+            self.send(message);
+        }
+    }
+
+    fn snapshot(game: &Tetris) -> Snapshot {
+        let mut board = game.board().clone();
+        if let Some(tet) = game.current() {
+            board.add_blocks(&game.rules().blocks(tet), tet.colour, tet.type_);
+        }
+        let state = game.ui_state();
+        Snapshot {
+            board: board.to_string(),
+            score: game.score,
+            lines: game.stats.lines,
+            garbage: state.garbage_waiting + state.garbage_ready,
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.status {
+            Status::Waiting => format!("{}: waiting", self.settings.room),
+            Status::Failed(ref message) => message.clone(),
+            Status::Desynced { frame } => format!("desync at frame {}", frame),
+            Status::MatchOver { won: true } => format!("{} WINS! r: rematch", self.settings.name),
+            Status::MatchOver { won: false } => format!("{} | r: rematch", self.settings.name),
+            _ => {
+                let state = if self.game().game_over {
+                    "K.O.".to_string()
+                } else {
+                    self.game().score.to_string()
+                };
+                format!(
+                    "{} | R{} | {}/{} | {}",
+                    self.settings.name, self.round, self.wins, self.first_to, state
+                )
+            }
+        }
+    }
+
+    fn opponent_state(&self) -> (UIState, String) {
+        let state = match self.play {
+            Play::Relay { ref opponent, .. } => self.snapshot_state(opponent.as_ref()),
+            Play::Rollback { ref rollback, .. } => {
+                rollback.games()[(self.seat + 1) % PLAYERS].ui_state()
+            }
+        };
+        let title = match (&self.opponent_name, &self.status) {
+            (None, _) => "no opponent yet".to_string(),
+            (Some(name), Status::MatchOver { won: false }) => format!("{} WINS!", name),
+            (Some(name), _) => format!(
+                "{} | {}/{} | {}",
+                name, self.opponent_wins, self.first_to, state.score
+            ),
+        };
+        (state, title)
+    }
+
+    /// Draws an opponent's game from their last snapshot, or as an empty board
+    /// before the first.
+    fn snapshot_state(&self, snapshot: Option<&Snapshot>) -> UIState {
+        let board = snapshot
+            .and_then(|snapshot| snapshot.board.parse::<Board>().ok())
+            .unwrap_or_else(|| Board::new(self.settings.width, self.settings.height));
+        UIState {
+            board: board.draw(),
+            board_pieces: board.pieces(),
+            score: snapshot.map_or(0, |snapshot| snapshot.score),
+            stats: Statistics {
+                lines: snapshot.map_or(0, |snapshot| snapshot.lines),
+                ..Statistics::default()
+            },
+            garbage_ready: snapshot.map_or(0, |snapshot| snapshot.garbage),
+            ..UIState::empty(board.width(), board.height())
+        }
+    }
+}
+
+impl Match for Online {
+    /// Plays `action` during a round. Restart asks for a rematch once the match
+    /// is over, and switching pieces is ignored as in local versus.
+    fn apply(&mut self, _player: usize, action: Action) {
+        match action {
+            Action::Restart => {
+                if let Status::MatchOver { .. } = self.status {
+                    self.send(ClientMessage::Ready);
+                    self.status = Status::Waiting;
+                }
+            }
+            Action::Switch => {}
+            action if self.status == Status::Playing => match self.play {
+                Play::Relay { ref mut game, .. } => game.apply(action),
+                Play::Rollback {
+                    ref mut rollback, ..
+                } => rollback.apply(action),
+            },
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, dt: Duration) {
+        loop {
+            let result = match self.messages.try_recv() {
+                Ok(message) => self.receive(message),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !matches!(self.status, Status::Failed(_)) {
+                        self.status = Status::Failed("disconnected".to_string());
+                    }
+                    break;
+                }
+            };
+            if let Err(err) = result {
+                warn!("could not follow the server: {}", err);
+                self.status = Status::Failed(err.to_string());
+            }
+        }
+
+        if let Some(remaining) = self.round_break {
+            let remaining = remaining.saturating_sub(dt);
+            self.round_break = Some(remaining);
+            if remaining.is_zero() {
+                self.round_break = None;
+                self.send(ClientMessage::Ready);
+            }
+        }
+
+        if self.status == Status::Playing {
+            self.play(dt);
+        }
+    }
+
+    fn ui_states(&self) -> Vec<(UIState, String)> {
+        vec![
+            (self.game().ui_state(), self.title()),
+            self.opponent_state(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        time::{Duration, Instant},
+    };
+
+    use clap::Parser;
+
+    use super::{Online, Status};
+    use crate::{
+        action::Action,
+        protocol::{self, ClientMessage, Netcode, ServerMessage},
+        settings::Settings,
+        versus::Match,
+    };
+
+    /// Ticks `online` until `done`, failing after a second.
+    fn tick_until(online: &mut Online, done: impl Fn(&Online) -> bool) {
+        let start = Instant::now();
+        while !done(online) {
+            assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+            online.tick(Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_plays_through_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = format!("--port={}", listener.local_addr().unwrap().port());
+        let mut online = Online::connect(&Settings::parse_from([
+            "tetris",
+            "--mode=online",
+            "--name=alice",
+            "--width=4",
+            "--height=6",
+            "--garbage-delay=0",
+            &port,
+        ]))
+        .unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut messages = protocol::messages::<ClientMessage>(stream.try_clone().unwrap());
+        assert_eq!(
+            messages.next().unwrap().unwrap(),
+            ClientMessage::Join {
+                room: "lobby".to_string(),
+                name: "alice".to_string(),
+                netcode: Netcode::Relay,
+            }
+        );
+
+        for message in [
+            ServerMessage::Start {
+                round: 1,
+                first_to: 3,
+                opponent: "bob".to_string(),
+                seat: 0,
+                seed: 1,
+            },
+            ServerMessage::Attack { lines: 2 },
+        ] {
+            protocol::send(&mut stream, &message).unwrap();
+        }
+        tick_until(&mut online, |online| {
+            online.game().ui_state().garbage_ready == 2
+        });
+        assert_eq!(online.status, Status::Playing);
+        assert!(messages.any(|message| matches!(
+            message,
+            Ok(ClientMessage::Snapshot { snapshot }) if snapshot.garbage == 2
+        )));
+
+        // Keep dropping until the rising garbage tops us out.
+        while !online.game().game_over {
+            online.tick(Duration::from_secs(1));
+            online.apply(0, Action::Drop);
+        }
+        online.tick(Duration::ZERO);
+        let topped_out = messages
+            .find(|message| !matches!(message, Ok(ClientMessage::Snapshot { .. })))
+            .unwrap()
+            .unwrap();
+        assert_eq!(topped_out, ClientMessage::ToppedOut);
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    board::Board,
+    positions::Position,
+    rotation::{
+        rotate_offsets, y_down, OffsetsMap, PositionLookup, RotationSystem, SavedSystem, Srs,
+        NO_KICK,
+    },
+    tetromino::{Orientation, Tetromino, TetrominoType},
+};
+
+/// Pieces read from a TOML file with a `[[piece]]` table for each:
+///
+/// ```toml
+/// [[piece]]
+/// name = "T"
+/// cells = [".#.", "###"]
+/// size = 3        # side of the rotation box, defaults to the longer side of `cells`
+/// spawn = "Up"    # orientation the piece spawns in, defaults to Up
+/// kicks = "srs"   # none (the default), srs, srs-i, or a table as below
+/// ```
+///
+/// A kick table is a list of `{ from = "Up", to = "Right", tests = [[-1, 0], ...] }`
+/// with y pointing up, as tables are usually published. Pieces named after a
+/// tetromino that have four cells play as that tetromino, so fumen export still
+/// works for them. Other pieces are written as garbage in board text and fumens,
+/// whatever their names. Tetrominoes the set leaves out keep their SRS shapes for
+/// fields and fumens that name them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PieceFile", into = "PieceFile")]
+pub struct PieceSet {
+    defs: Vec<PieceDef>,
+    types: Vec<TetrominoType>,
+    offsets: OffsetsMap,
+    kicks: HashMap<TetrominoType, PositionLookup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PieceFile {
+    piece: Vec<PieceDef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PieceDef {
+    name: char,
+    cells: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u32>,
+    #[serde(default = "PieceDef::default_spawn")]
+    spawn: Orientation,
+    #[serde(default)]
+    kicks: Kicks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Kicks {
+    Preset(KickPreset),
+    Table(Vec<KickRow>),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum KickPreset {
+    None,
+    Srs,
+    SrsI,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KickRow {
+    from: Orientation,
+    to: Orientation,
+    tests: Vec<(i32, i32)>,
+}
+
+impl Default for Kicks {
+    fn default() -> Self {
+        Kicks::Preset(KickPreset::None)
+    }
+}
+
+impl Kicks {
+    fn lookup(&self) -> PositionLookup {
+        match self {
+            Kicks::Preset(KickPreset::None) => PositionLookup::new(),
+            Kicks::Preset(KickPreset::Srs) => Srs::jltsz_kicks(),
+            Kicks::Preset(KickPreset::SrsI) => Srs::i_kicks(),
+            Kicks::Table(rows) => y_down(
+                rows.iter()
+                    .map(|row| {
+                        let tests = row.tests.iter().map(|(x, y)| Position { x: *x, y: *y });
+                        ((row.from, row.to), tests.collect())
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl PieceDef {
+    fn default_spawn() -> Orientation {
+        Orientation::Up
+    }
+
+    fn cells(&self) -> Result<Vec<Position>> {
+        let mut cells = vec![];
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                match cell {
+                    '#' => cells.push(Position {
+                        x: x as i32,
+                        y: y as i32,
+                    }),
+                    '.' => {}
+                    other => {
+                        return Err(anyhow!(
+                            "piece {:?} has unknown cell {:?}, use '#' or '.'",
+                            self.name,
+                            other
+                        ))
+                    }
+                }
+            }
+        }
+        if cells.is_empty() {
+            return Err(anyhow!("piece {:?} has no cells", self.name));
+        }
+        Ok(cells)
+    }
+
+    fn box_size(&self) -> Result<u32> {
+        let longest = self
+            .cells
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0)
+            .max(self.cells.len()) as u32;
+        match self.size {
+            Some(size) if size < longest => Err(anyhow!(
+                "piece {:?} is {} cells across but its box is only {}",
+                self.name,
+                longest,
+                size
+            )),
+            Some(size) => Ok(size),
+            None => Ok(longest),
+        }
+    }
+
+    fn type_(&self, cell_count: usize) -> TetrominoType {
+        match TetrominoType::from_letter(self.name) {
+            Some(type_) if cell_count == 4 => type_,
+            _ => TetrominoType::Custom(self.name),
+        }
+    }
+}
+
+impl PieceSet {
+    /// Reads a piece set file, for use as a command line value parser.
+    pub fn from_file(path: &str) -> Result<Self> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl TryFrom<PieceFile> for PieceSet {
+    type Error = anyhow::Error;
+
+    fn try_from(file: PieceFile) -> Result<Self> {
+        if file.piece.is_empty() {
+            return Err(anyhow!("piece set has no pieces"));
+        }
+        let mut names = HashSet::new();
+        let mut types = vec![];
+        let mut offsets = OffsetsMap::new();
+        let mut kicks = HashMap::new();
+        for def in &file.piece {
+            if !names.insert(def.name) {
+                return Err(anyhow!("piece {:?} is defined twice", def.name));
+            }
+            let cells = def.cells()?;
+            let size = def.box_size()?;
+            let type_ = def.type_(cells.len());
+            for or in Orientation::iter() {
+                offsets.insert((type_, or), rotate_offsets(&cells, or, size));
+            }
+            kicks.insert(type_, def.kicks.lookup());
+            types.push(type_);
+        }
+
+        let srs = Srs::new();
+        for type_ in TetrominoType::iter() {
+            for or in Orientation::iter() {
+                offsets
+                    .entry((type_, or))
+                    .or_insert_with(|| srs.offsets(type_, or).to_vec());
+            }
+        }
+
+        Ok(Self {
+            defs: file.piece,
+            types,
+            offsets,
+            kicks,
+        })
+    }
+}
+
+impl From<PieceSet> for PieceFile {
+    fn from(set: PieceSet) -> Self {
+        Self { piece: set.defs }
+    }
+}
+
+impl RotationSystem for PieceSet {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Pieces(self.clone())
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.offsets.get(&(type_, orientation)).unwrap()
+    }
+
+    fn kicks(&self, _board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position> {
+        let kicks = self
+            .kicks
+            .get(&tetromino.type_)
+            .and_then(|table| table.get(&(tetromino.orientation, to)));
+        std::iter::once(NO_KICK)
+            .chain(kicks.into_iter().flatten().copied())
+            .collect()
+    }
+
+    fn pieces(&self) -> Vec<TetrominoType> {
+        self.types.clone()
+    }
+
+    fn spawn_orientation(&self, type_: TetrominoType) -> Orientation {
+        self.types
+            .iter()
+            .zip(&self.defs)
+            .find(|(defined, _)| **defined == type_)
+            .map(|(_, def)| def.spawn)
+            .unwrap_or(Orientation::Up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::PieceSet;
+    use crate::{
+        positions::Position,
+        rotation::RotationSystem,
+        tetromino::{Orientation, TetrominoType},
+    };
+
+    const PENTOMINOES: &str = include_str!("../pieces/pentominoes.toml");
+    const TRIOMINOES: &str = include_str!("../pieces/triominoes.toml");
+
+    fn sorted(offsets: &[Position]) -> Vec<(i32, i32)> {
+        let mut cells = offsets.iter().map(|pos| (pos.x, pos.y)).collect::<Vec<_>>();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn test_bundled_sets() {
+        let pentominoes = toml::from_str::<PieceSet>(PENTOMINOES).unwrap();
+        assert_eq!(pentominoes.pieces().len(), 18);
+        for type_ in pentominoes.pieces() {
+            assert!(matches!(type_, TetrominoType::Custom(_)));
+            for or in Orientation::iter() {
+                assert_eq!(pentominoes.offsets(type_, or).len(), 5);
+            }
+        }
+
+        let triominoes = toml::from_str::<PieceSet>(TRIOMINOES).unwrap();
+        assert_eq!(triominoes.pieces().len(), 2);
+        assert!(triominoes
+            .pieces()
+            .iter()
+            .all(|type_| triominoes.offsets(*type_, Orientation::Up).len() == 3));
+    }
+
+    #[test]
+    fn test_mixed_set() {
+        let set = toml::from_str::<PieceSet>(
+            r####"
+            [[piece]]
+            name = "T"
+            cells = [".#.", "###"]
+            kicks = "srs"
+
+            [[piece]]
+            name = "+"
+            cells = [".#.", "###", ".#."]
+            spawn = "Down"
+            kicks = [{ from = "Up", to = "Right", tests = [[0, 1]] }]
+            "####,
+        )
+        .unwrap();
+        let plus = TetrominoType::Custom('+');
+
+        assert_eq!(set.pieces(), vec![TetrominoType::T, plus]);
+        assert_eq!(
+            sorted(set.offsets(TetrominoType::T, Orientation::Right)),
+            vec![(1, 0), (1, 1), (1, 2), (2, 1)]
+        );
+        assert_eq!(set.spawn_orientation(plus), Orientation::Down);
+        assert_eq!(set.spawn_orientation(TetrominoType::T), Orientation::Up);
+        // Tetrominoes the set doesn't name fall back to SRS.
+        assert_eq!(set.offsets(TetrominoType::I, Orientation::Up).len(), 4);
+
+        let saved = serde_json::to_string(&set).unwrap();
+        let loaded = serde_json::from_str::<PieceSet>(&saved).unwrap();
+        assert_eq!(loaded.pieces(), set.pieces());
+    }
+
+    #[test]
+    fn test_rejects_bad_sets() {
+        let parse = |text: &str| toml::from_str::<PieceSet>(text);
+        assert!(parse("piece = []").is_err());
+        assert!(parse("[[piece]]\nname = \"A\"\ncells = [\"#x\"]").is_err());
+        assert!(parse("[[piece]]\nname = \"A\"\ncells = [\"..\"]").is_err());
+        assert!(parse("[[piece]]\nname = \"A\"\ncells = [\"###\"]\nsize = 2").is_err());
+        assert!(parse(
+            "[[piece]]\nname = \"A\"\ncells = [\"#\"]\n[[piece]]\nname = \"A\"\ncells = [\"#\"]"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    board::Board,
+    positions::Position,
+    rotation::{rotate_offsets, OffsetsMap, RotationSystem, SavedSystem, NO_KICK},
+    tetromino::{Orientation, Tetromino, TetrominoType},
+};
+
+pub const MAX_SIZE: u8 = 7;
+
+/// Sideways and upward nudges tried after the plain rotation, since generated
+/// shapes have no published kick tables.
+const KICKS: [Position; 5] = [
+    Position { x: -1, y: 0 },
+    Position { x: 1, y: 0 },
+    Position { x: 0, y: -1 },
+    Position { x: -2, y: 0 },
+    Position { x: 2, y: 0 },
+];
+
+type Cell = (i32, i32);
+type Shape = Vec<Cell>;
+
+/// Every free polyomino of one size, so a random pick from `pieces` is fair
+/// across the distinct shapes. Pieces are `TetrominoType::Polyomino` indexes
+/// into the list and are saved as just the size, since the list is rebuilt the
+/// same way each time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Polyominoes {
+    size: u8,
+    types: Vec<TetrominoType>,
+    offsets: OffsetsMap,
+}
+
+impl Polyominoes {
+    pub fn new(size: u8) -> Result<Self> {
+        if !(1..=MAX_SIZE).contains(&size) {
+            return Err(anyhow!("polyominoes must have 1 to {} cells", MAX_SIZE));
+        }
+        let mut types = vec![];
+        let mut offsets = OffsetsMap::new();
+        for (index, shape) in free_polyominoes(size as usize).iter().enumerate() {
+            let type_ = TetrominoType::Polyomino(index as u8);
+            let cells = shape
+                .iter()
+                .map(|(x, y)| Position { x: *x, y: *y })
+                .collect::<Vec<_>>();
+            let (width, height) = extent(shape);
+            for or in Orientation::iter() {
+                offsets.insert((type_, or), rotate_offsets(&cells, or, width.max(height)));
+            }
+            types.push(type_);
+        }
+        Ok(Self {
+            size,
+            types,
+            offsets,
+        })
+    }
+}
+
+impl TryFrom<u8> for Polyominoes {
+    type Error = anyhow::Error;
+
+    fn try_from(size: u8) -> Result<Self> {
+        Self::new(size)
+    }
+}
+
+impl From<Polyominoes> for u8 {
+    fn from(polyominoes: Polyominoes) -> Self {
+        polyominoes.size
+    }
+}
+
+impl RotationSystem for Polyominoes {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Polyominoes(self.clone())
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.offsets.get(&(type_, orientation)).unwrap()
+    }
+
+    fn kicks(&self, _board: &Board, _tetromino: &Tetromino, _to: Orientation) -> Vec<Position> {
+        std::iter::once(NO_KICK).chain(KICKS).collect()
+    }
+
+    fn pieces(&self) -> Vec<TetrominoType> {
+        self.types.clone()
+    }
+}
+
+/// The free polyominoes with `size` cells, each lying flat (at least as wide as
+/// it is tall) and moved to the top left corner of its box.
+pub fn free_polyominoes(size: usize) -> Vec<Shape> {
+    let mut shapes = BTreeSet::from([vec![(0, 0)]]);
+    for _ in 1..size {
+        let mut grown = BTreeSet::new();
+        for shape in &shapes {
+            for (x, y) in shape {
+                for next in [(x - 1, *y), (x + 1, *y), (*x, y - 1), (*x, y + 1)] {
+                    if !shape.contains(&next) {
+                        let mut bigger = shape.clone();
+                        bigger.push(next);
+                        grown.insert(canonical(&bigger));
+                    }
+                }
+            }
+        }
+        shapes = grown;
+    }
+    shapes.into_iter().collect()
+}
+
+/// The smallest of the shape's rotations and reflections that lies flat, so
+/// every copy of a free polyomino maps to the same shape.
+fn canonical(shape: &[Cell]) -> Shape {
+    let transforms: [fn(Cell) -> Cell; 8] = [
+        |(x, y)| (x, y),
+        |(x, y)| (-y, x),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, -x),
+        |(x, y)| (-x, y),
+        |(x, y)| (y, x),
+        |(x, y)| (x, -y),
+        |(x, y)| (-y, -x),
+    ];
+    transforms
+        .iter()
+        .map(|transform| normalise(shape.iter().map(|cell| transform(*cell)).collect()))
+        .filter(|shape| {
+            let (width, height) = extent(shape);
+            width >= height
+        })
+        .min()
+        .unwrap()
+}
+
+fn normalise(mut shape: Shape) -> Shape {
+    let min_x = shape.iter().map(|(x, _)| *x).min().unwrap();
+    let min_y = shape.iter().map(|(_, y)| *y).min().unwrap();
+    for (x, y) in shape.iter_mut() {
+        *x -= min_x;
+        *y -= min_y;
+    }
+    shape.sort();
+    shape
+}
+
+fn extent(shape: &[Cell]) -> (u32, u32) {
+    let width = shape.iter().map(|(x, _)| *x).max().unwrap() + 1;
+    let height = shape.iter().map(|(_, y)| *y).max().unwrap() + 1;
+    (width as u32, height as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use strum::IntoEnumIterator;
+
+    use super::{canonical, free_polyominoes, Polyominoes, MAX_SIZE};
+    use crate::{positions::Position, rotation::RotationSystem, tetromino::Orientation};
+
+    fn connected(cells: &[Position]) -> bool {
+        let cells = cells.iter().copied().collect::<HashSet<_>>();
+        let mut seen = HashSet::from([*cells.iter().next().unwrap()]);
+        let mut todo = seen.iter().copied().collect::<Vec<_>>();
+        while let Some(pos) = todo.pop() {
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let next = Position {
+                    x: pos.x + dx,
+                    y: pos.y + dy,
+                };
+                if cells.contains(&next) && seen.insert(next) {
+                    todo.push(next);
+                }
+            }
+        }
+        seen.len() == cells.len()
+    }
+
+    #[test]
+    fn test_free_polyomino_counts() {
+        let counts = (1..=MAX_SIZE as usize)
+            .map(|size| free_polyominoes(size).len())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 1, 2, 5, 12, 35, 108]);
+    }
+
+    #[test]
+    fn test_turned_shapes_are_the_same_piece() {
+        let polyominoes = Polyominoes::new(5).unwrap();
+        let shapes = free_polyominoes(5);
+        for (type_, shape) in polyominoes.pieces().into_iter().zip(&shapes) {
+            for or in Orientation::iter() {
+                let turned = polyominoes.offsets(type_, or);
+                let turned = turned.iter().map(|pos| (pos.x, pos.y)).collect::<Vec<_>>();
+                assert_eq!(&canonical(&turned), shape);
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_shape_is_connected() {
+        for size in 1..=MAX_SIZE {
+            let polyominoes = Polyominoes::new(size).unwrap();
+            for type_ in polyominoes.pieces() {
+                for or in Orientation::iter() {
+                    let offsets = polyominoes.offsets(type_, or);
+                    let distinct = offsets.iter().collect::<HashSet<_>>();
+                    assert_eq!(distinct.len(), size as usize);
+                    assert!(offsets.iter().all(|pos| pos.x >= 0 && pos.y >= 0));
+                    assert!(connected(offsets), "{:?} {:?}", type_, or);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_sizes() {
+        assert!(Polyominoes::new(0).is_err());
+        assert!(Polyominoes::new(MAX_SIZE + 1).is_err());
+        assert!(serde_json::from_str::<Polyominoes>("8").is_err());
+        let saved = serde_json::to_string(&Polyominoes::new(6).unwrap()).unwrap();
+        assert_eq!(saved, "6");
+    }
+}
@@ -1,6 +1,8 @@
 use std::ops::Add;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
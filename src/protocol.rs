@@ -0,0 +1,168 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    iter,
+};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::action::Action;
+
+/// Port the server listens on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// Bytes a message can take, so a peer can't make us buffer an endless line.
+/// Room for a versus screen on the biggest boards.
+const MAX_MESSAGE: u64 = 1 << 20;
+
+/// How the clients in a room keep each other's games.
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Netcode {
+    /// Each client plays its own game and reports its attacks and board
+    Relay,
+    /// Clients send only their inputs and simulate both games, rolling back on
+    /// late inputs. The server checks both clients' boards agree
+    Rollback,
+}
+
+/// Messages from a client to the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Joins the room with this name, creating it if it doesn't exist.
+    /// Every player in a room has to use the same netcode.
+    Join {
+        room: String,
+        name: String,
+        netcode: Netcode,
+    },
+    /// Ready for the next round, or for a rematch once the match is over.
+    Ready,
+    /// Garbage for the opponent, after cancelling our own.
+    Attack {
+        lines: u32,
+    },
+    Snapshot {
+        snapshot: Snapshot,
+    },
+    ToppedOut,
+    /// Rollback netcode: our actions for each frame from `frame` on.
+    Inputs {
+        frame: usize,
+        actions: Vec<Vec<Action>>,
+    },
+    /// Rollback netcode: a hash of both boards after each frame from `frame` on,
+    /// once every player's inputs for it are known.
+    Checksums {
+        frame: usize,
+        hashes: Vec<u64>,
+    },
+}
+
+/// Messages from the server to a client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// In a room, but nobody to play yet.
+    Waiting,
+    /// Both players' games use `seed`. `seat` is this player's place in the
+    /// room, which orders the games in rollback checksums.
+    Start {
+        round: u32,
+        first_to: u32,
+        opponent: String,
+        seat: usize,
+        seed: u64,
+    },
+    Attack {
+        lines: u32,
+    },
+    Opponent {
+        snapshot: Snapshot,
+    },
+    RoundOver {
+        won: bool,
+        wins: u32,
+        opponent_wins: u32,
+        match_over: bool,
+    },
+    /// The opponent disconnected. The match is abandoned and the room waits for
+    /// someone else.
+    OpponentLeft,
+    /// Rollback netcode: the opponent's actions for each frame from `frame` on.
+    Inputs {
+        frame: usize,
+        actions: Vec<Vec<Action>>,
+    },
+    /// Rollback netcode: the players' checksums for `frame` differ. The round is
+    /// abandoned.
+    Desync {
+        frame: usize,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// What an opponent needs to draw someone else's game. The board is in the
+/// board text format, with the falling piece included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub board: String,
+    pub score: u32,
+    pub lines: u32,
+    pub garbage: u32,
+}
+
+/// Writes `message` as one line of JSON.
+pub fn send<T: Serialize>(stream: &mut impl Write, message: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads messages sent with `send` until the stream closes. A line longer than
+/// `MAX_MESSAGE` is an error.
+pub fn messages<T: DeserializeOwned>(stream: impl Read) -> impl Iterator<Item = Result<T>> {
+    let mut reader = BufReader::new(stream);
+    iter::from_fn(move || {
+        let mut line = vec![];
+        match (&mut reader)
+            .take(MAX_MESSAGE + 1)
+            .read_until(b'\n', &mut line)
+        {
+            Ok(0) => None,
+            Ok(read) if read as u64 > MAX_MESSAGE => {
+                Some(Err(anyhow!("message longer than {} bytes", MAX_MESSAGE)))
+            }
+            Ok(_) => Some(serde_json::from_slice(&line).map_err(Into::into)),
+            Err(err) => Some(Err(err.into())),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{messages, send, ClientMessage, MAX_MESSAGE};
+
+    #[test]
+    fn test_messages() {
+        let mut stream = vec![];
+        send(&mut stream, &ClientMessage::Ready).unwrap();
+        send(&mut stream, &ClientMessage::Attack { lines: 2 }).unwrap();
+        let read = messages::<ClientMessage>(Cursor::new(stream))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            [ClientMessage::Ready, ClientMessage::Attack { lines: 2 }]
+        );
+
+        let endless = vec![b' '; MAX_MESSAGE as usize + 1];
+        let mut read = messages::<ClientMessage>(Cursor::new(endless));
+        assert!(read.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,843 @@
+use std::{collections::HashMap, fmt::Debug, fs, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum::IntoEnumIterator;
+
+use crate::{
+    board::Board,
+    pieces::PieceSet,
+    polyominoes::Polyominoes,
+    positions::Position,
+    tetromino::{Orientation, Tetromino, TetrominoType},
+};
+
+pub type BlockOffsets = Vec<Position>;
+pub type PositionLookup = HashMap<(Orientation, Orientation), Vec<Position>>;
+pub type OffsetsMap = HashMap<(TetrominoType, Orientation), BlockOffsets>;
+
+pub const NO_KICK: Position = Position { x: 0, y: 0 };
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RotationSystemKind {
+    /// Super Rotation System, the modern guideline
+    Srs,
+    /// SRS with the symmetric I kicks used by TETR.IO
+    SrsPlus,
+    /// Arika Rotation System from TGM
+    Ars,
+    /// The classic NES game, which never kicks
+    Nes,
+}
+
+impl RotationSystemKind {
+    pub fn build(self) -> Box<dyn RotationSystem> {
+        match self {
+            RotationSystemKind::Srs => Box::new(Srs::new()),
+            RotationSystemKind::SrsPlus => Box::new(SrsPlus::new()),
+            RotationSystemKind::Ars => Box::new(Ars::new()),
+            RotationSystemKind::Nes => Box::new(Nes::new()),
+        }
+    }
+}
+
+/// Where each piece's blocks sit in every orientation, and where it may move to
+/// when a rotation is blocked.
+pub trait RotationSystem: Debug + Send + Sync {
+    fn save(&self) -> SavedSystem;
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position];
+
+    /// Offsets from the box corner to try in order when rotating `tetromino` to
+    /// `to`. The first entry is the unkicked rotation.
+    fn kicks(&self, board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position>;
+
+    /// The pieces the spawner picks from.
+    fn pieces(&self) -> Vec<TetrominoType> {
+        TetrominoType::iter().collect()
+    }
+
+    fn spawn_orientation(&self, _type_: TetrominoType) -> Orientation {
+        Orientation::Up
+    }
+}
+
+/// How a rotation system is saved: built-in ones by kind, piece sets in full.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SavedSystem {
+    Builtin(RotationSystemKind),
+    Pieces(PieceSet),
+    Polyominoes(Polyominoes),
+}
+
+/// Rotation systems are saved as a `SavedSystem` and rebuilt on load.
+#[allow(clippy::borrowed_box)] // serde's `with` passes the field by reference
+pub fn serialize<S: Serializer>(
+    system: &Box<dyn RotationSystem>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    system.save().serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Box<dyn RotationSystem>, D::Error> {
+    Ok(match SavedSystem::deserialize(deserializer)? {
+        SavedSystem::Builtin(kind) => kind.build(),
+        SavedSystem::Pieces(set) => Box::new(set),
+        SavedSystem::Polyominoes(polyominoes) => Box::new(polyominoes),
+    })
+}
+
+#[derive(Debug)]
+pub struct Srs {
+    jltsz_kicks_lookup: PositionLookup,
+    i_kicks_lookup: PositionLookup,
+    offsets_lookup: OffsetsMap,
+}
+
+impl Srs {
+    pub fn new() -> Self {
+        Self {
+            jltsz_kicks_lookup: Self::jltsz_kicks(),
+            i_kicks_lookup: Self::i_kicks(),
+            offsets_lookup: Self::build_offsets_lookup(),
+        }
+    }
+
+    /// The J, L, S, T and Z kick table in board coordinates.
+    pub fn jltsz_kicks() -> PositionLookup {
+        y_down(Self::build_jltsz_kicks_lookup())
+    }
+
+    /// The I kick table in board coordinates.
+    pub fn i_kicks() -> PositionLookup {
+        y_down(Self::build_i_kicks_lookup())
+    }
+
+    fn kick_table(&self, type_: TetrominoType) -> Option<&PositionLookup> {
+        match type_ {
+            TetrominoType::I => Some(&self.i_kicks_lookup),
+            TetrominoType::O => None,
+            _ => Some(&self.jltsz_kicks_lookup),
+        }
+    }
+
+    fn box_size(type_: TetrominoType) -> u32 {
+        match type_ {
+            TetrominoType::I => 4,
+            TetrominoType::O => 2,
+            _ => 3,
+        }
+    }
+
+    /// Kick tables here are written as published, with y pointing up.
+    fn build_jltsz_kicks_lookup() -> PositionLookup {
+        let jltsz_0_1_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: -1, y: 1 },
+            Position { x: 0, y: -2 },
+            Position { x: -1, y: -2 },
+        ];
+        let jltsz_1_0_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: 1, y: -1 },
+            Position { x: 0, y: 2 },
+            Position { x: 1, y: 2 },
+        ];
+        let jltsz_1_2_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: 1, y: -1 },
+            Position { x: 0, y: 2 },
+            Position { x: 1, y: 2 },
+        ];
+        let jltsz_2_1_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: -1, y: 1 },
+            Position { x: 0, y: -2 },
+            Position { x: -1, y: -2 },
+        ];
+        let jltsz_2_3_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: 1, y: 1 },
+            Position { x: 0, y: -2 },
+            Position { x: 1, y: -2 },
+        ];
+        let jltsz_3_2_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: -1, y: -1 },
+            Position { x: 0, y: 2 },
+            Position { x: -1, y: 2 },
+        ];
+        let jltsz_3_0_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: -1, y: -1 },
+            Position { x: 0, y: 2 },
+            Position { x: -1, y: 2 },
+        ];
+        let jltsz_0_3_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: 1, y: 1 },
+            Position { x: 0, y: -2 },
+            Position { x: 1, y: -2 },
+        ];
+        HashMap::from([
+            ((Orientation::Up, Orientation::Right), jltsz_0_1_kicks),
+            ((Orientation::Right, Orientation::Up), jltsz_1_0_kicks),
+            ((Orientation::Right, Orientation::Down), jltsz_1_2_kicks),
+            ((Orientation::Down, Orientation::Right), jltsz_2_1_kicks),
+            ((Orientation::Down, Orientation::Left), jltsz_2_3_kicks),
+            ((Orientation::Left, Orientation::Down), jltsz_3_2_kicks),
+            ((Orientation::Left, Orientation::Up), jltsz_3_0_kicks),
+            ((Orientation::Up, Orientation::Left), jltsz_0_3_kicks),
+        ])
+    }
+
+    fn build_i_kicks_lookup() -> PositionLookup {
+        let i_0_1_kicks = vec![
+            Position { x: -2, y: 0 },
+            Position { x: 1, y: 0 },
+            Position { x: -2, y: -1 },
+            Position { x: 1, y: 2 },
+        ];
+        let i_1_0_kicks = vec![
+            Position { x: 2, y: 0 },
+            Position { x: -1, y: 0 },
+            Position { x: 2, y: 1 },
+            Position { x: -1, y: -2 },
+        ];
+        let i_1_2_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: 2, y: 0 },
+            Position { x: -1, y: 2 },
+            Position { x: 2, y: -1 },
+        ];
+        let i_2_1_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: -2, y: 0 },
+            Position { x: 1, y: -2 },
+            Position { x: -2, y: 1 },
+        ];
+        let i_2_3_kicks = vec![
+            Position { x: 2, y: 0 },
+            Position { x: -1, y: 0 },
+            Position { x: 2, y: 1 },
+            Position { x: -1, y: -2 },
+        ];
+        let i_3_2_kicks = vec![
+            Position { x: -2, y: 0 },
+            Position { x: 1, y: 0 },
+            Position { x: -2, y: -1 },
+            Position { x: 1, y: 2 },
+        ];
+        let i_3_0_kicks = vec![
+            Position { x: 1, y: 0 },
+            Position { x: -2, y: 0 },
+            Position { x: 1, y: -2 },
+            Position { x: -2, y: 1 },
+        ];
+        let i_0_3_kicks = vec![
+            Position { x: -1, y: 0 },
+            Position { x: 2, y: 0 },
+            Position { x: -1, y: 2 },
+            Position { x: 2, y: -1 },
+        ];
+        HashMap::from([
+            ((Orientation::Up, Orientation::Right), i_0_1_kicks),
+            ((Orientation::Right, Orientation::Up), i_1_0_kicks),
+            ((Orientation::Right, Orientation::Down), i_1_2_kicks),
+            ((Orientation::Down, Orientation::Right), i_2_1_kicks),
+            ((Orientation::Down, Orientation::Left), i_2_3_kicks),
+            ((Orientation::Left, Orientation::Down), i_3_2_kicks),
+            ((Orientation::Left, Orientation::Up), i_3_0_kicks),
+            ((Orientation::Up, Orientation::Left), i_0_3_kicks),
+        ])
+    }
+
+    fn build_offsets_lookup() -> OffsetsMap {
+        let orientations = HashMap::from([
+            (TetrominoType::I, cells(&[(0, 1), (1, 1), (2, 1), (3, 1)])),
+            (TetrominoType::J, cells(&[(0, 0), (0, 1), (1, 1), (2, 1)])),
+            (TetrominoType::L, cells(&[(0, 1), (1, 1), (2, 1), (2, 0)])),
+            (TetrominoType::O, cells(&[(0, 0), (0, 1), (1, 0), (1, 1)])),
+            (TetrominoType::S, cells(&[(0, 1), (1, 1), (1, 0), (2, 0)])),
+            (TetrominoType::T, cells(&[(0, 1), (1, 1), (1, 0), (2, 1)])),
+            (TetrominoType::Z, cells(&[(0, 0), (1, 0), (1, 1), (2, 1)])),
+        ]);
+
+        TetrominoType::iter()
+            .flat_map(|type_| {
+                let offsets = orientations.get(&type_).unwrap();
+                Orientation::iter().map(move |or| {
+                    (
+                        (type_, or),
+                        rotate_offsets(offsets, or, Self::box_size(type_)),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl RotationSystem for Srs {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Builtin(RotationSystemKind::Srs)
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.offsets_lookup.get(&(type_, orientation)).unwrap()
+    }
+
+    fn kicks(&self, _board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position> {
+        let kicks = self
+            .kick_table(tetromino.type_)
+            .and_then(|table| table.get(&(tetromino.orientation, to)));
+        std::iter::once(NO_KICK)
+            .chain(kicks.into_iter().flatten().copied())
+            .collect()
+    }
+}
+
+/// SRS with an I kick table that treats left and right alike, so an I rotating
+/// next to either wall gets the same chances.
+#[derive(Debug)]
+pub struct SrsPlus {
+    srs: Srs,
+    i_kicks_lookup: PositionLookup,
+}
+
+impl SrsPlus {
+    pub fn new() -> Self {
+        Self {
+            srs: Srs::new(),
+            i_kicks_lookup: y_down(Self::build_i_kicks_lookup()),
+        }
+    }
+
+    fn build_i_kicks_lookup() -> PositionLookup {
+        let kicks = |table: [(i32, i32); 4]| {
+            table
+                .into_iter()
+                .map(|(x, y)| Position { x, y })
+                .collect::<Vec<_>>()
+        };
+        HashMap::from([
+            (
+                (Orientation::Up, Orientation::Right),
+                kicks([(1, 0), (-2, 0), (-2, -1), (1, 2)]),
+            ),
+            (
+                (Orientation::Right, Orientation::Up),
+                kicks([(-1, 0), (2, 0), (-1, -2), (2, 1)]),
+            ),
+            (
+                (Orientation::Right, Orientation::Down),
+                kicks([(-1, 0), (2, 0), (-1, 2), (2, -1)]),
+            ),
+            (
+                (Orientation::Down, Orientation::Right),
+                kicks([(-2, 0), (1, 0), (-2, 1), (1, -2)]),
+            ),
+            (
+                (Orientation::Down, Orientation::Left),
+                kicks([(2, 0), (-1, 0), (2, 1), (-1, -2)]),
+            ),
+            (
+                (Orientation::Left, Orientation::Down),
+                kicks([(1, 0), (-2, 0), (1, -2), (-2, 1)]),
+            ),
+            (
+                (Orientation::Left, Orientation::Up),
+                kicks([(1, 0), (-2, 0), (1, 2), (-2, -1)]),
+            ),
+            (
+                (Orientation::Up, Orientation::Left),
+                kicks([(-1, 0), (2, 0), (-1, 2), (2, -1)]),
+            ),
+        ])
+    }
+}
+
+impl RotationSystem for SrsPlus {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Builtin(RotationSystemKind::SrsPlus)
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.srs.offsets(type_, orientation)
+    }
+
+    fn kicks(&self, board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position> {
+        if tetromino.type_ != TetrominoType::I {
+            return self.srs.kicks(board, tetromino, to);
+        }
+        let kicks = self.i_kicks_lookup.get(&(tetromino.orientation, to));
+        std::iter::once(NO_KICK)
+            .chain(kicks.into_iter().flatten().copied())
+            .collect()
+    }
+}
+
+/// The TGM rotation system. Pieces sit flat side down in their box and kick one
+/// column right, then left. L, J and T refuse to kick when the first blocked cell
+/// of the rotated piece, in reading order, is in the centre column of the box.
+/// T may also kick up a row off the floor, and I kicks up to two rows up when
+/// stood upright and up to two columns sideways when laid flat.
+#[derive(Debug)]
+pub struct Ars {
+    offsets_lookup: OffsetsMap,
+}
+
+impl Ars {
+    pub fn new() -> Self {
+        Self {
+            offsets_lookup: Self::build_offsets_lookup(),
+        }
+    }
+
+    fn centre_column_blocked(&self, board: &Board, tetromino: &Tetromino, to: Orientation) -> bool {
+        let mut blocked = self
+            .offsets(tetromino.type_, to)
+            .iter()
+            .filter(|offset| board.blocked(&(tetromino.box_corner + **offset)))
+            .copied()
+            .collect::<Vec<_>>();
+        blocked.sort_by_key(|offset| (offset.y, offset.x));
+        blocked.first().map(|offset| offset.x == 1).unwrap_or(false)
+    }
+
+    fn build_offsets_lookup() -> OffsetsMap {
+        let flat_i = cells(&[(0, 1), (1, 1), (2, 1), (3, 1)]);
+        let upright_i = cells(&[(2, 0), (2, 1), (2, 2), (2, 3)]);
+        let flat_s = cells(&[(1, 1), (2, 1), (0, 2), (1, 2)]);
+        let upright_s = cells(&[(0, 0), (0, 1), (1, 1), (1, 2)]);
+        let flat_z = cells(&[(0, 1), (1, 1), (1, 2), (2, 2)]);
+        let upright_z = cells(&[(2, 0), (1, 1), (2, 1), (1, 2)]);
+        let shapes = [
+            (
+                TetrominoType::I,
+                [flat_i.clone(), upright_i.clone(), flat_i, upright_i],
+            ),
+            (
+                TetrominoType::J,
+                [
+                    cells(&[(0, 1), (1, 1), (2, 1), (2, 2)]),
+                    cells(&[(1, 0), (1, 1), (0, 2), (1, 2)]),
+                    cells(&[(0, 1), (0, 2), (1, 2), (2, 2)]),
+                    cells(&[(1, 0), (2, 0), (1, 1), (1, 2)]),
+                ],
+            ),
+            (
+                TetrominoType::L,
+                [
+                    cells(&[(0, 1), (1, 1), (2, 1), (0, 2)]),
+                    cells(&[(0, 0), (1, 0), (1, 1), (1, 2)]),
+                    cells(&[(2, 1), (0, 2), (1, 2), (2, 2)]),
+                    cells(&[(1, 0), (1, 1), (1, 2), (2, 2)]),
+                ],
+            ),
+            (
+                TetrominoType::O,
+                [0; 4].map(|_| cells(&[(0, 1), (1, 1), (0, 2), (1, 2)])),
+            ),
+            (
+                TetrominoType::S,
+                [flat_s.clone(), upright_s.clone(), flat_s, upright_s],
+            ),
+            (
+                TetrominoType::T,
+                [
+                    cells(&[(0, 1), (1, 1), (2, 1), (1, 2)]),
+                    cells(&[(1, 0), (0, 1), (1, 1), (1, 2)]),
+                    cells(&[(1, 1), (0, 2), (1, 2), (2, 2)]),
+                    cells(&[(1, 0), (1, 1), (2, 1), (1, 2)]),
+                ],
+            ),
+            (
+                TetrominoType::Z,
+                [flat_z.clone(), upright_z.clone(), flat_z, upright_z],
+            ),
+        ];
+        shapes
+            .into_iter()
+            .flat_map(|(type_, offsets)| {
+                Orientation::iter()
+                    .zip(offsets)
+                    .map(move |(or, offsets)| ((type_, or), offsets))
+            })
+            .collect()
+    }
+}
+
+impl RotationSystem for Ars {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Builtin(RotationSystemKind::Ars)
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.offsets_lookup.get(&(type_, orientation)).unwrap()
+    }
+
+    fn kicks(&self, board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position> {
+        let sideways = [NO_KICK, Position { x: 1, y: 0 }, Position { x: -1, y: 0 }];
+        match tetromino.type_ {
+            TetrominoType::O => vec![NO_KICK],
+            TetrominoType::I if matches!(to, Orientation::Right | Orientation::Left) => {
+                vec![NO_KICK, Position { x: 0, y: -1 }, Position { x: 0, y: -2 }]
+            }
+            TetrominoType::I => vec![
+                NO_KICK,
+                Position { x: 1, y: 0 },
+                Position { x: -1, y: 0 },
+                Position { x: 2, y: 0 },
+            ],
+            TetrominoType::S | TetrominoType::Z => sideways.to_vec(),
+            _ if self.centre_column_blocked(board, tetromino, to) => vec![NO_KICK],
+            TetrominoType::T => sideways
+                .into_iter()
+                .chain([Position { x: 0, y: -1 }])
+                .collect(),
+            _ => sideways.to_vec(),
+        }
+    }
+}
+
+/// The NES game: pieces spawn flat side up, S, Z and I only have two states, and
+/// a blocked rotation simply fails.
+#[derive(Debug)]
+pub struct Nes {
+    offsets_lookup: OffsetsMap,
+}
+
+impl Nes {
+    pub fn new() -> Self {
+        Self {
+            offsets_lookup: Self::build_offsets_lookup(),
+        }
+    }
+
+    fn build_offsets_lookup() -> OffsetsMap {
+        let flat_i = cells(&[(0, 2), (1, 2), (2, 2), (3, 2)]);
+        let upright_i = cells(&[(2, 0), (2, 1), (2, 2), (2, 3)]);
+        let flat_s = cells(&[(1, 1), (2, 1), (0, 2), (1, 2)]);
+        let upright_s = cells(&[(1, 0), (1, 1), (2, 1), (2, 2)]);
+        let flat_z = cells(&[(0, 1), (1, 1), (1, 2), (2, 2)]);
+        let upright_z = cells(&[(2, 0), (1, 1), (2, 1), (1, 2)]);
+        let two_state = [
+            (TetrominoType::I, [flat_i, upright_i]),
+            (TetrominoType::S, [flat_s, upright_s]),
+            (TetrominoType::Z, [flat_z, upright_z]),
+        ];
+        let rotating = [
+            (TetrominoType::J, cells(&[(0, 1), (1, 1), (2, 1), (2, 2)])),
+            (TetrominoType::L, cells(&[(0, 1), (1, 1), (2, 1), (0, 2)])),
+            (TetrominoType::T, cells(&[(0, 1), (1, 1), (2, 1), (1, 2)])),
+        ];
+        let o = cells(&[(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+        let mut lookup = OffsetsMap::new();
+        for or in Orientation::iter() {
+            let upright = matches!(or, Orientation::Right | Orientation::Left);
+            for (type_, [flat, upright_offsets]) in &two_state {
+                let offsets = if upright { upright_offsets } else { flat };
+                lookup.insert((*type_, or), offsets.clone());
+            }
+            for (type_, offsets) in &rotating {
+                lookup.insert((*type_, or), rotate_offsets(offsets, or, 3));
+            }
+            lookup.insert((TetrominoType::O, or), o.clone());
+        }
+        lookup
+    }
+}
+
+impl RotationSystem for Nes {
+    fn save(&self) -> SavedSystem {
+        SavedSystem::Builtin(RotationSystemKind::Nes)
+    }
+
+    fn offsets(&self, type_: TetrominoType, orientation: Orientation) -> &[Position] {
+        self.offsets_lookup.get(&(type_, orientation)).unwrap()
+    }
+
+    fn kicks(&self, _board: &Board, _tetromino: &Tetromino, _to: Orientation) -> Vec<Position> {
+        vec![NO_KICK]
+    }
+}
+
+/// Kicks tried when turning a piece around in one move, in board coordinates. The
+/// default table only turns in place.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Kicks180 {
+    table: Vec<(Orientation, Orientation, Vec<Position>)>,
+}
+
+impl Kicks180 {
+    /// The table TETR.IO uses for every piece.
+    pub fn tetrio() -> Self {
+        let kicks = |table: [(i32, i32); 5]| {
+            table
+                .into_iter()
+                .map(|(x, y)| Position { x, y: -y })
+                .collect()
+        };
+        Self {
+            table: vec![
+                (
+                    Orientation::Up,
+                    Orientation::Down,
+                    kicks([(0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]),
+                ),
+                (
+                    Orientation::Right,
+                    Orientation::Left,
+                    kicks([(1, 0), (1, 2), (1, 1), (0, 2), (0, 1)]),
+                ),
+                (
+                    Orientation::Down,
+                    Orientation::Up,
+                    kicks([(0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)]),
+                ),
+                (
+                    Orientation::Left,
+                    Orientation::Right,
+                    kicks([(-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)]),
+                ),
+            ],
+        }
+    }
+
+    /// Reads `none`, `tetrio`, or the path of a custom table.
+    pub fn from_arg(arg: &str) -> Result<Self> {
+        match arg {
+            "none" => Ok(Self::default()),
+            "tetrio" => Ok(Self::tetrio()),
+            path => fs::read_to_string(path)?.parse(),
+        }
+    }
+
+    /// Offsets to try in order when turning `tetromino` around. The first entry is
+    /// the unkicked rotation.
+    pub fn kicks(&self, tetromino: &Tetromino) -> Vec<Position> {
+        let to = tetromino.orientation.rotate_180();
+        let kicks = self
+            .table
+            .iter()
+            .find(|(from, into, _)| *from == tetromino.orientation && *into == to)
+            .map(|(_, _, kicks)| kicks);
+        std::iter::once(NO_KICK)
+            .chain(kicks.into_iter().flatten().copied())
+            .collect()
+    }
+}
+
+/// A custom table has a line per turn such as `up down 0,1 1,1 -1,1`, giving the
+/// orientations and then the kicks with y pointing up, as tables are published.
+/// Lines starting with `#` are comments.
+impl FromStr for Kicks180 {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let orientation = |word: &str| {
+            Orientation::iter()
+                .find(|or| format!("{:?}", or).eq_ignore_ascii_case(word))
+                .ok_or_else(|| anyhow!("unknown orientation {:?}", word))
+        };
+        let kick = |word: &str| -> Result<Position> {
+            let (x, y) = word
+                .split_once(',')
+                .ok_or_else(|| anyhow!("kick {:?} should look like x,y", word))?;
+            Ok(Position {
+                x: x.parse()?,
+                y: -y.parse::<i32>()?,
+            })
+        };
+
+        let mut table = vec![];
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            if words.len() < 2 {
+                return Err(anyhow!("180 kick line {:?} has no orientations", line));
+            }
+            let (from, to) = (orientation(words[0])?, orientation(words[1])?);
+            if from.rotate_180() != to {
+                return Err(anyhow!("{:?} to {:?} is not a 180 turn", from, to));
+            }
+            let kicks = words[2..]
+                .iter()
+                .map(|word| kick(word))
+                .collect::<Result<_>>()?;
+            table.push((from, to, kicks));
+        }
+        Ok(Self { table })
+    }
+}
+
+/// Flips a published kick table, where y points up, to board coordinates.
+pub fn y_down(lookup: PositionLookup) -> PositionLookup {
+    lookup
+        .into_iter()
+        .map(|(key, kicks)| {
+            let kicks = kicks
+                .into_iter()
+                .map(|kick| Position { y: -kick.y, ..kick });
+            (key, kicks.collect())
+        })
+        .collect()
+}
+
+fn cells(cells: &[(i32, i32)]) -> BlockOffsets {
+    cells
+        .iter()
+        .map(|(x, y)| Position { x: *x, y: *y })
+        .collect()
+}
+
+fn rotate_offset_90(pos: Position, box_size: u32) -> Position {
+    Position {
+        x: box_size as i32 - pos.y - 1,
+        y: pos.x,
+    }
+}
+
+fn rotate_offset(pos: Position, orientation: Orientation, box_size: u32) -> Position {
+    let rotate = move |arg| rotate_offset_90(arg, box_size);
+    match orientation {
+        Orientation::Up => pos,
+        Orientation::Right => rotate(pos),
+        Orientation::Down => rotate(rotate(pos)),
+        Orientation::Left => rotate(rotate(rotate(pos))),
+    }
+}
+
+/// Turns offsets within a square box of `box_size` from `Up` to `orientation`.
+pub fn rotate_offsets(
+    offsets: &[Position],
+    orientation: Orientation,
+    box_size: u32,
+) -> BlockOffsets {
+    offsets
+        .iter()
+        .map(|pos| rotate_offset(*pos, orientation, box_size))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::{rotate_offset_90, Kicks180, RotationSystemKind, SavedSystem};
+    use crate::{
+        board::Board,
+        positions::Position,
+        tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+    };
+
+    #[test]
+    fn test_rotate_90() {
+        assert_eq!(
+            rotate_offset_90(Position { x: 0, y: 0 }, 4),
+            Position { x: 3, y: 0 }
+        );
+        assert_eq!(
+            rotate_offset_90(Position { x: 1, y: 1 }, 4),
+            Position { x: 2, y: 1 }
+        );
+        assert_eq!(
+            rotate_offset_90(Position { x: 1, y: 1 }, 3),
+            Position { x: 1, y: 1 }
+        );
+        assert_eq!(
+            rotate_offset_90(Position { x: 1, y: 1 }, 2),
+            Position { x: 0, y: 1 }
+        );
+    }
+
+    #[test]
+    fn test_every_system_has_every_shape() {
+        for kind in [
+            RotationSystemKind::Srs,
+            RotationSystemKind::SrsPlus,
+            RotationSystemKind::Ars,
+            RotationSystemKind::Nes,
+        ] {
+            let system = kind.build();
+            assert!(matches!(system.save(), SavedSystem::Builtin(saved) if saved == kind));
+            for type_ in TetrominoType::iter() {
+                for or in Orientation::iter() {
+                    let mut offsets = system.offsets(type_, or).to_vec();
+                    offsets.sort_by_key(|pos| (pos.x, pos.y));
+                    offsets.dedup();
+                    assert_eq!(offsets.len(), 4, "{:?} {:?} {:?}", kind, type_, or);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_kicks_180() {
+        let t = Tetromino::new(0, Colour::Magenta, Orientation::Right, TetrominoType::T);
+        assert_eq!(Kicks180::default().kicks(&t), vec![Position { x: 0, y: 0 }]);
+        assert_eq!(
+            Kicks180::tetrio().kicks(&t)[..3],
+            [
+                Position { x: 0, y: 0 },
+                Position { x: 1, y: 0 },
+                Position { x: 1, y: -2 },
+            ]
+        );
+
+        let custom = "
+            # up only, one row up then one down
+            up down 0,1 0,-1
+            "
+        .parse::<Kicks180>()
+        .unwrap();
+        let up = Tetromino::new(0, Colour::Magenta, Orientation::Up, TetrominoType::T);
+        assert_eq!(
+            custom.kicks(&up),
+            vec![
+                Position { x: 0, y: 0 },
+                Position { x: 0, y: -1 },
+                Position { x: 0, y: 1 },
+            ]
+        );
+        assert_eq!(custom.kicks(&t).len(), 1);
+
+        assert!("up right 0,1".parse::<Kicks180>().is_err());
+        assert!("up down 0;1".parse::<Kicks180>().is_err());
+    }
+
+    #[test]
+    fn test_ars_centre_column_rule() {
+        let system = RotationSystemKind::Ars.build();
+        let t = Tetromino::new(0, Colour::Magenta, Orientation::Up, TetrominoType::T);
+
+        // A block above the centre of the box stops the kick entirely.
+        let centre = "
+            .G........
+            ..........
+            ..........
+            "
+        .parse::<Board>()
+        .unwrap();
+        assert_eq!(
+            system.kicks(&centre, &t, Orientation::Right),
+            vec![Position { x: 0, y: 0 }]
+        );
+
+        // One beside it still lets it try kicking sideways.
+        let corner = "
+            ..........
+            G.........
+            ..........
+            "
+        .parse::<Board>()
+        .unwrap();
+        assert_eq!(system.kicks(&corner, &t, Orientation::Right).len(), 4);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::game::Tetris;
+
+/// Bumped whenever a change to the engine types would stop older saves from loading.
+pub const SAVE_VERSION: u64 = 3;
+
+#[derive(Deserialize)]
+struct Header {
+    version: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct SaveRef<'a> {
+    version: u64,
+    game: &'a Tetris,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    game: Tetris,
+}
+
+pub fn save(game: &Tetris, path: &Path) -> Result<()> {
+    let contents = serde_json::to_string(&SaveRef {
+        version: SAVE_VERSION,
+        game,
+    })?;
+    fs::write(path, contents).with_context(|| format!("failed to write save to {:?}", path))
+}
+
+/// Loads the game saved at `path`, or `None` if there is no save.
+pub fn load(path: &Path) -> Result<Option<Tetris>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to read save {:?}", path)),
+    };
+    let header: Header = serde_json::from_str(&contents)
+        .with_context(|| format!("save {:?} is not a valid save file", path))?;
+
+    match header.version {
+        Some(SAVE_VERSION) => {}
+        Some(version) => {
+            return Err(anyhow!(
+                "save {:?} has format version {} but this build only reads version {}",
+                path,
+                version,
+                SAVE_VERSION
+            ))
+        }
+        None => return Err(anyhow!("save {:?} has no format version", path)),
+    }
+
+    let save: SaveFile = serde_json::from_str(&contents)
+        .with_context(|| format!("save {:?} could not be restored", path))?;
+    Ok(Some(save.game))
+}
+
+pub fn discard(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove save {:?}", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use clap::Parser;
+
+    use super::{load, save, SAVE_VERSION};
+    use crate::{game::Tetris, settings::Settings};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("tetris_save_{}_{}.json", name, process::id()))
+    }
+
+    fn assert_round_trip(args: &[&str], name: &str) {
+        let path = temp_path(name);
+        let settings = Settings::parse_from(std::iter::once("tetris").chain(args.iter().copied()));
+        let mut game = Tetris::new(&settings);
+        game.update();
+        game.move_tetromino_left();
+        game.drop_tetromino();
+        game.update();
+
+        save(&game, &path).unwrap();
+        let mut loaded = load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.ui_state(), game.ui_state());
+
+        for game in [&mut game, &mut loaded] {
+            game.drop_tetromino();
+            game.update();
+        }
+        assert_eq!(loaded.ui_state(), game.ui_state());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(&[], "round_trip");
+    }
+
+    #[test]
+    fn test_round_trip_piece_set() {
+        assert_round_trip(&["--pieces=pieces/pentominoes.toml"], "round_trip_pieces");
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        let path = temp_path("other_version");
+        fs::write(
+            &path,
+            format!("{{\"version\": {}, \"game\": {{}}}}", SAVE_VERSION + 1),
+        )
+        .unwrap();
+        let err = load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert!(err.to_string().contains("format version"));
+    }
+
+    #[test]
+    fn test_missing_save() {
+        let path = temp_path("missing");
+        assert!(load(&path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// How many high scores are kept.
+pub const KEPT: usize = 10;
+
+/// A finished marathon game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub lines: u32,
+    pub seconds: f64,
+}
+
+/// The high scores saved at `path`, best first. No file means no scores yet.
+pub fn load(path: &Path) -> Result<Vec<HighScore>> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .with_context(|| format!("high scores {:?} could not be read", path)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err).with_context(|| format!("failed to read high scores {:?}", path)),
+    }
+}
+
+/// Adds `score` to the high scores saved at `path` if it's good enough to be
+/// kept, and returns its place from 0.
+pub fn record(path: &Path, score: HighScore) -> Result<Option<usize>> {
+    let mut scores = load(path)?;
+    let place = insert(&mut scores, score);
+    if place.is_some() {
+        fs::write(path, serde_json::to_string_pretty(&scores)?)
+            .with_context(|| format!("failed to write high scores {:?}", path))?;
+    }
+    Ok(place)
+}
+
+/// Puts `score` in its place in `scores`, after any it ties with, and drops
+/// whichever score no longer makes the table.
+fn insert(scores: &mut Vec<HighScore>, score: HighScore) -> Option<usize> {
+    let place = scores
+        .iter()
+        .position(|kept| kept.score < score.score)
+        .unwrap_or(scores.len());
+    if place >= KEPT {
+        return None;
+    }
+    scores.insert(place, score);
+    scores.truncate(KEPT);
+    Some(place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert, HighScore, KEPT};
+
+    fn high_score(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score,
+            lines: score / 100,
+            seconds: 60.0,
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut scores = vec![];
+        assert_eq!(insert(&mut scores, high_score("a", 500)), Some(0));
+        assert_eq!(insert(&mut scores, high_score("b", 900)), Some(0));
+        assert_eq!(insert(&mut scores, high_score("c", 500)), Some(2));
+        let names = scores
+            .iter()
+            .map(|score| score.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "a", "c"]);
+
+        for score in 0..KEPT as u32 {
+            insert(&mut scores, high_score("d", 1000 + score));
+        }
+        assert_eq!(scores.len(), KEPT);
+        assert_eq!(insert(&mut scores, high_score("e", 100)), None);
+        assert_eq!(insert(&mut scores, high_score("f", 1005)), Some(5));
+        assert_eq!(scores.last().unwrap().score, 1001);
+    }
+}
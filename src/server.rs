@@ -0,0 +1,561 @@
+//! Hosts rooms for `tetris --mode=online`. Each client plays its own game; the
+//! server pairs up the players in a room and relays their attacks, board
+//! snapshots and round results.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        mpsc::{sync_channel, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::Result;
+use clap::Parser;
+use log::{info, warn};
+
+use protocol::{ClientMessage, Netcode, ServerMessage, DEFAULT_PORT};
+
+mod action;
+mod protocol;
+
+const PLAYERS: usize = 2;
+
+/// Messages waiting to be written to a client before it's dropped for not
+/// keeping up.
+const OUTBOX: usize = 256;
+
+#[derive(Parser, Debug, Clone)]
+#[clap(about = "Hosts online versus rooms for tetris")]
+struct Settings {
+    /// Port to listen on, on every interface
+    #[clap(short, long, value_parser, default_value_t = DEFAULT_PORT)]
+    port: u16,
+    /// Rounds a player needs to win a match
+    #[clap(long, value_parser, default_value = "3")]
+    first_to: u32,
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let settings = Settings::parse();
+    let listener = TcpListener::bind(("0.0.0.0", settings.port))?;
+    info!("listening on {}", listener.local_addr()?);
+    serve(listener, settings.first_to);
+    Ok(())
+}
+
+/// Handles each connection on its own thread, sharing the rooms between them.
+fn serve(listener: TcpListener, first_to: u32) {
+    let lobby = Arc::new(Mutex::new(Lobby {
+        first_to,
+        rooms: HashMap::new(),
+    }));
+    for (id, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => {
+                let lobby = lobby.clone();
+                thread::spawn(move || {
+                    if let Err(err) = connection(id, stream, &lobby) {
+                        warn!("connection {}: {}", id, err);
+                    }
+                });
+            }
+            Err(err) => warn!("could not accept a connection: {}", err),
+        }
+    }
+}
+
+/// Passes a client's messages to its room until it disconnects, then lets the
+/// room know it has gone.
+fn connection(id: usize, stream: TcpStream, lobby: &Mutex<Lobby>) -> Result<()> {
+    info!("connection {} from {}", id, stream.peer_addr()?);
+    let outbox = Outbox::new(&stream)?;
+    let mut joined: Option<String> = None;
+    let mut result = Ok(());
+    for message in protocol::messages::<ClientMessage>(stream.try_clone()?) {
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        };
+        let mut lobby = lobby.lock().unwrap();
+        match (message, &joined) {
+            (
+                ClientMessage::Join {
+                    room,
+                    name,
+                    netcode,
+                },
+                None,
+            ) => {
+                if lobby.join(&room, id, name, netcode, outbox.clone()) {
+                    joined = Some(room);
+                }
+            }
+            (message, Some(room)) => lobby.handle(room, id, message),
+            (_, None) => outbox.send(ServerMessage::Error {
+                message: "join a room first".to_string(),
+            }),
+        }
+    }
+    if let Some(room) = joined {
+        lobby.lock().unwrap().leave(&room, id);
+    }
+    info!("connection {} closed", id);
+    result
+}
+
+/// Queues messages for a client, written by a thread of its own so a client
+/// that stops reading holds up nobody else.
+#[derive(Clone)]
+struct Outbox {
+    sender: SyncSender<ServerMessage>,
+    stream: Arc<TcpStream>,
+}
+
+impl Outbox {
+    fn new(stream: &TcpStream) -> Result<Self> {
+        let (sender, messages) = sync_channel(OUTBOX);
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for message in messages {
+                if let Err(err) = protocol::send(&mut writer, &message) {
+                    warn!("could not send {:?}: {}", message, err);
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            sender,
+            stream: Arc::new(stream.try_clone()?),
+        })
+    }
+
+    /// Queues a message, leaving the reading side of the connection to notice
+    /// if the client has gone. A client too far behind is disconnected.
+    fn send(&self, message: ServerMessage) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(message) {
+            warn!("disconnecting a client that stopped reading");
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct Player {
+    id: usize,
+    name: String,
+    outbox: Outbox,
+    wins: u32,
+    ready: bool,
+}
+
+struct Room {
+    netcode: Netcode,
+    players: Vec<Player>,
+    round: u32,
+    /// Whether a round is under way. Attacks and top outs arriving between
+    /// rounds are from the round before and are dropped.
+    playing: bool,
+    /// Rollback netcode: frames this round whose checksums have matched.
+    verified: usize,
+    /// Rollback netcode: each player's checksums for the frames after
+    /// `verified`, waiting for the other's.
+    checksums: [VecDeque<u64>; PLAYERS],
+}
+
+impl Room {
+    fn new(netcode: Netcode) -> Self {
+        Self {
+            netcode,
+            players: vec![],
+            round: 0,
+            playing: false,
+            verified: 0,
+            checksums: Default::default(),
+        }
+    }
+
+    /// Takes player `seat`'s checksums for the frames from `frame` on, and
+    /// returns the first frame where they disagree with the other player's.
+    fn check(&mut self, seat: usize, frame: usize, hashes: Vec<u64>) -> Option<usize> {
+        if frame != self.verified + self.checksums[seat].len() {
+            warn!("checksums for frame {} out of order", frame);
+            return None;
+        }
+        self.checksums[seat].extend(hashes);
+        while let (Some(ours), Some(theirs)) =
+            (self.checksums[0].front(), self.checksums[1].front())
+        {
+            if ours != theirs {
+                return Some(self.verified);
+            }
+            self.checksums[0].pop_front();
+            self.checksums[1].pop_front();
+            self.verified += 1;
+        }
+        None
+    }
+
+    /// The index of player `id` and of their opponent.
+    fn seats(&self, id: usize) -> Option<(usize, usize)> {
+        let seat = self.players.iter().position(|player| player.id == id)?;
+        Some((seat, (seat + 1) % PLAYERS))
+    }
+}
+
+struct Lobby {
+    first_to: u32,
+    rooms: HashMap<String, Room>,
+}
+
+impl Lobby {
+    /// Seats a player in `room`, starting the match if that fills it. Returns
+    /// false if the room was already full.
+    fn join(
+        &mut self,
+        room_name: &str,
+        id: usize,
+        name: String,
+        netcode: Netcode,
+        outbox: Outbox,
+    ) -> bool {
+        let room = self
+            .rooms
+            .entry(room_name.to_string())
+            .or_insert_with(|| Room::new(netcode));
+        if room.netcode != netcode {
+            outbox.send(ServerMessage::Error {
+                message: format!("room {:?} uses {:?} netcode", room_name, room.netcode),
+            });
+            return false;
+        }
+        if room.players.len() == PLAYERS {
+            outbox.send(ServerMessage::Error {
+                message: format!("room {:?} is full", room_name),
+            });
+            return false;
+        }
+        info!("{} joined room {:?}", name, room_name);
+        room.players.push(Player {
+            id,
+            name,
+            outbox,
+            wins: 0,
+            ready: true,
+        });
+        if room.players.len() < PLAYERS {
+            room.players[0].outbox.send(ServerMessage::Waiting);
+        }
+        self.start_if_ready(room_name);
+        true
+    }
+
+    fn handle(&mut self, room_name: &str, id: usize, message: ClientMessage) {
+        let room = match self.rooms.get_mut(room_name) {
+            Some(room) => room,
+            None => return,
+        };
+        let (seat, other) = match room.seats(id) {
+            Some(seats) => seats,
+            None => return,
+        };
+        let opponent = room.players.get(other);
+        match message {
+            ClientMessage::Join { .. } => room.players[seat].outbox.send(ServerMessage::Error {
+                message: "already in a room".to_string(),
+            }),
+            ClientMessage::Ready => {
+                room.players[seat].ready = true;
+                self.start_if_ready(room_name);
+            }
+            ClientMessage::Attack { lines } => {
+                if let (true, Some(opponent)) = (room.playing, opponent) {
+                    opponent.outbox.send(ServerMessage::Attack { lines });
+                }
+            }
+            ClientMessage::Snapshot { snapshot } => {
+                if let Some(opponent) = opponent {
+                    opponent.outbox.send(ServerMessage::Opponent { snapshot });
+                }
+            }
+            ClientMessage::Inputs { frame, actions } => {
+                if let (true, Some(opponent)) = (room.playing, opponent) {
+                    opponent
+                        .outbox
+                        .send(ServerMessage::Inputs { frame, actions });
+                }
+            }
+            ClientMessage::Checksums { frame, hashes } => {
+                if !room.playing || opponent.is_none() {
+                    return;
+                }
+                if let Some(frame) = room.check(seat, frame, hashes) {
+                    warn!(
+                        "room {:?} round {} desynced at frame {}",
+                        room_name, room.round, frame
+                    );
+                    room.playing = false;
+                    for player in room.players.iter() {
+                        player.outbox.send(ServerMessage::Desync { frame });
+                    }
+                }
+            }
+            ClientMessage::ToppedOut => {
+                if room.playing && opponent.is_some() {
+                    room.playing = false;
+                    room.players[other].wins += 1;
+                    let match_over = room.players[other].wins >= self.first_to;
+                    info!(
+                        "room {:?} round {} to {}",
+                        room_name, room.round, room.players[other].name
+                    );
+                    for (seat, player) in room.players.iter().enumerate() {
+                        player.outbox.send(ServerMessage::RoundOver {
+                            won: seat == other,
+                            wins: player.wins,
+                            opponent_wins: room.players[(seat + 1) % PLAYERS].wins,
+                            match_over,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes player `id` out of the room, abandoning any match and closing the
+    /// room if nobody is left.
+    fn leave(&mut self, room_name: &str, id: usize) {
+        let room = match self.rooms.get_mut(room_name) {
+            Some(room) => room,
+            None => return,
+        };
+        room.players.retain(|player| player.id != id);
+        room.playing = false;
+        room.round = 0;
+        for player in room.players.iter_mut() {
+            player.wins = 0;
+            player.ready = true;
+            player.outbox.send(ServerMessage::OpponentLeft);
+        }
+        if room.players.is_empty() {
+            self.rooms.remove(room_name);
+        }
+    }
+
+    /// Starts the next round once both players are ready, or a new match if the
+    /// last one was won.
+    fn start_if_ready(&mut self, room_name: &str) {
+        let room = match self.rooms.get_mut(room_name) {
+            Some(room) => room,
+            None => return,
+        };
+        if room.playing
+            || room.players.len() < PLAYERS
+            || !room.players.iter().all(|player| player.ready)
+        {
+            return;
+        }
+        if room
+            .players
+            .iter()
+            .any(|player| player.wins >= self.first_to)
+        {
+            room.round = 0;
+            for player in room.players.iter_mut() {
+                player.wins = 0;
+            }
+        }
+        room.round += 1;
+        room.playing = true;
+        room.verified = 0;
+        room.checksums = Default::default();
+        let seed = rand::random();
+        info!("room {:?} round {}", room_name, room.round);
+        for seat in 0..PLAYERS {
+            room.players[seat].ready = false;
+            room.players[seat].outbox.send(ServerMessage::Start {
+                round: room.round,
+                first_to: self.first_to,
+                opponent: room.players[(seat + 1) % PLAYERS].name.clone(),
+                seat,
+                seed,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::{serve, OUTBOX};
+    use crate::{
+        action::Action,
+        protocol::{self, ClientMessage, Netcode, ServerMessage, Snapshot},
+    };
+
+    struct Client {
+        stream: TcpStream,
+        messages: Box<dyn Iterator<Item = ServerMessage>>,
+    }
+
+    impl Client {
+        fn join(port: u16, room: &str, name: &str, netcode: Netcode) -> Self {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let messages = protocol::messages(stream.try_clone().unwrap()).map(Result::unwrap);
+            let mut client = Self {
+                stream,
+                messages: Box::new(messages),
+            };
+            client.send(ClientMessage::Join {
+                room: room.to_string(),
+                name: name.to_string(),
+                netcode,
+            });
+            client
+        }
+
+        fn send(&mut self, message: ClientMessage) {
+            protocol::send(&mut self.stream, &message).unwrap();
+        }
+
+        fn next(&mut self) -> ServerMessage {
+            self.messages.next().unwrap()
+        }
+
+        /// Checks the next message starts round `round` against `opponent`, and
+        /// returns its seed.
+        fn started(&mut self, opponent: &str, round: u32) -> u64 {
+            match self.next() {
+                ServerMessage::Start {
+                    round: started,
+                    opponent: against,
+                    seed,
+                    ..
+                } if started == round && against == opponent => seed,
+                message => panic!("expected round {} to start, got {:?}", round, message),
+            }
+        }
+    }
+
+    fn server(first_to: u32) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || serve(listener, first_to));
+        port
+    }
+
+    #[test]
+    fn test_match() {
+        let port = server(1);
+        let mut alice = Client::join(port, "lan", "alice", Netcode::Relay);
+        assert_eq!(alice.next(), ServerMessage::Waiting);
+        let mut bob = Client::join(port, "lan", "bob", Netcode::Relay);
+        let seed = alice.started("bob", 1);
+        assert_eq!(bob.started("alice", 1), seed);
+
+        alice.send(ClientMessage::Attack { lines: 4 });
+        assert_eq!(bob.next(), ServerMessage::Attack { lines: 4 });
+        let snapshot = Snapshot {
+            board: "..\nGG".to_string(),
+            score: 100,
+            lines: 4,
+            garbage: 0,
+        };
+        bob.send(ClientMessage::Snapshot {
+            snapshot: snapshot.clone(),
+        });
+        assert_eq!(alice.next(), ServerMessage::Opponent { snapshot });
+
+        bob.send(ClientMessage::ToppedOut);
+        let over = |won, wins, opponent_wins| ServerMessage::RoundOver {
+            won,
+            wins,
+            opponent_wins,
+            match_over: true,
+        };
+        assert_eq!(alice.next(), over(true, 1, 0));
+        assert_eq!(bob.next(), over(false, 0, 1));
+
+        alice.send(ClientMessage::Ready);
+        bob.send(ClientMessage::Ready);
+        alice.started("bob", 1);
+
+        let mut carol = Client::join(port, "lan", "carol", Netcode::Relay);
+        assert!(matches!(carol.next(), ServerMessage::Error { .. }));
+        drop(bob);
+        assert_eq!(alice.next(), ServerMessage::OpponentLeft);
+    }
+
+    #[test]
+    fn test_rollback_inputs_and_checksums() {
+        let port = server(3);
+        let mut alice = Client::join(port, "lan", "alice", Netcode::Rollback);
+        alice.next();
+        let mut relay = Client::join(port, "lan", "carol", Netcode::Relay);
+        assert!(matches!(relay.next(), ServerMessage::Error { .. }));
+        let mut bob = Client::join(port, "lan", "bob", Netcode::Rollback);
+        alice.started("bob", 1);
+        bob.started("alice", 1);
+
+        let actions = vec![vec![], vec![Action::Drop]];
+        alice.send(ClientMessage::Inputs {
+            frame: 0,
+            actions: actions.clone(),
+        });
+        assert_eq!(bob.next(), ServerMessage::Inputs { frame: 0, actions });
+
+        alice.send(ClientMessage::Checksums {
+            frame: 0,
+            hashes: vec![1, 2, 3],
+        });
+        bob.send(ClientMessage::Checksums {
+            frame: 0,
+            hashes: vec![1, 2],
+        });
+        bob.send(ClientMessage::Checksums {
+            frame: 2,
+            hashes: vec![4],
+        });
+        assert_eq!(alice.next(), ServerMessage::Desync { frame: 2 });
+        assert_eq!(bob.next(), ServerMessage::Desync { frame: 2 });
+    }
+
+    #[test]
+    fn test_client_that_stops_reading() {
+        let port = server(3);
+        let mut alice = Client::join(port, "slow", "alice", Netcode::Relay);
+        alice.next();
+        let bob = Client::join(port, "slow", "bob", Netcode::Relay);
+        alice.started("bob", 1);
+
+        // Bob never reads, so the snapshots back up until he's dropped.
+        let snapshot = Snapshot {
+            board: ".".repeat(100_000),
+            score: 0,
+            lines: 0,
+            garbage: 0,
+        };
+        for _ in 0..OUTBOX * 2 {
+            alice.send(ClientMessage::Snapshot {
+                snapshot: snapshot.clone(),
+            });
+        }
+
+        let mut carol = Client::join(port, "other", "carol", Netcode::Relay);
+        assert_eq!(carol.next(), ServerMessage::Waiting);
+        let mut dave = Client::join(port, "other", "dave", Netcode::Relay);
+        carol.started("dave", 1);
+        dave.started("carol", 1);
+        assert_eq!(alice.next(), ServerMessage::OpponentLeft);
+        drop(bob);
+    }
+}
@@ -1,11 +1,221 @@
-use clap::Parser;
+use std::{ffi::OsString, iter, ops::RangeInclusive, path::PathBuf, str::FromStr};
 
-#[derive(Parser, Debug)]
+use anyhow::{ensure, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+
+use crate::{
+    attack::AttackTable,
+    bot::Difficulty,
+    config,
+    logging::LogFormat,
+    pieces::PieceSet,
+    polyominoes::MAX_SIZE,
+    protocol::{Netcode, DEFAULT_PORT},
+    rotation::{Kicks180, RotationSystemKind},
+    theme::{Glyph, Marks, Theme},
+    ui::RendererKind,
+};
+
+/// The shortest gravity interval, under which a tick would drop rows without end.
+const MIN_INTERVAL: f64 = 0.001;
+/// The longest interval or delay a setting can be, an hour.
+const MAX_SECONDS: f64 = 3600.0;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    Marathon,
+    FinesseTrainer,
+    /// Two players on one keyboard, sending each other garbage
+    Versus,
+    /// Versus against a computer player
+    VersusBot,
+    /// Versus against a player on another machine, through `tetrs-server`
+    Online,
+}
+
+/// Something to do other than play.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Watch a game broadcast with `--broadcast`, read-only
+    Watch {
+        /// Address the game is broadcast on
+        addr: String,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[clap(args_override_self = true)]
 pub struct Settings {
-    #[clap(short, long, value_parser, default_value = "1")]
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+    /// Seconds the falling piece takes to drop a row
+    #[clap(short, long, value_parser = interval, default_value = "1")]
     pub interval: f64,
     #[clap(short, long, value_parser, default_value = "10")]
     pub width: u32,
     #[clap(short, long, value_parser, default_value = "20")]
     pub height: u32,
+    #[clap(short, long, value_enum, default_value = "marathon")]
+    pub mode: Mode,
+    /// How pieces rotate and kick off walls and the floor
+    #[clap(short, long, value_enum, default_value = "srs")]
+    pub rotation_system: RotationSystemKind,
+    /// Kicks for 180 turns: none, tetrio, or the path of a custom table
+    #[clap(long = "kicks-180", value_parser = Kicks180::from_arg, default_value = "none")]
+    pub kicks_180: Kicks180,
+    /// Play with the pieces from a TOML piece set file, see `PieceSet`. The set
+    /// brings its own rotation, so it can't be given with --rotation-system
+    #[clap(long, value_parser = PieceSet::from_file, conflicts_with = "rotation-system")]
+    pub pieces: Option<PieceSet>,
+    /// Chaos mode: play with every polyomino of this many cells
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=MAX_SIZE as i64), conflicts_with = "pieces")]
+    pub polyominoes: Option<u8>,
+    /// Seconds cleared lines stay on the board before the rows above fall
+    #[clap(long, value_parser = seconds, default_value = "0")]
+    pub line_clear_delay: f64,
+    /// Entry delay (ARE): seconds between a piece locking and the next spawning
+    #[clap(long, alias = "are", value_parser = seconds, default_value = "0")]
+    pub spawn_delay: f64,
+    /// Garbage sent for each kind of clear, from a TOML file, see `AttackTable`.
+    /// Defaults to the guideline table
+    #[clap(long, value_parser = AttackTable::from_file)]
+    pub attack_table: Option<AttackTable>,
+    /// Seconds garbage waits in the queue before it can rise
+    #[clap(long, value_parser = seconds, default_value = "1")]
+    pub garbage_delay: f64,
+    /// Rounds a player needs to win a versus match
+    #[clap(long, value_parser, default_value = "3")]
+    pub first_to: u32,
+    /// Pieces per second the bot is held to in versus-bot mode
+    #[clap(long, value_parser = pieces_per_second, default_value = "1")]
+    pub bot_pps: f64,
+    #[clap(long, value_enum, default_value = "medium")]
+    pub bot_difficulty: Difficulty,
+    /// Host of the `tetrs-server` to play online through
+    #[clap(long, value_parser, default_value = "127.0.0.1")]
+    pub server: String,
+    #[clap(long, value_parser, default_value_t = DEFAULT_PORT)]
+    pub port: u16,
+    /// Room on the server to join. Its first two players play each other
+    #[clap(long, value_parser, default_value = "lobby")]
+    pub room: String,
+    #[clap(long, value_enum, default_value = "relay")]
+    pub netcode: Netcode,
+    /// Name shown to online opponents
+    #[clap(long, value_parser, default_value = "player")]
+    pub name: String,
+    /// Let spectators watch the game on this address, e.g. 0.0.0.0:7879
+    #[clap(long, value_parser)]
+    pub broadcast: Option<String>,
+    /// How the game is drawn. Defaults to ascii when NO_COLOR is set and tui
+    /// otherwise
+    #[clap(long, value_enum)]
+    pub renderer: Option<RendererKind>,
+    /// Colours and glyphs for the tui renderer: classic, brackets, blocks,
+    /// rounded, half-block, the colour-blind friendly deuteranopia,
+    /// protanopia and tritanopia, or the path of a theme file, see `Theme`
+    #[clap(long, value_parser = Theme::from_arg, default_value = "classic")]
+    pub theme: Theme,
+    /// Draw blocks with this glyph instead of the theme's
+    #[clap(long, value_enum)]
+    pub glyph: Option<Glyph>,
+    /// Mark each piece's blocks with its letter or a pattern, so pieces can be
+    /// told apart without colour. Defaults to the theme's marks
+    #[clap(long, value_enum)]
+    pub marks: Option<Marks>,
+    /// Play with the mouse too: hover over a column to move the piece there,
+    /// scroll to rotate, left click to drop and right click to hold
+    #[clap(long)]
+    pub mouse: bool,
+    /// Start playing straight away instead of showing the menu, as `--resume`
+    /// does too
+    #[clap(long)]
+    pub no_menu: bool,
+    /// Settings read before the command line's, which win over them. The
+    /// menu's settings editor writes here, see `config`
+    #[clap(long, value_parser, default_value = "tetris.toml")]
+    pub config: PathBuf,
+    /// Where the best marathon scores are kept
+    #[clap(long, value_parser, default_value = "tetris_scores.json")]
+    pub scores_file: PathBuf,
+    /// Where the game in progress is saved on quit
+    #[clap(long, value_parser, default_value = "tetris_save.json")]
+    pub save_file: PathBuf,
+    /// Where the panic and the last screen drawn are written if the game crashes
+    #[clap(long, value_parser, default_value = "tetris_crash.log")]
+    pub crash_file: PathBuf,
+    /// Resume the game saved by the last quit instead of starting a new one
+    #[clap(long)]
+    pub resume: bool,
+    /// Start from the field of a fumen string
+    #[clap(long, value_parser, conflicts_with = "field")]
+    pub board: Option<String>,
+    /// Start from a text field file, see `Field`
+    #[clap(long, value_parser)]
+    pub field: Option<PathBuf>,
+    /// Where the log is written
+    #[clap(long, value_parser, default_value = "log.txt")]
+    pub log_file: PathBuf,
+    /// Least severe messages logged: off, error, warn, info, debug or trace. Game
+    /// events are logged at any level
+    #[clap(long, value_parser = LevelFilter::from_str, default_value = "info")]
+    pub log_level: LevelFilter,
+    /// How log lines are written: text, timestamped lines, or json: one object
+    /// per line for other tools to read
+    #[clap(long, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+    /// Kilobytes the log grows to before it is rolled over, keeping three old logs
+    #[clap(long, value_parser, default_value = "10240")]
+    pub log_max_size: u64,
+    /// Append exported fumen strings to this file instead of printing them on exit
+    #[clap(long, value_parser)]
+    pub fumen_file: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Parses `args`, the command line, over the settings in its config file.
+    pub fn load(args: Vec<OsString>) -> Result<Self> {
+        let path = Self::parse_from(&args).config;
+        let from_config = config::args(&path)?;
+        let program = args.first().cloned().unwrap_or_else(|| "tetris".into());
+        Self::try_parse_from(
+            iter::once(program)
+                .chain(from_config.into_iter().map(OsString::from))
+                .chain(args.into_iter().skip(1)),
+        )
+        .with_context(|| format!("bad setting in config {:?}", path))
+    }
+}
+
+/// Parses a number in `range`, described as `what` when it isn't.
+fn in_range(arg: &str, range: RangeInclusive<f64>, what: &str) -> Result<f64> {
+    let value = arg.parse::<f64>()?;
+    ensure!(
+        range.contains(&value),
+        "{} is not {} from {} to {}",
+        arg,
+        what,
+        range.start(),
+        range.end()
+    );
+    Ok(value)
+}
+
+fn interval(arg: &str) -> Result<f64> {
+    in_range(arg, MIN_INTERVAL..=MAX_SECONDS, "a number of seconds")
+}
+
+fn seconds(arg: &str) -> Result<f64> {
+    in_range(arg, 0.0..=MAX_SECONDS, "a number of seconds")
+}
+
+/// Parses a rate, which the bot turns into an interval between pieces of a
+/// millisecond to 1000 seconds.
+fn pieces_per_second(arg: &str) -> Result<f64> {
+    in_range(
+        arg,
+        MIN_INTERVAL..=1.0 / MIN_INTERVAL,
+        "a number of pieces per second",
+    )
 }
@@ -0,0 +1,68 @@
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::Result;
+use log::{error, info};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM, SIGTSTP},
+    iterator::Signals,
+    low_level::emulate_default_handler,
+};
+
+use crate::ui::{restore_terminal, UI};
+
+/// Set once SIGINT or SIGTERM has asked the game to quit.
+static QUIT: AtomicBool = AtomicBool::new(false);
+
+/// The UI last shown, which SIGTSTP suspends while it's started.
+static SHOWN: Mutex<Option<Arc<Mutex<UI>>>> = Mutex::new(None);
+
+/// Whether a signal has asked the game to quit. The input loops and the menu
+/// then quit as if `q` was pressed, so the game is saved and the terminal put
+/// back.
+pub fn quit_requested() -> bool {
+    QUIT.load(Ordering::SeqCst)
+}
+
+/// Makes `ui` the one SIGTSTP suspends.
+pub fn show(ui: Arc<Mutex<UI>>) {
+    *SHOWN.lock().unwrap() = Some(ui);
+}
+
+/// Handles signals from now on. SIGINT and SIGTERM ask the game to quit, and a
+/// second one quits straight away in case it's stuck. SIGTSTP suspends the
+/// game until it's resumed.
+pub fn handle() -> Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGTSTP])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGTSTP => {
+                    info!("suspending");
+                    let shown = SHOWN.lock().unwrap().clone();
+                    let suspended = match shown {
+                        Some(ui) => ui.lock().unwrap().suspend(),
+                        None => emulate_default_handler(SIGTSTP).map_err(Into::into),
+                    };
+                    if let Err(err) = suspended {
+                        error!("could not suspend: {}", err);
+                    }
+                    info!("resumed");
+                }
+                _ if QUIT.swap(true, Ordering::SeqCst) => {
+                    error!("quitting at once on signal {}", signal);
+                    restore_terminal();
+                    process::exit(128 + signal);
+                }
+                _ => info!("quitting on signal {}", signal),
+            }
+        }
+    });
+    Ok(())
+}
@@ -1,68 +1,70 @@
-use rand::{
-    distributions::{Distribution, Standard},
-    rngs::StdRng,
-    thread_rng, Rng, SeedableRng,
-};
+use std::collections::VecDeque;
 
-use crate::tetromino::{Colour, Orientation, Tetromino, TetrominoType};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::tetromino::{Orientation, Tetromino, TetrominoType};
+
+/// Pieces picked ahead of time, so the next few can be shown.
+pub const NEXT_PIECES: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TetrominoSpawner {
-    rng: StdRng,
+    rng: ChaCha12Rng,
+    #[serde(default)]
+    queue: VecDeque<TetrominoType>,
+    /// The pieces spawned next, in order.
+    #[serde(default)]
+    next: VecDeque<Tetromino>,
 }
 
-impl Distribution<Colour> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Colour {
-        match rng.gen_range(0..=5) {
-            0 => Colour::Red,
-            1 => Colour::Green,
-            2 => Colour::Blue,
-            3 => Colour::Yellow,
-            4 => Colour::Cyan,
-            5 => Colour::Magenta,
-            _ => unreachable!(),
+impl TetrominoSpawner {
+    pub fn new() -> Self {
+        Self {
+            rng: ChaCha12Rng::from_rng(thread_rng()).unwrap(),
+            queue: VecDeque::new(),
+            next: VecDeque::new(),
         }
     }
-}
 
-impl Distribution<Orientation> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Orientation {
-        match rng.gen_range(0..=3) {
-            0 => Orientation::Up,
-            1 => Orientation::Right,
-            2 => Orientation::Down,
-            3 => Orientation::Left,
-            _ => unreachable!(),
+    /// A spawner that gives the same pieces and garbage holes as any other
+    /// with the same seed.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            queue: VecDeque::new(),
+            next: VecDeque::new(),
         }
     }
-}
 
-impl Distribution<TetrominoType> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TetrominoType {
-        match rng.gen_range(0..=6) {
-            0 => TetrominoType::I,
-            1 => TetrominoType::J,
-            2 => TetrominoType::L,
-            3 => TetrominoType::O,
-            4 => TetrominoType::S,
-            5 => TetrominoType::T,
-            6 => TetrominoType::Z,
-            _ => unreachable!(),
-        }
+    /// Queues pieces to be spawned, in order, before random ones.
+    pub fn enqueue(&mut self, types: impl IntoIterator<Item = TetrominoType>) {
+        self.queue.extend(types);
     }
-}
 
-impl TetrominoSpawner {
-    pub fn new() -> Self {
-        Self {
-            rng: StdRng::from_rng(thread_rng()).unwrap(),
+    /// A random column for the gap in a row of garbage.
+    pub fn garbage_hole(&mut self, width: u32) -> u32 {
+        self.rng.gen_range(0..width)
+    }
+
+    /// The next piece out of `pieces`, in the left corner. `TetrominoRules::spawn`
+    /// turns it and moves it to its spawn column.
+    pub fn spawn(&mut self, pieces: &[TetrominoType]) -> Tetromino {
+        while self.next.len() <= NEXT_PIECES {
+            let type_ = self
+                .queue
+                .pop_front()
+                .unwrap_or_else(|| pieces[self.rng.gen_range(0..pieces.len())]);
+            self.next
+                .push_back(Tetromino::new(0, type_.colour(), Orientation::Up, type_));
         }
+        self.next.pop_front().unwrap()
     }
 
-    pub fn spawn(&mut self) -> Tetromino {
-        let colour = self.rng.gen();
-        let type_ = self.rng.gen();
-        let orientation = self.rng.gen();
-        Tetromino::new(5, colour, type_, orientation)
+    /// The pieces that will spawn next, soonest first. Filled in by the first
+    /// spawn.
+    pub fn next(&self) -> impl Iterator<Item = &Tetromino> {
+        self.next.iter()
     }
 }
@@ -0,0 +1,125 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::{
+        mpsc::{sync_channel, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::{protocol, ui::UIState};
+
+/// Screens held for a spectator that is slow to read them. Past this, new
+/// screens are dropped for them rather than holding up the game.
+const BACKLOG: usize = 4;
+
+/// What spectators are shown: each game on screen with its title.
+pub type Screen = Vec<(UIState, String)>;
+
+/// Spectators watching this game over TCP. Each gets every screen published as
+/// a line of JSON, and can connect or hang up whenever they like.
+pub struct Spectators {
+    addr: SocketAddr,
+    senders: Arc<Mutex<Vec<SyncSender<Screen>>>>,
+}
+
+impl Spectators {
+    /// Listens for spectators on `addr`.
+    pub fn listen(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let senders = Arc::new(Mutex::new(vec![]));
+        thread::spawn({
+            let senders = senders.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("could not accept a spectator: {}", err);
+                            continue;
+                        }
+                    };
+                    info!("spectator joined from {:?}", stream.peer_addr());
+                    let (sender, screens) = sync_channel::<Screen>(BACKLOG);
+                    senders.lock().unwrap().push(sender);
+                    thread::spawn(move || {
+                        for screen in screens {
+                            if protocol::send(&mut stream, &screen).is_err() {
+                                break;
+                            }
+                        }
+                        info!("spectator left");
+                    });
+                }
+            }
+        });
+        Ok(Self { addr, senders })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Sends `screen` to every spectator, forgetting any that have gone.
+    pub fn publish(&self, screen: &[(UIState, String)]) {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.is_empty() {
+            return;
+        }
+        senders.retain(|sender| match sender.try_send(screen.to_vec()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread::sleep, time::Duration};
+
+    use super::{Screen, Spectators};
+    use crate::{protocol, ui::UIState};
+
+    fn screen(score: u32) -> Screen {
+        let state = UIState {
+            score,
+            ..UIState::empty(4, 4)
+        };
+        vec![(state, "TETRIS!".to_string())]
+    }
+
+    fn wait_for(spectators: &Spectators, count: usize) {
+        while spectators.senders.lock().unwrap().len() != count {
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_spectators_come_and_go() {
+        let spectators = Spectators::listen("127.0.0.1:0").unwrap();
+        let watch = || {
+            let stream = TcpStream::connect(spectators.addr()).unwrap();
+            protocol::messages::<Screen>(stream).map(Result::unwrap)
+        };
+        let mut alice = watch();
+        wait_for(&spectators, 1);
+        let bob = watch();
+        wait_for(&spectators, 2);
+
+        spectators.publish(&screen(10));
+        assert_eq!(alice.next().unwrap(), screen(10));
+        drop(bob);
+        // A spectator's thread only notices they've gone when a write fails.
+        let mut score = 0;
+        while spectators.senders.lock().unwrap().len() > 1 {
+            spectators.publish(&screen(score));
+            score += 1;
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(alice.next().unwrap(), screen(0));
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tetromino::TetrominoType;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub elapsed: Duration,
+    pub pieces: u32,
+    pub keys: u32,
+    pub lines: u32,
+    pub attack: u32,
+    pub finesse_faults: u32,
+    #[serde(with = "counts")]
+    pub piece_counts: HashMap<TetrominoType, u32>,
+}
+
+/// Saves piece counts as a list of pairs, since JSON keys must be strings and
+/// custom pieces aren't.
+mod counts {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::tetromino::TetrominoType;
+
+    pub fn serialize<S: Serializer>(
+        counts: &HashMap<TetrominoType, u32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        counts.iter().collect::<Vec<_>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<TetrominoType, u32>, D::Error> {
+        Ok(Vec::<(TetrominoType, u32)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_spawn(&mut self, type_: TetrominoType) {
+        *self.piece_counts.entry(type_).or_insert(0) += 1;
+    }
+
+    pub fn record_key(&mut self) {
+        self.keys += 1;
+    }
+
+    pub fn record_lock(&mut self, lines: u32, attack: u32) {
+        self.pieces += 1;
+        self.lines += lines;
+        self.attack += attack;
+    }
+
+    pub fn record_finesse_fault(&mut self) {
+        self.finesse_faults += 1;
+    }
+
+    /// The marathon level, which goes up every ten lines.
+    pub fn level(&self) -> u32 {
+        self.lines / 10 + 1
+    }
+
+    pub fn pps(&self) -> f64 {
+        Self::per(self.pieces as f64, self.elapsed.as_secs_f64())
+    }
+
+    pub fn kpp(&self) -> f64 {
+        Self::per(self.keys as f64, self.pieces as f64)
+    }
+
+    pub fn apm(&self) -> f64 {
+        Self::per(self.attack as f64, self.elapsed.as_secs_f64() / 60.0)
+    }
+
+    pub fn count(&self, type_: TetrominoType) -> u32 {
+        self.piece_counts.get(&type_).copied().unwrap_or(0)
+    }
+
+    fn per(value: f64, over: f64) -> f64 {
+        if over > 0.0 {
+            value / over
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Statistics;
+    use crate::tetromino::TetrominoType;
+
+    #[test]
+    fn test_rates() {
+        let mut stats = Statistics::new();
+        assert_eq!(stats.pps(), 0.0);
+        assert_eq!(stats.kpp(), 0.0);
+
+        stats.elapsed = Duration::from_secs(30);
+        for _ in 0..6 {
+            stats.record_key();
+        }
+        stats.record_lock(2, 1);
+        stats.record_lock(4, 4);
+
+        assert_eq!(stats.pps(), 2.0 / 30.0);
+        assert_eq!(stats.kpp(), 3.0);
+        assert_eq!(stats.apm(), 10.0);
+        assert_eq!(stats.lines, 6);
+        assert_eq!(stats.level(), 1);
+        stats.record_lock(4, 4);
+        assert_eq!(stats.level(), 2);
+    }
+
+    #[test]
+    fn test_piece_counts() {
+        let mut stats = Statistics::new();
+        stats.record_spawn(TetrominoType::T);
+        stats.record_spawn(TetrominoType::T);
+        stats.record_spawn(TetrominoType::I);
+
+        assert_eq!(stats.count(TetrominoType::T), 2);
+        assert_eq!(stats.count(TetrominoType::I), 1);
+        assert_eq!(stats.count(TetrominoType::O), 0);
+    }
+}
@@ -1,52 +1,73 @@
-use std::collections::HashMap;
-
-use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
-use strum::IntoEnumIterator;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    board::Board,
+    events::TSpin,
     positions::Position,
+    rotation::{self, Kicks180, RotationSystem, RotationSystemKind},
     tetromino::{Orientation, Tetromino, TetrominoType},
 };
 
-pub type Blocks = [Position; 4];
-type BlockOffsets = [Position; 4];
-type PositionLookup = HashMap<(Orientation, Orientation), Vec<Position>>;
-type OffsetsMap = HashMap<(TetrominoType, Orientation), BlockOffsets>;
+pub type Blocks = Vec<Position>;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TetrominoRules {
-    jltsz_kicks_lookup: PositionLookup,
-    i_kicks_lookup: PositionLookup,
-    offsets_lookup: OffsetsMap,
-    empty: Vec<Position>,
-    rng: StdRng,
+    #[serde(with = "rotation")]
+    system: Box<dyn RotationSystem>,
+    #[serde(default)]
+    kicks_180: Kicks180,
+    rng: ChaCha12Rng,
 }
 
 impl TetrominoRules {
-    pub fn new() -> Self {
+    pub fn new(system: RotationSystemKind) -> Self {
+        Self::from_system(system.build())
+    }
+
+    pub fn from_system(system: Box<dyn RotationSystem>) -> Self {
         Self {
-            jltsz_kicks_lookup: Self::build_jltsz_kicks_lookup(),
-            i_kicks_lookup: Self::build_i_kicks_lookup(),
-            offsets_lookup: Self::build_offsets_lookup(),
-            empty: vec![],
-            rng: StdRng::from_rng(thread_rng()).unwrap(),
+            system,
+            kicks_180: Kicks180::default(),
+            rng: ChaCha12Rng::from_rng(thread_rng()).unwrap(),
         }
     }
 
-    pub fn rotate(&self, tetromino: &Tetromino, new_orientation: Orientation) -> Vec<Tetromino> {
+    pub fn with_kicks_180(self, kicks_180: Kicks180) -> Self {
+        Self { kicks_180, ..self }
+    }
+
+    /// Picks switched pieces the same as any other rules with the same seed. The
+    /// spawner draws from the seed's first stream, so this takes the second.
+    pub fn seeded(self, seed: u64) -> Self {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        rng.set_stream(1);
+        Self { rng, ..self }
+    }
+
+    /// Rotates `tetromino` to `new_orientation` with the first kick that fits on
+    /// `board`, returning the rotated piece and the index of that kick (0 when it
+    /// didn't need one).
+    pub fn rotate(
+        &self,
+        board: &Board,
+        tetromino: &Tetromino,
+        new_orientation: Orientation,
+    ) -> Option<(Tetromino, usize)> {
         let base_rotation = Tetromino {
             orientation: new_orientation,
             ..tetromino.clone()
         };
-        let mut rotations = vec![base_rotation.clone()];
-        for kick in self.kicks(&base_rotation, new_orientation) {
-            let kick_rotation = Tetromino {
-                box_corner: &tetromino.box_corner + kick,
+        self.kicks(board, tetromino, new_orientation)
+            .into_iter()
+            .map(|kick| Tetromino {
+                box_corner: tetromino.box_corner + kick,
                 ..base_rotation.clone()
-            };
-            rotations.push(kick_rotation);
-        }
-        rotations
+            })
+            .enumerate()
+            .find(|(_, rotated)| board.valid_position(&self.blocks(rotated)))
+            .map(|(kick, rotated)| (rotated, kick))
     }
 
     pub fn move_down(&self, tetromino: &Tetromino) -> Tetromino {
@@ -71,301 +92,372 @@ impl TetrominoRules {
     }
 
     pub fn switch(&mut self, tetromino: &Tetromino) -> Tetromino {
+        let pieces = self.pieces();
+        let type_ = pieces[self.rng.gen_range(0..pieces.len())];
         Tetromino {
-            type_: self.rng.gen(),
-            colour: self.rng.gen(),
+            type_,
+            colour: type_.colour(),
             ..tetromino.clone()
         }
     }
 
-    pub fn blocks(&self, tetromino: &Tetromino) -> Blocks {
-        self.offsets(tetromino)
-            .map(|offset| tetromino.box_corner + offset)
+    /// The pieces that spawn in this game.
+    pub fn pieces(&self) -> Vec<TetrominoType> {
+        self.system.pieces()
     }
 
-    fn offsets(&self, tetromino: &Tetromino) -> &BlockOffsets {
-        self.offsets_lookup
-            .get(&(tetromino.type_, tetromino.orientation))
-            .unwrap()
+    /// Turns `tetromino` to its spawn orientation and moves it to the middle columns
+    /// of a board `width` wide, rounding left like the guideline spawn.
+    pub fn spawn(&self, tetromino: &Tetromino, width: u32) -> Tetromino {
+        let orientation = self.system.spawn_orientation(tetromino.type_);
+        let offsets = self.system.offsets(tetromino.type_, orientation);
+        let left = offsets.iter().map(|pos| pos.x).min().unwrap_or(0);
+        let right = offsets.iter().map(|pos| pos.x).max().unwrap_or(0);
+        let column = (width as i32 - (right - left + 1)) / 2;
+        Tetromino {
+            box_corner: Position {
+                x: column - left,
+                ..tetromino.box_corner
+            },
+            orientation,
+            ..tetromino.clone()
+        }
     }
 
-    fn box_size(type_: TetrominoType) -> u32 {
-        match type_ {
-            TetrominoType::I => 4,
-            TetrominoType::O => 2,
-            _ => 3,
-        }
+    pub fn blocks(&self, tetromino: &Tetromino) -> Blocks {
+        self.system
+            .offsets(tetromino.type_, tetromino.orientation)
+            .iter()
+            .map(|offset| tetromino.box_corner + *offset)
+            .collect()
     }
 
-    fn kicks(&self, tetromino: &Tetromino, new_orientation: Orientation) -> &Vec<Position> {
-        match tetromino.type_ {
-            TetrominoType::I => self
-                .i_kicks_lookup
-                .get(&(tetromino.orientation, new_orientation))
-                .unwrap_or(&self.empty),
-            TetrominoType::O => &self.empty,
-            _ => self
-                .jltsz_kicks_lookup
-                .get(&(tetromino.orientation, new_orientation))
-                .unwrap_or(&self.empty),
+    /// Whether a T that just turned into place with kick number `kick` made a
+    /// T-spin, by the three corner rule: three of the four cells diagonal to its
+    /// centre must be filled or outside the board. It is a mini unless both corners
+    /// on the side the T points to are blocked, or the turn used the last SRS kick.
+    pub fn t_spin(&self, board: &Board, tetromino: &Tetromino, kick: usize) -> TSpin {
+        const LAST_SRS_KICK: usize = 4;
+        if tetromino.type_ != TetrominoType::T {
+            return TSpin::None;
+        }
+        let blocks = self.blocks(tetromino);
+        let (centre, nose) = match Self::t_centre(&blocks) {
+            Some(found) => found,
+            None => return TSpin::None,
+        };
+        let (side_x, side_y) = (nose.y.abs(), nose.x.abs());
+        let corner = |x: i32, y: i32| board.blocked(&(centre + Position { x, y })) as u32;
+        let front =
+            corner(nose.x + side_x, nose.y + side_y) + corner(nose.x - side_x, nose.y - side_y);
+        let behind =
+            corner(-nose.x + side_x, -nose.y + side_y) + corner(-nose.x - side_x, -nose.y - side_y);
+        match (front, front + behind) {
+            (_, 0..=2) => TSpin::None,
+            (2, _) => TSpin::Full,
+            _ if kick == LAST_SRS_KICK => TSpin::Full,
+            _ => TSpin::Mini,
         }
     }
 
-    fn build_jltsz_kicks_lookup() -> PositionLookup {
-        let jltsz_0_1_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: -1, y: 1 },
-            Position { x: 0, y: -2 },
-            Position { x: -1, y: -2 },
-        ];
-        let jltsz_1_0_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: 1, y: -1 },
-            Position { x: 0, y: -2 },
-            Position { x: 1, y: 2 },
-        ];
-        let jltsz_1_2_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: 1, y: -1 },
-            Position { x: 0, y: 2 },
-            Position { x: 1, y: 2 },
-        ];
-        let jltsz_2_1_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: -1, y: 1 },
-            Position { x: 0, y: -2 },
-            Position { x: -1, y: -2 },
-        ];
-        let jltsz_2_3_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: 1, y: 1 },
-            Position { x: 0, y: -2 },
-            Position { x: 1, y: -2 },
-        ];
-        let jltsz_3_2_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: -1, y: -1 },
-            Position { x: 0, y: -2 },
-            Position { x: -1, y: 2 },
-        ];
-        let jltsz_3_0_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: -1, y: -1 },
-            Position { x: 0, y: 2 },
-            Position { x: -1, y: 2 },
-        ];
-        let jltsz_0_3_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: 1, y: 1 },
-            Position { x: 0, y: -2 },
-            Position { x: 1, y: -2 },
-        ];
-        HashMap::from([
-            ((Orientation::Up, Orientation::Right), jltsz_0_1_kicks),
-            ((Orientation::Right, Orientation::Up), jltsz_1_0_kicks),
-            ((Orientation::Right, Orientation::Down), jltsz_1_2_kicks),
-            ((Orientation::Down, Orientation::Right), jltsz_2_1_kicks),
-            ((Orientation::Down, Orientation::Left), jltsz_2_3_kicks),
-            ((Orientation::Left, Orientation::Down), jltsz_3_2_kicks),
-            ((Orientation::Left, Orientation::Up), jltsz_3_0_kicks),
-            ((Orientation::Up, Orientation::Left), jltsz_0_3_kicks),
-        ])
+    /// The block of a T with three neighbours, and the direction of the one
+    /// neighbour with nothing opposite it, which is where the T points.
+    fn t_centre(blocks: &[Position]) -> Option<(Position, Position)> {
+        let steps = [(-1, 0), (1, 0), (0, -1), (0, 1)].map(|(x, y)| Position { x, y });
+        blocks.iter().find_map(|centre| {
+            let arms = steps
+                .iter()
+                .filter(|step| blocks.contains(&(*centre + **step)))
+                .collect::<Vec<_>>();
+            let nose = arms.iter().find(|step| {
+                !blocks.contains(&Position {
+                    x: centre.x - step.x,
+                    y: centre.y - step.y,
+                })
+            })?;
+            (arms.len() == 3).then_some((*centre, **nose))
+        })
     }
 
-    fn build_i_kicks_lookup() -> PositionLookup {
-        let i_0_1_kicks = vec![
-            Position { x: -2, y: 0 },
-            Position { x: 1, y: 0 },
-            Position { x: -2, y: -1 },
-            Position { x: 1, y: 2 },
-        ];
-        let i_1_0_kicks = vec![
-            Position { x: 2, y: 0 },
-            Position { x: -1, y: 0 },
-            Position { x: 2, y: 1 },
-            Position { x: -1, y: -2 },
-        ];
-        let i_1_2_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: 2, y: 0 },
-            Position { x: -1, y: 2 },
-            Position { x: 2, y: -1 },
-        ];
-        let i_2_1_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: -2, y: 0 },
-            Position { x: 1, y: -2 },
-            Position { x: -2, y: 1 },
-        ];
-        let i_2_3_kicks = vec![
-            Position { x: 2, y: 0 },
-            Position { x: -1, y: 0 },
-            Position { x: 2, y: 1 },
-            Position { x: -1, y: -2 },
-        ];
-        let i_3_2_kicks = vec![
-            Position { x: -2, y: 0 },
-            Position { x: 1, y: 0 },
-            Position { x: -2, y: -1 },
-            Position { x: 1, y: 2 },
-        ];
-        let i_3_0_kicks = vec![
-            Position { x: 1, y: 0 },
-            Position { x: -2, y: 0 },
-            Position { x: 1, y: -2 },
-            Position { x: -2, y: 1 },
-        ];
-        let i_0_3_kicks = vec![
-            Position { x: -1, y: 0 },
-            Position { x: 2, y: 0 },
-            Position { x: -1, y: 2 },
-            Position { x: 2, y: -1 },
-        ];
-        HashMap::from([
-            ((Orientation::Up, Orientation::Right), i_0_1_kicks),
-            ((Orientation::Right, Orientation::Up), i_1_0_kicks),
-            ((Orientation::Right, Orientation::Down), i_1_2_kicks),
-            ((Orientation::Down, Orientation::Right), i_2_1_kicks),
-            ((Orientation::Down, Orientation::Left), i_2_3_kicks),
-            ((Orientation::Left, Orientation::Down), i_3_2_kicks),
-            ((Orientation::Left, Orientation::Up), i_3_0_kicks),
-            ((Orientation::Up, Orientation::Left), i_0_3_kicks),
-        ])
+    fn kicks(&self, board: &Board, tetromino: &Tetromino, to: Orientation) -> Vec<Position> {
+        if to == tetromino.orientation.rotate_180() {
+            self.kicks_180.kicks(tetromino)
+        } else {
+            self.system.kicks(board, tetromino, to)
+        }
     }
+}
 
-    fn rotate_offset_90(pos: Position, box_size: u32) -> Position {
-        Position {
-            x: box_size as i32 - pos.y - 1,
-            y: pos.x,
-        }
+#[cfg(test)]
+mod tests {
+    use super::TetrominoRules;
+    use crate::{
+        board::Board,
+        events::TSpin,
+        positions::Position,
+        rotation::{Kicks180, RotationSystemKind},
+        tetromino::{Colour, Orientation, Tetromino, TetrominoType},
+    };
+
+    /// Rotates a piece with its box corner at `(x, y)` on `board` under SRS, returning
+    /// the new box corner and the kick index used.
+    fn rotate(
+        board: &str,
+        type_: TetrominoType,
+        from: Orientation,
+        (x, y): (i32, i32),
+        to: Orientation,
+    ) -> Option<((i32, i32), usize)> {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let board = board.parse::<Board>().unwrap();
+        let tet = Tetromino {
+            box_corner: Position { x, y },
+            ..Tetromino::new(0, Colour::Red, from, type_)
+        };
+        assert!(board.valid_position(&rules.blocks(&tet)));
+        rules.rotate(&board, &tet, to).map(|(rotated, kick)| {
+            assert_eq!(rotated.orientation, to);
+            ((rotated.box_corner.x, rotated.box_corner.y), kick)
+        })
     }
 
-    fn rotate_offset(pos: Position, orientation: Orientation, box_size: u32) -> Position {
-        let rotate = move |arg| Self::rotate_offset_90(arg, box_size);
-        match orientation {
-            Orientation::Up => pos,
-            Orientation::Right => rotate(pos),
-            Orientation::Down => rotate(rotate(pos)),
-            Orientation::Left => rotate(rotate(rotate(pos))),
-        }
+    #[test]
+    fn test_tst_kick() {
+        // The T is tucked under the overhang and drops two rows into the slot.
+        let board = "
+            ..........
+            ..........
+            GGG.......
+            GG........
+            GG.GGGGGGG
+            GG..GGGGGG
+            GG.GGGGGGG
+            ";
+        assert_eq!(
+            rotate(
+                board,
+                TetrominoType::T,
+                Orientation::Up,
+                (2, 2),
+                Orientation::Right
+            ),
+            Some(((1, 4), 4))
+        );
     }
 
-    fn rotate_offsets(
-        offsets: &BlockOffsets,
-        orientation: Orientation,
-        box_size: u32,
-    ) -> BlockOffsets {
-        offsets
-            .iter()
-            .map(|pos| Self::rotate_offset(*pos, orientation, box_size))
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap()
+    #[test]
+    fn test_tst_kick_mirrored() {
+        let board = "
+            ..........
+            ..........
+            .......GGG
+            ........GG
+            GGGGGGG.GG
+            GGGGGG..GG
+            GGGGGGG.GG
+            ";
+        assert_eq!(
+            rotate(
+                board,
+                TetrominoType::T,
+                Orientation::Up,
+                (5, 2),
+                Orientation::Left
+            ),
+            Some(((6, 4), 4))
+        );
     }
 
-    fn build_offsets_lookup() -> HashMap<(TetrominoType, Orientation), BlockOffsets> {
-        let orientations = HashMap::from([
-            (
+    #[test]
+    fn test_i_wall_kicks() {
+        let board = "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ";
+        // Upright against the left wall, laid flat two columns out.
+        assert_eq!(
+            rotate(
+                board,
                 TetrominoType::I,
-                [
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 2, y: 1 },
-                    Position { x: 3, y: 1 },
-                ],
-            ),
-            (
-                TetrominoType::J,
-                [
-                    Position { x: 0, y: 0 },
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 2, y: 1 },
-                ],
-            ),
-            (
-                TetrominoType::L,
-                [
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 2, y: 1 },
-                    Position { x: 2, y: 0 },
-                ],
-            ),
-            (
-                TetrominoType::O,
-                [
-                    Position { x: 0, y: 0 },
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 0 },
-                    Position { x: 1, y: 1 },
-                ],
-            ),
-            (
-                TetrominoType::S,
-                [
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 1, y: 0 },
-                    Position { x: 2, y: 0 },
-                ],
-            ),
-            (
-                TetrominoType::T,
-                [
-                    Position { x: 0, y: 1 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 1, y: 0 },
-                    Position { x: 2, y: 1 },
-                ],
+                Orientation::Right,
+                (-2, 1),
+                Orientation::Down
             ),
-            (
-                TetrominoType::Z,
-                [
-                    Position { x: 0, y: 0 },
-                    Position { x: 1, y: 0 },
-                    Position { x: 1, y: 1 },
-                    Position { x: 2, y: 1 },
-                ],
+            Some(((0, 1), 2))
+        );
+        // Upright against the right wall, laid flat two columns in.
+        assert_eq!(
+            rotate(
+                board,
+                TetrominoType::I,
+                Orientation::Left,
+                (8, 1),
+                Orientation::Up
             ),
-        ]);
-
-        TetrominoType::iter()
-            .flat_map(|type_| {
-                let offsets = orientations.get(&type_).unwrap();
-                Orientation::iter().map(move |or| {
-                    (
-                        (type_, or),
-                        Self::rotate_offsets(offsets, or, Self::box_size(type_)),
-                    )
-                })
-            })
-            .collect()
+            Some(((6, 1), 2))
+        );
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::TetrominoRules;
-    use crate::positions::Position;
 
     #[test]
-    fn test_rotate_90() {
+    fn test_i_floor_kick() {
+        // Flat on the floor, the I can only stand up by moving up two rows.
+        let board = "
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ..........
+            ";
         assert_eq!(
-            TetrominoRules::rotate_offset_90(Position { x: 0, y: 0 }, 4),
-            Position { x: 0, y: 3 }
+            rotate(
+                board,
+                TetrominoType::I,
+                Orientation::Up,
+                (3, 4),
+                Orientation::Right
+            ),
+            Some(((4, 2), 4))
         );
+    }
+
+    #[test]
+    fn test_s_spin_in() {
+        let board = "
+            ..........
+            ..........
+            .....G....
+            GGGG...GGG
+            GGGG..GGGG
+            GGGGGGGG.G
+            ";
         assert_eq!(
-            TetrominoRules::rotate_offset_90(Position { x: 1, y: 1 }, 4),
-            Position { x: 1, y: 2 }
+            rotate(
+                board,
+                TetrominoType::S,
+                Orientation::Right,
+                (3, 2),
+                Orientation::Up
+            ),
+            Some(((4, 3), 2))
         );
+    }
+
+    #[test]
+    fn test_z_spin_in() {
+        let board = "
+            ..........
+            ..........
+            ....G.....
+            GGG...GGGG
+            GGGG..GGGG
+            G.GGGGGGGG
+            ";
         assert_eq!(
-            TetrominoRules::rotate_offset_90(Position { x: 1, y: 1 }, 3),
-            Position { x: 1, y: 1 }
+            rotate(
+                board,
+                TetrominoType::Z,
+                Orientation::Left,
+                (4, 2),
+                Orientation::Up
+            ),
+            Some(((3, 3), 2))
         );
+    }
+
+    #[test]
+    fn test_blocked_rotation_fails() {
+        let board = "
+            ..........
+            G.G.......
+            G.G.......
+            G.G.......
+            G.G.......
+            ";
         assert_eq!(
-            TetrominoRules::rotate_offset_90(Position { x: 1, y: 1 }, 2),
-            Position { x: 1, y: 0 }
+            rotate(
+                board,
+                TetrominoType::I,
+                Orientation::Right,
+                (-1, 1),
+                Orientation::Down
+            ),
+            None
         );
     }
+
+    #[test]
+    fn test_180_floor_kick() {
+        let board = "
+            ..........
+            ..........
+            ..........
+            ..........
+            "
+        .parse::<Board>()
+        .unwrap();
+        let tet = Tetromino {
+            box_corner: Position { x: 3, y: 2 },
+            ..Tetromino::new(0, Colour::Magenta, Orientation::Up, TetrominoType::T)
+        };
+
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        assert!(rules.rotate(&board, &tet, Orientation::Down).is_none());
+
+        let rules = rules.with_kicks_180(Kicks180::tetrio());
+        let (rotated, kick) = rules.rotate(&board, &tet, Orientation::Down).unwrap();
+        assert_eq!(rotated.box_corner, Position { x: 3, y: 1 });
+        assert_eq!(kick, 1);
+    }
+
+    #[test]
+    fn test_spawn_columns() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let columns = |type_| {
+            let tet = Tetromino::new(0, Colour::Red, Orientation::Up, type_);
+            let mut columns = rules
+                .blocks(&rules.spawn(&tet, 10))
+                .iter()
+                .map(|pos| pos.x)
+                .collect::<Vec<_>>();
+            columns.sort();
+            columns.dedup();
+            columns
+        };
+
+        assert_eq!(columns(TetrominoType::I), vec![3, 4, 5, 6]);
+        assert_eq!(columns(TetrominoType::O), vec![4, 5]);
+        for type_ in [
+            TetrominoType::J,
+            TetrominoType::L,
+            TetrominoType::S,
+            TetrominoType::T,
+            TetrominoType::Z,
+        ] {
+            assert_eq!(columns(type_), vec![3, 4, 5]);
+        }
+    }
+
+    #[test]
+    fn test_t_spin_corners() {
+        let rules = TetrominoRules::new(RotationSystemKind::Srs);
+        let board = "....\nG...\n...G\nG.GG".parse::<Board>().unwrap();
+        let t = |orientation| Tetromino {
+            box_corner: Position { x: 0, y: 1 },
+            ..Tetromino::new(0, Colour::Magenta, orientation, TetrominoType::T)
+        };
+
+        // Pointing down into the slot, both front corners are filled.
+        assert_eq!(rules.t_spin(&board, &t(Orientation::Down), 1), TSpin::Full);
+        // Pointing up, only one front corner is, unless the last kick got it there.
+        assert_eq!(rules.t_spin(&board, &t(Orientation::Up), 1), TSpin::Mini);
+        assert_eq!(rules.t_spin(&board, &t(Orientation::Up), 4), TSpin::Full);
+
+        let open = "....\n....\n....\n....".parse::<Board>().unwrap();
+        assert_eq!(rules.t_spin(&open, &t(Orientation::Up), 0), TSpin::None);
+    }
 }
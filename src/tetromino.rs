@@ -1,8 +1,9 @@
-use strum::EnumIter;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
 
 use crate::positions::Position;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter, Serialize, Deserialize)]
 pub enum Colour {
     Red,
     Green,
@@ -10,9 +11,27 @@ pub enum Colour {
     Yellow,
     Cyan,
     Magenta,
+    Orange,
+    Grey,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter)]
+impl Colour {
+    /// The piece whose guideline colour this is. Grey is used for garbage.
+    pub fn tetromino_type(&self) -> Option<TetrominoType> {
+        match self {
+            Colour::Cyan => Some(TetrominoType::I),
+            Colour::Blue => Some(TetrominoType::J),
+            Colour::Orange => Some(TetrominoType::L),
+            Colour::Yellow => Some(TetrominoType::O),
+            Colour::Green => Some(TetrominoType::S),
+            Colour::Magenta => Some(TetrominoType::T),
+            Colour::Red => Some(TetrominoType::Z),
+            Colour::Grey => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter, Serialize, Deserialize)]
 pub enum TetrominoType {
     I,
     J,
@@ -21,9 +40,58 @@ pub enum TetrominoType {
     S,
     T,
     Z,
+    /// A piece from a piece set, named by its letter
+    #[strum(disabled)]
+    Custom(char),
+    /// A generated polyomino, by its index in `Polyominoes`
+    #[strum(disabled)]
+    Polyomino(u8),
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter)]
+/// Letters for generated polyominoes. There are enough for every hexomino;
+/// heptominoes past the end show as `?`.
+const POLYOMINO_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+impl TetrominoType {
+    pub fn letter(&self) -> char {
+        match self {
+            TetrominoType::I => 'I',
+            TetrominoType::J => 'J',
+            TetrominoType::L => 'L',
+            TetrominoType::O => 'O',
+            TetrominoType::S => 'S',
+            TetrominoType::T => 'T',
+            TetrominoType::Z => 'Z',
+            TetrominoType::Custom(letter) => *letter,
+            TetrominoType::Polyomino(index) => POLYOMINO_LETTERS
+                .get(*index as usize)
+                .map_or('?', |letter| *letter as char),
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::iter().find(|type_| type_.letter() == letter)
+    }
+
+    pub fn colour(&self) -> Colour {
+        match self {
+            TetrominoType::I => Colour::Cyan,
+            TetrominoType::J => Colour::Blue,
+            TetrominoType::L => Colour::Orange,
+            TetrominoType::O => Colour::Yellow,
+            TetrominoType::S => Colour::Green,
+            TetrominoType::T => Colour::Magenta,
+            TetrominoType::Z => Colour::Red,
+            TetrominoType::Custom(_) => Colour::Grey,
+            TetrominoType::Polyomino(index) => Colour::iter()
+                .filter(|colour| *colour != Colour::Grey)
+                .nth(*index as usize % 7)
+                .unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, EnumIter, Serialize, Deserialize)]
 pub enum Orientation {
     Up,
     Right,
@@ -49,9 +117,13 @@ impl Orientation {
             Orientation::Left => Orientation::Down,
         }
     }
+
+    pub fn rotate_180(&self) -> Orientation {
+        self.rotate_clockwise().rotate_clockwise()
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Tetromino {
     pub orientation: Orientation,
     pub colour: Colour,
@@ -0,0 +1,338 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use tui::style::Color;
+
+use crate::tetromino::{Colour, TetrominoType};
+
+/// Themes that come with the game, by name.
+const BUILT_IN: &[(&str, &str)] = &[
+    ("brackets", include_str!("../themes/brackets.toml")),
+    ("blocks", include_str!("../themes/blocks.toml")),
+    ("rounded", include_str!("../themes/rounded.toml")),
+    ("half-block", include_str!("../themes/half-block.toml")),
+    ("deuteranopia", include_str!("../themes/deuteranopia.toml")),
+    ("protanopia", include_str!("../themes/protanopia.toml")),
+    ("tritanopia", include_str!("../themes/tritanopia.toml")),
+];
+
+/// How blocks are drawn in each cell.
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Glyph {
+    /// A solid background
+    Solid,
+    /// `[]` in the block's colour
+    Brackets,
+    /// `██` in the block's colour
+    FullBlock,
+    /// `◖◗` in the block's colour
+    Rounded,
+    /// Half a character high, with two rows of cells to each line of text
+    HalfBlock,
+}
+
+impl Glyph {
+    /// The two characters drawn across each cell, or none for a solid
+    /// background.
+    pub fn text(&self) -> Option<&'static str> {
+        match self {
+            Glyph::Solid | Glyph::HalfBlock => None,
+            Glyph::Brackets => Some("[]"),
+            Glyph::FullBlock => Some("██"),
+            Glyph::Rounded => Some("◖◗"),
+        }
+    }
+}
+
+/// Marks that tell pieces apart without relying on colour.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Marks {
+    /// Pieces are only told apart by colour
+    #[default]
+    None,
+    /// Each cell of a piece shows its letter
+    Letters,
+    /// Each tetromino has its own shading pattern, and other pieces their letter
+    Patterns,
+}
+
+impl Marks {
+    /// The two characters drawn across each cell of `piece`, if it's marked.
+    pub fn text(&self, piece: TetrominoType) -> Option<[char; 2]> {
+        let letter = [piece.letter(), ' '];
+        match self {
+            Marks::None => None,
+            Marks::Letters => Some(letter),
+            Marks::Patterns => Some(match piece {
+                TetrominoType::I => ['▓', '▓'],
+                TetrominoType::O => ['░', '░'],
+                TetrominoType::T => ['▒', '▒'],
+                TetrominoType::S => ['╱', '╱'],
+                TetrominoType::Z => ['╲', '╲'],
+                TetrominoType::J => ['▚', '▚'],
+                TetrominoType::L => ['▞', '▞'],
+                TetrominoType::Custom(_) | TetrominoType::Polyomino(_) => letter,
+            }),
+        }
+    }
+}
+
+/// A colour in a theme file: a name such as `red` or `lightred`, a number from
+/// the 256 colour palette, or `#rrggbb` for truecolor terminals.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ThemeColour(pub Color);
+
+impl TryFrom<String> for ThemeColour {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        let named = match text.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "reset" => Some(Color::Reset),
+            "black" => Some(Color::Black),
+            "red" => Some(Color::Red),
+            "green" => Some(Color::Green),
+            "yellow" => Some(Color::Yellow),
+            "blue" => Some(Color::Blue),
+            "magenta" => Some(Color::Magenta),
+            "cyan" => Some(Color::Cyan),
+            "gray" | "grey" => Some(Color::Gray),
+            "darkgray" | "darkgrey" => Some(Color::DarkGray),
+            "lightred" => Some(Color::LightRed),
+            "lightgreen" => Some(Color::LightGreen),
+            "lightyellow" => Some(Color::LightYellow),
+            "lightblue" => Some(Color::LightBlue),
+            "lightmagenta" => Some(Color::LightMagenta),
+            "lightcyan" => Some(Color::LightCyan),
+            "white" => Some(Color::White),
+            _ => None,
+        };
+        if let Some(colour) = named {
+            return Ok(Self(colour));
+        }
+        if let Some(hex) = text.strip_prefix('#') {
+            if hex.len() == 6 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16);
+                if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+                    return Ok(Self(Color::Rgb(r, g, b)));
+                }
+            }
+        }
+        text.parse::<u8>()
+            .map(|index| Self(Color::Indexed(index)))
+            .map_err(|_| anyhow!("{:?} is not a colour name, 0-255 or #rrggbb", text))
+    }
+}
+
+/// How a kind of cell that isn't part of a piece is drawn. `glyph`, when
+/// given, replaces the theme's glyph for these cells and must be two
+/// characters.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CellStyle {
+    pub colour: ThemeColour,
+    #[serde(default)]
+    pub glyph: Option<String>,
+}
+
+impl CellStyle {
+    fn new(colour: Color, glyph: Option<&str>) -> Self {
+        Self {
+            colour: ThemeColour(colour),
+            glyph: glyph.map(str::to_string),
+        }
+    }
+}
+
+/// The colour of each piece colour. Garbage is styled on its own.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub red: ThemeColour,
+    pub green: ThemeColour,
+    pub blue: ThemeColour,
+    pub yellow: ThemeColour,
+    pub cyan: ThemeColour,
+    pub magenta: ThemeColour,
+    pub orange: ThemeColour,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            red: ThemeColour(Color::Red),
+            green: ThemeColour(Color::Green),
+            blue: ThemeColour(Color::Blue),
+            yellow: ThemeColour(Color::Yellow),
+            cyan: ThemeColour(Color::Cyan),
+            magenta: ThemeColour(Color::Magenta),
+            orange: ThemeColour(Color::LightRed),
+        }
+    }
+}
+
+/// How the tui renderer draws cells, read from a TOML file such as:
+///
+/// ```toml
+/// glyph = "full-block"   # solid, brackets, full-block, rounded or half-block
+/// marks = "patterns"      # none, letters or patterns, see `Marks`
+/// ghost = { colour = "240", glyph = "░░" }
+/// garbage = { colour = "#808080" }
+/// target = { colour = "darkgray" }   # the finesse trainer's target
+///
+/// [colours]
+/// cyan = "#00f0f0"
+/// orange = "208"
+/// ```
+///
+/// Anything left out is as in the classic theme, solid blocks in the terminal's
+/// own colours.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub glyph: Glyph,
+    pub marks: Marks,
+    pub colours: Palette,
+    pub ghost: CellStyle,
+    pub garbage: CellStyle,
+    pub target: CellStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            glyph: Glyph::Solid,
+            marks: Marks::None,
+            colours: Palette::default(),
+            ghost: CellStyle::new(Color::DarkGray, Some("░░")),
+            garbage: CellStyle::new(Color::Gray, None),
+            target: CellStyle::new(Color::DarkGray, None),
+        }
+    }
+}
+
+impl Theme {
+    /// The names of the built-in themes, classic first.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        ["classic"]
+            .into_iter()
+            .chain(BUILT_IN.iter().map(|(name, _)| *name))
+    }
+
+    /// Reads `classic`, another built-in theme's name, or the path of a theme
+    /// file.
+    pub fn from_arg(arg: &str) -> Result<Self> {
+        if arg == "classic" {
+            return Ok(Self::default());
+        }
+        match BUILT_IN.iter().find(|(name, _)| *name == arg) {
+            Some((_, text)) => Self::from_toml(text),
+            None => Self::from_toml(&fs::read_to_string(arg)?),
+        }
+    }
+
+    fn from_toml(text: &str) -> Result<Self> {
+        let theme: Self = toml::from_str(text)?;
+        for style in [&theme.ghost, &theme.garbage, &theme.target] {
+            if let Some(ref glyph) = style.glyph {
+                if glyph.chars().count() != 2 {
+                    return Err(anyhow!("glyph {:?} is not two characters", glyph));
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    /// The colour blocks of `colour` are drawn in.
+    pub fn colour(&self, colour: Colour) -> Color {
+        let ThemeColour(colour) = match colour {
+            Colour::Red => self.colours.red,
+            Colour::Green => self.colours.green,
+            Colour::Blue => self.colours.blue,
+            Colour::Yellow => self.colours.yellow,
+            Colour::Cyan => self.colours.cyan,
+            Colour::Magenta => self.colours.magenta,
+            Colour::Orange => self.colours.orange,
+            Colour::Grey => self.garbage.colour,
+        };
+        colour
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use strum::IntoEnumIterator;
+    use tui::style::Color;
+
+    use super::{Glyph, Marks, Theme, ThemeColour, BUILT_IN};
+    use crate::tetromino::{Colour, TetrominoType};
+
+    #[test]
+    fn test_colours() {
+        let parse = |text: &str| ThemeColour::try_from(text.to_string()).map(|colour| colour.0);
+        assert_eq!(parse("lightred").unwrap(), Color::LightRed);
+        assert_eq!(parse("Dark Grey").unwrap(), Color::DarkGray);
+        assert_eq!(parse("208").unwrap(), Color::Indexed(208));
+        assert_eq!(parse("#00f0a0").unwrap(), Color::Rgb(0, 0xf0, 0xa0));
+        assert!(parse("#00f0a").is_err());
+        assert!(parse("#aééb").is_err());
+        assert!(parse("#+1+2+3").is_err());
+        assert!(parse("256").is_err());
+        assert!(parse("mauve").is_err());
+    }
+
+    #[test]
+    fn test_theme_file() {
+        let theme = Theme::from_toml(
+            r##"
+            glyph = "brackets"
+            garbage = { colour = "#808080", glyph = "▒▒" }
+
+            [colours]
+            cyan = "51"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.glyph, Glyph::Brackets);
+        assert_eq!(theme.colour(Colour::Cyan), Color::Indexed(51));
+        assert_eq!(theme.colour(Colour::Grey), Color::Rgb(128, 128, 128));
+        assert_eq!(theme.garbage.glyph.as_deref(), Some("▒▒"));
+        // The rest is classic.
+        assert_eq!(theme.colour(Colour::Orange), Color::LightRed);
+        assert_eq!(theme.ghost, Theme::default().ghost);
+
+        assert!(Theme::from_toml("glyph = \"sparkles\"").is_err());
+        assert!(Theme::from_toml("ghost = { colour = \"red\", glyph = \"o\" }").is_err());
+    }
+
+    #[test]
+    fn test_built_in_themes() {
+        for (name, _) in BUILT_IN {
+            Theme::from_arg(name).unwrap();
+        }
+        assert_eq!(Theme::from_arg("classic").unwrap(), Theme::default());
+        assert_eq!(
+            Theme::from_arg("half-block").unwrap().glyph,
+            Glyph::HalfBlock
+        );
+    }
+
+    #[test]
+    fn test_marks() {
+        assert_eq!(Marks::None.text(TetrominoType::T), None);
+        assert_eq!(Marks::Letters.text(TetrominoType::T), Some(['T', ' ']));
+        let patterns = TetrominoType::iter()
+            .map(|piece| Marks::Patterns.text(piece).unwrap())
+            .collect::<HashSet<_>>();
+        assert_eq!(patterns.len(), 7);
+        assert_eq!(
+            Marks::Patterns.text(TetrominoType::Custom('V')),
+            Some(['V', ' '])
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+
+use tui::{
+    backend::Backend,
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::Color,
+    text::Spans,
+    widgets::{Block, BorderType, Borders, Paragraph, Widget},
+    Frame,
+};
+
+use crate::{
+    positions::Position,
+    tetromino::{Colour, TetrominoType},
+    theme::{CellStyle, Glyph, Theme},
+    ui::{collapsing_cell_shown, preview_size, stats_lines, Preview, Renderer, UIState},
+};
+
+/// Characters across the stats panel, inside its border.
+const STATS_WIDTH: u16 = 24;
+
+/// Where each part of a game goes. Cells are twice as wide as they are tall,
+/// which looks about square in most terminal fonts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GameLayout {
+    /// Width of a cell in characters and height in half lines, since half block
+    /// themes fit two cells to a character.
+    cell: (u16, u16),
+    /// The well, including its border.
+    well: Rect,
+    meter: Rect,
+    hold: Rect,
+    next: Rect,
+    /// Left out when there's no room for it.
+    stats: Option<Rect>,
+}
+
+impl GameLayout {
+    /// Lays `state` out in `area` with the biggest cells that fit. Hold and the
+    /// stats go left of the well and the next queue right of it, or if that's
+    /// too wide, hold goes over the next queue and the stats are left out.
+    fn new(state: &UIState, area: Rect, half: bool) -> Option<Self> {
+        let largest = area.height.saturating_sub(2) * 2 / state.board.len().max(1) as u16;
+        (1..=largest)
+            .rev()
+            .find_map(|size| Self::arrange(state, area, size, true, half))
+            .or_else(|| {
+                (1..=largest)
+                    .rev()
+                    .find_map(|size| Self::arrange(state, area, size, false, half))
+            })
+    }
+
+    /// The space the narrow layout needs with the smallest cells.
+    fn min_size(state: &UIState, half: bool) -> (u16, u16) {
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: u16::MAX,
+            height: u16::MAX,
+        };
+        let layout = Self::arrange(state, area, 1, false, half)
+            .expect("everything fits in the biggest area");
+        (layout.hold.right() - layout.meter.x, layout.well.height)
+    }
+
+    /// The layout with cells `size` characters high, or half a character for
+    /// half block themes, if it fits in `area`.
+    fn arrange(state: &UIState, area: Rect, size: u16, wide: bool, half: bool) -> Option<Self> {
+        let cell = if half {
+            (size, size)
+        } else {
+            (size * 2, size * 2)
+        };
+        let lines = |cells: u16| Some(cells.checked_mul(cell.1)?.div_ceil(2));
+        let x_cells = state.board.first().map_or(0, Vec::len) as u16;
+        let y_cells = state.board.len() as u16;
+        let (preview_width, preview_height) = preview_size(state);
+
+        let well_width = x_cells.checked_mul(cell.0)?.checked_add(2)?;
+        let well_height = lines(y_cells)?.checked_add(2)?;
+        let box_width = preview_width.checked_mul(cell.0)?.checked_add(2)?;
+        let hold_height = lines(preview_height)?.checked_add(2)?;
+        let next_height = lines((state.next.len() as u16).checked_mul(preview_height + 1)?)?
+            .checked_add(2)?
+            .max(hold_height);
+        let left_width = if wide {
+            box_width.max(STATS_WIDTH + 2)
+        } else {
+            0
+        };
+        let width = left_width
+            .checked_add(cell.0)?
+            .checked_add(well_width)?
+            .checked_add(box_width)?;
+        let min_height = if wide {
+            hold_height
+        } else {
+            hold_height.checked_add(2)?
+        };
+        if width > area.width || well_height > area.height || min_height > well_height {
+            return None;
+        }
+
+        let x = area.x + (area.width - width) / 2;
+        let well = Rect::new(x + left_width + cell.0, area.y, well_width, well_height);
+        let meter = Rect::new(x + left_width, area.y + 1, cell.0, well_height - 2);
+        let right = well.right();
+        let layout = if wide {
+            Self {
+                cell,
+                well,
+                meter,
+                hold: Rect::new(x, area.y, left_width, hold_height),
+                next: Rect::new(right, area.y, box_width, next_height.min(well_height)),
+                stats: Some(Rect::new(
+                    x,
+                    area.y + hold_height,
+                    left_width,
+                    well_height - hold_height,
+                )),
+            }
+        } else {
+            Self {
+                cell,
+                well,
+                meter,
+                hold: Rect::new(right, area.y, box_width, hold_height),
+                next: Rect::new(
+                    right,
+                    area.y + hold_height,
+                    box_width,
+                    next_height.min(well_height - hold_height),
+                ),
+                stats: None,
+            }
+        };
+        Some(layout)
+    }
+
+    /// The well inside its border.
+    fn board(&self) -> Rect {
+        Rect::new(
+            self.well.x + 1,
+            self.well.y + 1,
+            self.well.width - 2,
+            self.well.height - 2,
+        )
+    }
+}
+
+/// What a cell is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Paint {
+    colour: Color,
+    /// The two characters across the cell, or none for a solid background.
+    glyph: Option<[char; 2]>,
+    /// Whether the glyph is drawn in black over a background of the colour,
+    /// rather than in the colour.
+    background: bool,
+}
+
+impl Paint {
+    fn new(colour: Color, glyph: Option<&str>) -> Self {
+        let glyph = glyph.map(|text| {
+            let mut chars = text.chars();
+            [chars.next().unwrap_or(' '), chars.next().unwrap_or(' ')]
+        });
+        Self {
+            colour,
+            glyph,
+            background: false,
+        }
+    }
+
+    /// A block of `piece`, or of garbage if it's grey. Marked pieces show
+    /// their mark instead of the theme's glyph.
+    fn block(theme: &Theme, colour: Colour, piece: Option<TetrominoType>) -> Self {
+        let mark = piece.and_then(|piece| theme.marks.text(piece));
+        match (colour, mark) {
+            (Colour::Grey, _) => Self::styled(theme, &theme.garbage),
+            (colour, Some(mark)) => Self {
+                colour: theme.colour(colour),
+                glyph: Some(mark),
+                background: theme.glyph.text().is_none(),
+            },
+            (colour, None) => Self::new(theme.colour(colour), theme.glyph.text()),
+        }
+    }
+
+    fn styled(theme: &Theme, style: &CellStyle) -> Self {
+        Self::new(
+            style.colour.0,
+            style.glyph.as_deref().or_else(|| theme.glyph.text()),
+        )
+    }
+}
+
+/// Painted cells, `cell` characters across and half lines down, from the top
+/// left of the area they're rendered in and `shift` half lines further down.
+/// Where a line of text is split between two cells, each half is drawn with a
+/// half block.
+struct Grid {
+    cells: HashMap<Position, Paint>,
+    cell: (u16, u16),
+    shift: u16,
+}
+
+impl Widget for Grid {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let at = |column: u16, half_line: u16| {
+            let half_line = half_line.checked_sub(self.shift)?;
+            let position = Position {
+                x: (column / self.cell.0) as i32,
+                y: (half_line / self.cell.1) as i32,
+            };
+            self.cells.get(&position)
+        };
+        for line in 0..area.height {
+            for column in 0..area.width {
+                let cell = buf.get_mut(area.x + column, area.y + line);
+                match (at(column, line * 2), at(column, line * 2 + 1)) {
+                    (Some(top), Some(bottom)) if top == bottom => match top.glyph {
+                        Some(glyph) => {
+                            let x = column % self.cell.0;
+                            cell.set_char(glyph[x as usize % 2]);
+                            if top.background {
+                                cell.set_fg(Color::Black).set_bg(top.colour)
+                            } else {
+                                cell.set_fg(top.colour)
+                            }
+                        }
+                        None => cell.set_char(' ').set_bg(top.colour),
+                    },
+                    (Some(top), Some(bottom)) => {
+                        cell.set_char('▀').set_fg(top.colour).set_bg(bottom.colour)
+                    }
+                    (Some(top), None) => cell.set_char('▀').set_fg(top.colour),
+                    (None, Some(bottom)) => cell.set_char('▄').set_fg(bottom.colour),
+                    (None, None) => continue,
+                };
+            }
+        }
+    }
+}
+
+/// Draws each cell as a block in the theme's colours.
+pub struct TuiRenderer {
+    theme: Theme,
+}
+
+impl<B: Backend> Renderer<B> for TuiRenderer {
+    fn min_size(&self, state: &UIState) -> (u16, u16) {
+        GameLayout::min_size(state, self.half())
+    }
+
+    fn board_area(&self, state: &UIState, area: Rect) -> Option<(Rect, u16)> {
+        GameLayout::new(state, area, self.half()).map(|layout| (layout.board(), layout.cell.0))
+    }
+
+    fn draw_game(&self, frame: &mut Frame<B>, state: &UIState, title: &str, area: Rect) {
+        let layout = match GameLayout::new(state, area, self.half()) {
+            Some(layout) => layout,
+            None => return,
+        };
+        let outline = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .title(title.to_string())
+            .title_alignment(Alignment::Center);
+        frame.render_widget(outline, layout.well);
+
+        let preview_size = preview_size(state);
+        self.previews(
+            frame,
+            "Hold",
+            state.hold.as_slice(),
+            preview_size,
+            &layout,
+            layout.hold,
+        );
+        self.previews(
+            frame,
+            "Next",
+            &state.next,
+            preview_size,
+            &layout,
+            layout.next,
+        );
+
+        if let Some(stats_area) = layout.stats {
+            let stats = stats_lines(&state.stats, &state.pieces)
+                .into_iter()
+                .map(Spans::from)
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                Paragraph::new(stats).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Stats")
+                        .title_alignment(Alignment::Center),
+                ),
+                stats_area,
+            );
+        }
+
+        self.garbage_meter(frame, state, &layout);
+
+        let grid = Grid {
+            cells: self.board(state),
+            cell: layout.cell,
+            shift: 0,
+        };
+        frame.render_widget(grid, layout.board());
+    }
+}
+
+impl TuiRenderer {
+    pub fn new(theme: Theme) -> Self {
+        Self { theme }
+    }
+
+    fn half(&self) -> bool {
+        self.theme.glyph == Glyph::HalfBlock
+    }
+
+    /// How each cell of the well that isn't empty is drawn.
+    fn board(&self, state: &UIState) -> HashMap<Position, Paint> {
+        let theme = &self.theme;
+        let contains = |blocks: &Option<Vec<Position>>, cell: &Position| {
+            blocks.as_ref().is_some_and(|blocks| blocks.contains(cell))
+        };
+        let mut cells = HashMap::new();
+        for (y, row) in state.board.iter().enumerate() {
+            for (x, cell_colour) in row.iter().enumerate() {
+                let cell = Position {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                let clearing = state
+                    .clearing
+                    .as_ref()
+                    .filter(|(rows, _)| rows.contains(&(y as u32)));
+                let paint = if let Some((_, progress)) = clearing {
+                    collapsing_cell_shown(x, row.len(), *progress)
+                        .then(|| Paint::new(Color::White, None))
+                } else if contains(&state.tetromino_blocks, &cell) {
+                    state
+                        .tetromino_colour
+                        .map(|colour| Paint::block(theme, colour, state.tetromino_type))
+                } else if let Some(colour) = cell_colour {
+                    let piece = state
+                        .board_pieces
+                        .get(y)
+                        .and_then(|row| row.get(x))
+                        .copied();
+                    Some(Paint::block(theme, *colour, piece.flatten()))
+                } else if contains(&state.target_blocks, &cell) {
+                    Some(Paint::styled(theme, &theme.target))
+                } else if contains(&state.ghost_blocks, &cell) {
+                    Some(Paint::styled(theme, &theme.ghost))
+                } else {
+                    None
+                };
+                if let Some(paint) = paint {
+                    cells.insert(cell, paint);
+                }
+            }
+        }
+        cells
+    }
+
+    /// Draws `pieces` one under another in a box, centred across it. Pieces that
+    /// don't fit are left off the bottom.
+    fn previews<B: Backend>(
+        &self,
+        frame: &mut Frame<B>,
+        title: &str,
+        pieces: &[Preview],
+        (width, height): (u16, u16),
+        layout: &GameLayout,
+        area: Rect,
+    ) {
+        let outline = Block::default()
+            .borders(Borders::ALL)
+            .title(title.to_string())
+            .title_alignment(Alignment::Center);
+        let inner = outline.inner(area);
+        frame.render_widget(outline, area);
+        let (cell_width, cell_height) = layout.cell;
+        for (slot, piece) in pieces.iter().enumerate() {
+            let (piece_width, piece_height) = piece.size();
+            let top = slot as u16 * (height + 1) * cell_height;
+            let bottom = top + piece_height * cell_height;
+            if bottom > inner.height * 2 {
+                break;
+            }
+            let paint = Paint::block(&self.theme, piece.colour, Some(piece.piece));
+            let grid = Grid {
+                cells: piece.blocks.iter().map(|block| (*block, paint)).collect(),
+                cell: layout.cell,
+                shift: top % 2,
+            };
+            let left = (width - piece_width) * cell_width / 2;
+            let rect = Rect::new(
+                inner.x + left,
+                inner.y + top / 2,
+                piece_width * cell_width,
+                bottom.div_ceil(2) - top / 2,
+            );
+            frame.render_widget(grid, rect);
+        }
+    }
+
+    /// Stacks the queued garbage up from the bottom of the meter, red for lines
+    /// that rise on the next lock and yellow for lines still on their way.
+    fn garbage_meter<B: Backend>(
+        &self,
+        frame: &mut Frame<B>,
+        state: &UIState,
+        layout: &GameLayout,
+    ) {
+        let y_cells = state.board.len() as u16;
+        let ready = state.garbage_ready as u16;
+        let total = (ready + state.garbage_waiting as u16).min(y_cells);
+        let cells = (0..total)
+            .map(|line| {
+                let colour = if line < ready {
+                    Color::Red
+                } else {
+                    Color::Yellow
+                };
+                let position = Position {
+                    x: 0,
+                    y: (y_cells - line - 1) as i32,
+                };
+                (position, Paint::new(colour, self.theme.glyph.text()))
+            })
+            .collect();
+        let grid = Grid {
+            cells,
+            cell: layout.cell,
+            shift: 0,
+        };
+        frame.render_widget(grid, layout.meter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+    use super::{GameLayout, Grid, Paint};
+    use crate::{
+        positions::Position,
+        tetromino::{Colour, TetrominoType},
+        theme::{Marks, Theme},
+        ui::{Preview, UIState},
+    };
+
+    fn state() -> UIState {
+        let i = Preview::new(
+            &(0..4).map(|x| Position { x, y: 0 }).collect::<Vec<_>>(),
+            Colour::Cyan,
+            TetrominoType::I,
+        );
+        UIState {
+            hold: Some(i.clone()),
+            next: vec![i; 5],
+            ..UIState::empty(10, 20)
+        }
+    }
+
+    #[test]
+    fn test_layout_fits() {
+        let state = state();
+        for half in [false, true] {
+            for (width, height) in [(34, 22), (60, 24), (80, 24), (100, 30), (200, 70)] {
+                let area = Rect::new(3, 2, width, height);
+                let layout = GameLayout::new(&state, area, half).unwrap();
+                assert_eq!(layout.cell.0, layout.cell.1);
+                let mut panels = vec![layout.well, layout.meter, layout.hold, layout.next];
+                panels.extend(layout.stats);
+                for (i, panel) in panels.iter().enumerate() {
+                    assert_eq!(area.union(*panel), area, "{:?} outside {:?}", panel, area);
+                    for other in panels[i + 1..].iter() {
+                        assert!(!panel.intersects(*other), "{:?} over {:?}", panel, other);
+                    }
+                }
+                let board = layout.board();
+                assert_eq!(board.width, 10 * layout.cell.0);
+                assert_eq!(board.height, 10 * layout.cell.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_grows_with_the_terminal() {
+        let state = state();
+        let layout = GameLayout::new(&state, Rect::new(0, 0, 34, 22), false).unwrap();
+        assert_eq!((layout.cell, layout.stats), ((2, 2), None));
+        let layout = GameLayout::new(&state, Rect::new(0, 0, 80, 24), false).unwrap();
+        assert_eq!(layout.cell, (2, 2));
+        assert!(layout.stats.is_some());
+        let layout = GameLayout::new(&state, Rect::new(0, 0, 200, 70), false).unwrap();
+        assert_eq!(layout.cell, (6, 6));
+        // Half blocks fit twice the rows in the same lines.
+        let layout = GameLayout::new(&state, Rect::new(0, 0, 80, 24), true).unwrap();
+        assert_eq!(layout.cell, (2, 2));
+        let layout = GameLayout::new(&state, Rect::new(0, 0, 60, 12), true).unwrap();
+        assert_eq!(layout.cell, (1, 1));
+    }
+
+    #[test]
+    fn test_too_small() {
+        let state = state();
+        assert_eq!(GameLayout::min_size(&state, false), (34, 22));
+        assert!(GameLayout::new(&state, Rect::new(0, 0, 33, 22), false).is_none());
+        assert!(GameLayout::new(&state, Rect::new(0, 0, 34, 21), false).is_none());
+        assert!(GameLayout::new(&state, Rect::new(0, 0, 0, 0), false).is_none());
+        assert_eq!(GameLayout::min_size(&state, true), (19, 12));
+    }
+
+    fn render(cells: &[((i32, i32), Paint)], cell: (u16, u16), shift: u16, area: Rect) -> Buffer {
+        let mut buf = Buffer::empty(area);
+        let cells = cells
+            .iter()
+            .map(|((x, y), paint)| (Position { x: *x, y: *y }, *paint))
+            .collect::<HashMap<_, _>>();
+        Grid { cells, cell, shift }.render(area, &mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_grid() {
+        let red = Paint::new(Color::Red, Some("[]"));
+        let blue = Paint::new(Color::Blue, None);
+
+        let buf = render(&[((1, 0), red)], (2, 2), 0, Rect::new(0, 0, 4, 1));
+        let symbols = buf.content.iter().map(|cell| cell.symbol.as_str());
+        assert_eq!(symbols.collect::<String>(), "  []");
+        assert_eq!(buf.get(2, 0).fg, Color::Red);
+
+        // Half blocks, with two cells to a line.
+        let buf = render(
+            &[((0, 0), red), ((0, 1), blue), ((1, 1), blue)],
+            (1, 1),
+            0,
+            Rect::new(0, 0, 2, 1),
+        );
+        let (left, right) = (buf.get(0, 0), buf.get(1, 0));
+        assert_eq!(
+            (left.symbol.as_str(), left.fg, left.bg),
+            ("▀", Color::Red, Color::Blue)
+        );
+        assert_eq!((right.symbol.as_str(), right.fg), ("▄", Color::Blue));
+
+        // Shifted down half a line.
+        let buf = render(&[((0, 0), blue)], (1, 2), 1, Rect::new(0, 0, 1, 2));
+        assert_eq!(buf.get(0, 0).symbol, "▄");
+        assert_eq!(buf.get(0, 1).symbol, "▀");
+    }
+
+    #[test]
+    fn test_marked_blocks() {
+        let theme = Theme {
+            marks: Marks::Letters,
+            ..Theme::default()
+        };
+        let t = Paint::block(&theme, Colour::Magenta, Some(TetrominoType::T));
+        assert_eq!(t.glyph, Some(['T', ' ']));
+        // On solid blocks, the mark is drawn over the piece's colour.
+        let buf = render(&[((0, 0), t)], (2, 2), 0, Rect::new(0, 0, 2, 1));
+        let cell = buf.get(0, 0);
+        assert_eq!(
+            (cell.symbol.as_str(), cell.fg, cell.bg),
+            ("T", Color::Black, Color::Magenta)
+        );
+        // Garbage and blocks of unknown pieces aren't marked.
+        let garbage = Paint::block(&theme, Colour::Grey, Some(TetrominoType::T));
+        assert_eq!(garbage.glyph, None);
+        assert_eq!(Paint::block(&theme, Colour::Red, None).glyph, None);
+    }
+}
@@ -1,118 +1,216 @@
-use std::io::{stdout, Stdout};
+use std::{
+    env,
+    io::{stdout, Stdout},
+    mem,
+};
 
 use anyhow::Result;
+use clap::ValueEnum;
 use crossterm::{
+    cursor::Show,
     event::{DisableMouseCapture, EnableMouseCapture},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
+use serde::{Deserialize, Serialize};
+use signal_hook::{consts::SIGTSTP, low_level::emulate_default_handler};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Alignment, Rect},
-    style::Style,
-    widgets::{Block, BorderType, Borders},
-    Terminal,
+    backend::{Backend, CrosstermBackend},
+    layout::Rect,
+    widgets::{Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use crate::{
+    ascii_render::AsciiRenderer,
+    crash, input,
+    menu::draw_controls,
+    mouse::{self, BoardArea},
+    positions::Position,
+    settings::Settings,
+    stats::Statistics,
+    tetramino_rules::Blocks,
+    tetromino::{Colour, TetrominoType},
+    tui_render::TuiRenderer,
 };
 
-use crate::{positions::Position, tetramino_rules::Blocks, tetromino::Colour};
+/// A piece shown outside the board, as its blocks from the top left corner of
+/// the smallest box around them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preview {
+    pub blocks: Blocks,
+    pub colour: Colour,
+    pub piece: TetrominoType,
+}
 
-#[derive(Debug)]
+impl Preview {
+    pub fn new(blocks: &[Position], colour: Colour, piece: TetrominoType) -> Self {
+        let left = blocks.iter().map(|pos| pos.x).min().unwrap_or(0);
+        let top = blocks.iter().map(|pos| pos.y).min().unwrap_or(0);
+        Self {
+            blocks: blocks
+                .iter()
+                .map(|pos| Position {
+                    x: pos.x - left,
+                    y: pos.y - top,
+                })
+                .collect(),
+            colour,
+            piece,
+        }
+    }
+
+    /// Width and height in cells.
+    pub fn size(&self) -> (u16, u16) {
+        let extent = |along: fn(&Position) -> i32| {
+            self.blocks.iter().map(along).max().map_or(0, |max| max + 1) as u16
+        };
+        (extent(|pos| pos.x), extent(|pos| pos.y))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UIState {
     pub game_over: bool,
     pub board: Vec<Vec<Option<Colour>>>,
+    /// The piece each filled cell of `board` was part of, where it's known
+    pub board_pieces: Vec<Vec<Option<TetrominoType>>>,
     pub tetromino_blocks: Option<Blocks>,
     pub tetromino_colour: Option<Colour>,
+    pub tetromino_type: Option<TetrominoType>,
+    /// Where the falling piece would land if dropped
+    pub ghost_blocks: Option<Blocks>,
+    /// Rows waiting to be cleared and how far through the line clear delay it is,
+    /// from 0 to 1
+    pub clearing: Option<(Vec<u32>, f64)>,
+    pub target_blocks: Option<Vec<Position>>,
     pub score: u32,
+    pub stats: Statistics,
+    pub pieces: Vec<TetrominoType>,
+    pub hold: Option<Preview>,
+    pub next: Vec<Preview>,
+    /// Queued garbage lines still waiting out the garbage delay
+    pub garbage_waiting: u32,
+    /// Queued garbage lines that rise when the next piece locks without a clear
+    pub garbage_ready: u32,
+}
+
+impl UIState {
+    /// The title over a single player game.
+    pub fn title(&self) -> String {
+        if self.game_over {
+            format!("GAME OVER :( ! Final Score: {}", self.score)
+        } else {
+            format!("TETRIS! Score: {}", self.score)
+        }
+    }
+
+    /// An empty board `width` by `height`, with no piece, previews or score.
+    pub fn empty(width: u32, height: u32) -> Self {
+        Self {
+            game_over: false,
+            board: vec![vec![None; width as usize]; height as usize],
+            board_pieces: vec![vec![None; width as usize]; height as usize],
+            tetromino_blocks: None,
+            tetromino_colour: None,
+            tetromino_type: None,
+            ghost_blocks: None,
+            clearing: None,
+            target_blocks: None,
+            score: 0,
+            stats: Statistics::default(),
+            pieces: vec![],
+            hold: None,
+            next: vec![],
+            garbage_waiting: 0,
+            garbage_ready: 0,
+        }
+    }
+}
+
+/// Draws a game into its part of a terminal with backend `B`.
+pub trait Renderer<B: Backend>: Send {
+    /// The smallest area `state` can be drawn in.
+    fn min_size(&self, state: &UIState) -> (u16, u16);
+
+    /// Draws `state` under `title`, in `area` of the frame. `area` is at least
+    /// `min_size`.
+    fn draw_game(&self, frame: &mut Frame<B>, state: &UIState, title: &str, area: Rect);
+
+    /// Where `draw_game` puts the board's cells in `area`, and how many
+    /// characters across each cell is.
+    fn board_area(&self, state: &UIState, area: Rect) -> Option<(Rect, u16)>;
 }
 
-pub struct UI {
-    terminal: Terminal<CrosstermBackend<Stdout>>,
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RendererKind {
+    /// Cells drawn as coloured blocks
+    Tui,
+    /// Cells drawn as `[]`, or their marks, in plain text with no colour
+    Ascii,
+}
+
+impl RendererKind {
+    /// The renderer asked for, or if none was, the ASCII one when `no_color`
+    /// (the `NO_COLOR` environment variable) is set and the tui one otherwise.
+    pub fn choose(asked: Option<RendererKind>, no_color: Option<String>) -> RendererKind {
+        match (asked, no_color) {
+            (Some(kind), _) => kind,
+            (None, Some(value)) if !value.is_empty() => RendererKind::Ascii,
+            (None, _) => RendererKind::Tui,
+        }
+    }
+}
+
+/// The game's screen, drawn through `B`. Only the crossterm backend can be
+/// started, stopped and suspended, as it's the one on a real terminal.
+pub struct UI<B: Backend = CrosstermBackend<Stdout>> {
+    terminal: Terminal<B>,
+    renderer: Box<dyn Renderer<B>>,
+    /// The games last drawn, to draw again after a suspend
+    last: Vec<(UIState, String)>,
+    /// Whether the game is played with the mouse as well as the keyboard
+    mouse: bool,
+    /// Between `start_ui` and `stop_ui`
+    started: bool,
+    /// The keys for the game, and whether they're shown over it
+    controls: Vec<(&'static str, &'static str)>,
+    help: bool,
 }
 
 impl UI {
-    pub fn new() -> Result<Self> {
-        let backend = CrosstermBackend::new(stdout());
-        let terminal = Terminal::new(backend)?;
-        Ok(Self { terminal })
+    pub fn new(settings: &Settings) -> Result<Self> {
+        Self::with_backend(CrosstermBackend::new(stdout()), settings)
     }
 
     pub fn start_ui(&mut self) -> Result<()> {
         enable_raw_mode()?;
-        self.terminal
-            .backend_mut()
-            .execute(EnterAlternateScreen)?
-            .execute(EnableMouseCapture)?;
-        Ok(())
-    }
-
-    pub fn draw(&mut self, state: &UIState) -> Result<()> {
-        self.terminal.draw(|frame| {
-            let frame_rect = frame.size();
-            let x_cells = state.board[0].len() as u16;
-            let y_cells = state.board.len() as u16;
-            let x_cell_size = (frame_rect.width / 3) / (x_cells + 2);
-            let y_cell_size = frame_rect.height / (y_cells + 2);
-            let border = Rect {
-                x: frame_rect.width / 3,
-                y: 0,
-                width: x_cell_size * (x_cells + 2),
-                height: y_cell_size * (y_cells + 2),
-            };
-            let game_area = Rect {
-                x: border.x + x_cell_size,
-                y: border.y + y_cell_size,
-                width: border.width - (x_cell_size * 2),
-                height: border.height - (y_cell_size * 2),
-            };
-            let title = if state.game_over {
-                format!("GAME OVER :( ! Final Score: {}", state.score)
-            } else {
-                format!("TETRIS! Score: {}", state.score)
-            };
-            let outline = Block::default()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Double)
-                .title(title)
-                .title_alignment(Alignment::Center);
-
-            frame.render_widget(outline, border);
-
-            for (y, row) in state.board.iter().enumerate() {
-                for (x, cell_colour) in row.iter().enumerate() {
-                    let cell = Position {
-                        x: x as i32,
-                        y: y as i32,
-                    };
-                    let rect = Self::cell_rectangle(&cell, x_cells, y_cells, &game_area);
-                    let mut block = Block::default().borders(Borders::NONE);
-
-                    if let Some(blocks) = state.tetromino_blocks {
-                        if blocks.contains(&cell) {
-                            let style = Style::default()
-                                .bg(Self::map_colour(&state.tetromino_colour.unwrap()));
-                            block = block.style(style);
-                        } else if let Some(colour) = cell_colour {
-                            let style = Style::default().bg(Self::map_colour(colour));
-                            block = block.style(style);
-                        }
-                    } else if let Some(colour) = cell_colour {
-                        let style = Style::default().bg(Self::map_colour(colour));
-                        block = block.style(style);
-                    }
-                    frame.render_widget(block, rect);
-                }
-            }
-        })?;
+        let backend = self.terminal.backend_mut();
+        backend.execute(EnterAlternateScreen)?;
+        if self.mouse {
+            backend.execute(EnableMouseCapture)?;
+        }
+        self.started = true;
         Ok(())
     }
 
-    fn cell_rectangle(pos: &Position, max_x: u16, max_y: u16, game_area: &Rect) -> Rect {
-        Rect {
-            x: game_area.x + pos.x as u16 * game_area.width / max_x,
-            y: game_area.y + pos.y as u16 * game_area.height / max_y,
-            width: game_area.width / max_x,
-            height: game_area.height / max_y,
+    /// Puts the terminal back and stops the process, as SIGTSTP would have,
+    /// then once it's resumed takes the terminal again and redraws the screen.
+    /// A screen other than the games is drawn again by whatever shows it.
+    pub fn suspend(&mut self) -> Result<()> {
+        if !self.started {
+            emulate_default_handler(SIGTSTP)?;
+            return Ok(());
+        }
+        self.stop_ui()?;
+        emulate_default_handler(SIGTSTP)?;
+        self.start_ui()?;
+        self.terminal.clear()?;
+        let last = mem::take(&mut self.last);
+        if last.is_empty() {
+            return Ok(());
         }
+        self.draw_versus(&last)
     }
 
     pub fn stop_ui(&mut self) -> Result<()> {
@@ -122,17 +220,197 @@ impl UI {
             .execute(DisableMouseCapture)?;
         self.terminal.show_cursor()?;
         disable_raw_mode()?;
+        self.started = false;
         Ok(())
     }
+}
+
+impl<B: Backend> UI<B> {
+    pub fn with_backend(backend: B, settings: &Settings) -> Result<Self> {
+        let terminal = Terminal::new(backend)?;
+        let marks = settings.marks.unwrap_or(settings.theme.marks);
+        let renderer: Box<dyn Renderer<B>> =
+            match RendererKind::choose(settings.renderer, env::var("NO_COLOR").ok()) {
+                RendererKind::Tui => {
+                    let mut theme = settings.theme.clone();
+                    if let Some(glyph) = settings.glyph {
+                        theme.glyph = glyph;
+                    }
+                    theme.marks = marks;
+                    Box::new(TuiRenderer::new(theme))
+                }
+                RendererKind::Ascii => Box::new(AsciiRenderer::new(marks)),
+            };
+        Ok(Self {
+            terminal,
+            renderer,
+            last: vec![],
+            mouse: settings.mouse,
+            started: false,
+            controls: input::controls(settings),
+            help: false,
+        })
+    }
 
-    fn map_colour(colour: &Colour) -> tui::style::Color {
-        match colour {
-            Colour::Blue => tui::style::Color::Blue,
-            Colour::Cyan => tui::style::Color::Cyan,
-            Colour::Green => tui::style::Color::Green,
-            Colour::Magenta => tui::style::Color::Magenta,
-            Colour::Red => tui::style::Color::Red,
-            Colour::Yellow => tui::style::Color::Yellow,
+    pub fn draw(&mut self, state: &UIState) -> Result<()> {
+        self.draw_versus(&[(state.clone(), state.title())])
+    }
+
+    /// Shows or hides the controls over the games.
+    pub fn toggle_help(&mut self) {
+        self.help = !self.help;
+    }
+
+    /// Draws each player's game in its own column, under the given titles, or
+    /// asks for a bigger terminal if they don't fit. Nothing is drawn without
+    /// any games, as a broadcast could send.
+    pub fn draw_versus(&mut self, games: &[(UIState, String)]) -> Result<()> {
+        if games.is_empty() {
+            return Ok(());
         }
+        crash::record(games);
+        self.last = games.to_vec();
+        let renderer = &self.renderer;
+        let controls = self.help.then_some(self.controls.as_slice());
+        self.terminal.draw(|frame| {
+            let area = frame.size();
+            let width = area.width / games.len() as u16;
+            let (min_width, min_height) = games
+                .iter()
+                .map(|(state, _)| renderer.min_size(state))
+                .fold((0, 0), |(w, h), (min_w, min_h)| {
+                    (w.max(min_w), h.max(min_h))
+                });
+            if width < min_width || area.height < min_height {
+                mouse::record(vec![]);
+                let message = format!(
+                    "Terminal too small: resize to at least {}x{}",
+                    min_width * games.len() as u16,
+                    min_height
+                );
+                frame.render_widget(Paragraph::new(message).wrap(Wrap { trim: true }), area);
+                return;
+            }
+            let mut boards = vec![];
+            for (player, (state, title)) in games.iter().enumerate() {
+                let column = Rect {
+                    x: area.x + width * player as u16,
+                    width,
+                    ..area
+                };
+                renderer.draw_game(frame, state, title, column);
+                boards.push(BoardArea::new(
+                    state,
+                    column,
+                    renderer.board_area(state, column),
+                ));
+            }
+            mouse::record(boards);
+            if let Some(controls) = controls {
+                draw_controls(frame, area, controls);
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Draws a screen other than the games, such as a menu.
+    pub fn draw_screen(&mut self, draw: impl FnOnce(&mut Frame<B>)) -> Result<()> {
+        self.last.clear();
+        mouse::record(vec![]);
+        self.terminal.draw(draw)?;
+        Ok(())
+    }
+}
+
+/// Puts the terminal back as `stop_ui` would, as far as it can, for when the
+/// UI can't be stopped normally.
+pub fn restore_terminal() {
+    let mut stdout = stdout();
+    let _ = stdout
+        .execute(LeaveAlternateScreen)
+        .and_then(|stdout| stdout.execute(DisableMouseCapture))
+        .and_then(|stdout| stdout.execute(Show));
+    let _ = disable_raw_mode();
+}
+
+/// Cells across and down the biggest piece in the hold and next panels, and at
+/// least enough for a tetromino, so the panels keep their size.
+pub fn preview_size(state: &UIState) -> (u16, u16) {
+    state
+        .hold
+        .iter()
+        .chain(state.next.iter())
+        .map(Preview::size)
+        .fold((4, 2), |(w, h), (width, height)| {
+            (w.max(width), h.max(height))
+        })
+}
+
+/// Cleared rows flash and shrink towards the middle over the line clear delay.
+pub fn collapsing_cell_shown(x: usize, width: usize, progress: f64) -> bool {
+    let from_middle = (x as f64 + 0.5 - width as f64 / 2.0).abs();
+    from_middle < (1.0 - progress) * width as f64 / 2.0
+}
+
+/// The lines of the stats panel shown beside each board.
+pub fn stats_lines(stats: &Statistics, pieces: &[TetrominoType]) -> Vec<String> {
+    let secs = stats.elapsed.as_secs_f64();
+    let mut lines = vec![
+        format!("Time   {}:{:04.1}", (secs / 60.0) as u32, secs % 60.0),
+        format!("Pieces {:<5} PPS {:.2}", stats.pieces, stats.pps()),
+        format!("Keys   {:<5} KPP {:.2}", stats.keys, stats.kpp()),
+        format!("Lines  {:<5} Level {}", stats.lines, stats.level()),
+        format!("Attack {:<5} APM {:.1}", stats.attack, stats.apm()),
+        format!("Finesse faults {}", stats.finesse_faults),
+        String::new(),
+    ];
+    lines.extend(
+        pieces
+            .iter()
+            .map(|type_| format!("{}      {}", type_.letter(), stats.count(*type_))),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use tui::backend::TestBackend;
+
+    use super::{RendererKind, UIState, UI};
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_draws_to_any_backend() {
+        let settings = Settings::parse_from(["tetris", "--renderer=ascii"]);
+        let mut ui = UI::with_backend(TestBackend::new(60, 30), &settings).unwrap();
+        ui.draw(&UIState::empty(10, 20)).unwrap();
+
+        let text = ui
+            .terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect::<String>();
+        assert!(text.contains("TETRIS! Score: 0"));
+    }
+
+    #[test]
+    fn test_no_color_picks_ascii() {
+        assert_eq!(RendererKind::choose(None, None), RendererKind::Tui);
+        assert_eq!(
+            RendererKind::choose(None, Some("1".to_string())),
+            RendererKind::Ascii
+        );
+        assert_eq!(
+            RendererKind::choose(None, Some(String::new())),
+            RendererKind::Tui
+        );
+        assert_eq!(
+            RendererKind::choose(Some(RendererKind::Tui), Some("1".to_string())),
+            RendererKind::Tui
+        );
     }
 }
@@ -0,0 +1,218 @@
+use std::{sync::mpsc::Receiver, time::Duration};
+
+use log::info;
+
+use crate::{
+    action::Action,
+    bot::Bot,
+    events::Event,
+    game::Tetris,
+    settings::{Mode, Settings},
+    ui::UIState,
+};
+
+pub const PLAYERS: usize = 2;
+
+/// How long a finished round stays on screen before the next one starts.
+pub const ROUND_BREAK: Duration = Duration::from_secs(2);
+
+/// A versus match played from this terminal, against players on it or online.
+pub trait Match: Send {
+    /// Plays `action` for the local player `player`.
+    fn apply(&mut self, player: usize, action: Action);
+    fn tick(&mut self, dt: Duration);
+    /// Each player's game with the title to show above it.
+    fn ui_states(&self) -> Vec<(UIState, String)>;
+}
+
+/// A local match between two games, passing the garbage each one sends to the
+/// other and counting rounds until a player has won `--first-to` of them. In
+/// versus-bot mode the second player is a `Bot`.
+pub struct Versus {
+    settings: Settings,
+    games: Vec<Tetris>,
+    events: Vec<Receiver<Event>>,
+    wins: [u32; PLAYERS],
+    round: u32,
+    /// Time left before the next round, once this one is over.
+    round_break: Option<Duration>,
+    bot: Option<Bot>,
+}
+
+impl Match for Versus {
+    /// Plays `action` for `player`, or starts a new match on restart. Switching
+    /// pieces would be cheating against an opponent, so it is ignored.
+    fn apply(&mut self, player: usize, action: Action) {
+        if action == Action::Restart {
+            *self = Versus::new(&self.settings);
+        } else if self.round_break.is_none() && action != Action::Switch {
+            self.games[player].apply(action);
+        }
+    }
+
+    fn tick(&mut self, dt: Duration) {
+        if self.winner().is_some() {
+            return;
+        }
+        if let Some(remaining) = self.round_break {
+            let remaining = remaining.saturating_sub(dt);
+            self.round_break = Some(remaining);
+            if remaining.is_zero() {
+                self.start_round();
+            }
+            return;
+        }
+
+        for game in self.games.iter_mut() {
+            game.tick(dt);
+        }
+        if let Some(bot) = self.bot.as_mut() {
+            bot.tick(dt, &mut self.games[1]);
+        }
+        self.exchange_garbage();
+        self.check_round();
+    }
+
+    fn ui_states(&self) -> Vec<(UIState, String)> {
+        self.games
+            .iter()
+            .enumerate()
+            .map(|(player, game)| {
+                // Kept short to fit over a board a third of the player's column wide.
+                let name = match self.bot {
+                    Some(_) if player == 1 => "Bot".to_string(),
+                    _ => format!("P{}", player + 1),
+                };
+                let title = if self.winner() == Some(player) {
+                    format!("{} WINS! r: rematch", name)
+                } else {
+                    let state = if game.game_over {
+                        "K.O.".to_string()
+                    } else {
+                        game.score.to_string()
+                    };
+                    format!(
+                        "{} | R{} | {}/{} | {}",
+                        name, self.round, self.wins[player], self.settings.first_to, state
+                    )
+                };
+                (game.ui_state(), title)
+            })
+            .collect()
+    }
+}
+
+impl Versus {
+    pub fn new(settings: &Settings) -> Self {
+        let mut versus = Self {
+            settings: settings.clone(),
+            games: vec![],
+            events: vec![],
+            wins: [0; PLAYERS],
+            round: 0,
+            round_break: None,
+            bot: (settings.mode == Mode::VersusBot)
+                .then(|| Bot::new(settings.bot_difficulty, settings.bot_pps)),
+        };
+        versus.start_round();
+        versus
+    }
+
+    /// The player who has won the match, if either has yet.
+    pub fn winner(&self) -> Option<usize> {
+        self.wins
+            .iter()
+            .position(|wins| *wins >= self.settings.first_to)
+    }
+
+    fn start_round(&mut self) {
+        self.round += 1;
+        info!("versus round {}", self.round);
+        self.games = (0..PLAYERS).map(|_| Tetris::new(&self.settings)).collect();
+        self.events = self.games.iter_mut().map(Tetris::subscribe).collect();
+        self.round_break = None;
+    }
+
+    fn exchange_garbage(&mut self) {
+        for player in 0..PLAYERS {
+            let sent = self.events[player]
+                .try_iter()
+                .map(|event| match event {
+                    Event::GarbageSent { lines } => lines,
+                    _ => 0,
+                })
+                .sum::<u32>();
+            if sent > 0 {
+                self.games[(player + 1) % PLAYERS].receive_garbage(sent);
+            }
+        }
+    }
+
+    /// Ends the round once someone has topped out, giving it to whoever is still
+    /// playing. A round where both top out together goes to nobody.
+    fn check_round(&mut self) {
+        if !self.games.iter().any(|game| game.game_over) {
+            return;
+        }
+        for (player, game) in self.games.iter().enumerate() {
+            if !game.game_over {
+                self.wins[player] += 1;
+            }
+        }
+        info!("versus round {} over, wins {:?}", self.round, self.wins);
+        if self.winner().is_none() {
+            self.round_break = Some(ROUND_BREAK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::{Match, Versus, ROUND_BREAK};
+    use crate::{action::Action, settings::Settings};
+
+    fn versus(args: &[&str]) -> Versus {
+        let base = ["tetris", "--mode=versus", "--width=4", "--height=6"];
+        Versus::new(&Settings::parse_from(base.iter().chain(args)))
+    }
+
+    #[test]
+    fn test_tetris_sends_garbage() {
+        let mut versus = versus(&["--garbage-delay=0"]);
+        let field = "queue: I\n....\n....\nGGG.\nGGG.\nGGG.\nGGG.";
+        versus.games[0].load_field(field.parse().unwrap()).unwrap();
+        versus.games[0].update();
+        for action in [Action::RotateClockwise, Action::MoveRight, Action::Drop] {
+            versus.apply(0, action);
+        }
+        versus.exchange_garbage();
+
+        assert_eq!(versus.games[0].stats.lines, 4);
+        assert_eq!(versus.games[1].ui_state().garbage_ready, 4);
+        assert_eq!(versus.games[0].ui_state().garbage_ready, 0);
+    }
+
+    #[test]
+    fn test_rounds_and_match() {
+        let mut versus = versus(&["--first-to=2"]);
+        versus.games[1].game_over = true;
+        versus.tick(Duration::from_millis(10));
+        assert_eq!(versus.wins, [1, 0]);
+        assert_eq!(versus.winner(), None);
+
+        versus.tick(ROUND_BREAK);
+        assert_eq!(versus.round, 2);
+        assert!(!versus.games[1].game_over);
+
+        versus.games[1].game_over = true;
+        versus.tick(Duration::from_millis(10));
+        assert_eq!(versus.winner(), Some(0));
+        let round = versus.round;
+        versus.tick(ROUND_BREAK);
+        assert_eq!(versus.round, round);
+    }
+}